@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::executor::ownership::{add_verified_nodes, OwnershipVerifier};
+use crate::executor::records::Records;
+use crate::id::{Id, EXPECTED_ID_LENGTH_IN_BYTES};
+use crate::kademlia::endpoint_from;
+use crate::lookup::IterativeLookup;
+use crate::metrics::PrometheusText;
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::puzzle::PuzzleOptions;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+use crate::server::AsyncConnectionHandler;
+use crate::store::record::RecordType;
+use crate::store::{Key, Store, VersionedValue};
+use crate::time::Clock;
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const JSON_CONTENT_TYPE: &str = "application/json";
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub(crate) struct AdminState {
+    pub(crate) current_node: Node,
+    pub(crate) routing_table: Arc<Table>,
+    pub(crate) store: Arc<dyn Store>,
+    pub(crate) async_network: Arc<AsyncNetwork>,
+    pub(crate) connection_handler: Arc<AsyncConnectionHandler>,
+    pub(crate) lookup: IterativeLookup,
+    pub(crate) ownership_verifier: OwnershipVerifier,
+    pub(crate) puzzle_options: PuzzleOptions,
+    pub(crate) records: Arc<Records>,
+    pub(crate) clock: Box<dyn Clock>,
+}
+
+struct AdminError {
+    status: u16,
+    description: String,
+}
+
+impl AdminError {
+    fn bad_request(description: String) -> Self {
+        AdminError {
+            status: 400,
+            description,
+        }
+    }
+
+    fn forbidden(description: String) -> Self {
+        AdminError {
+            status: 403,
+            description,
+        }
+    }
+
+    fn not_found(description: String) -> Self {
+        AdminError {
+            status: 404,
+            description,
+        }
+    }
+
+    fn bad_gateway(description: String) -> Self {
+        AdminError {
+            status: 502,
+            description,
+        }
+    }
+}
+
+pub(crate) async fn serve(tcp_listener: TcpListener, admin_state: Arc<AdminState>) {
+    loop {
+        match tcp_listener.accept().await {
+            Ok((tcp_stream, _)) => {
+                let admin_state = admin_state.clone();
+                tokio::spawn(async move { admin_state.handle(tcp_stream).await });
+            }
+            Err(err) => error!("could not accept an admin connection {:?}", err),
+        }
+    }
+}
+
+impl AdminState {
+    async fn handle(&self, mut tcp_stream: TcpStream) {
+        let (status, content_type, body) = match read_request(&mut tcp_stream).await {
+            Some(request) if !request.is_from_loopback() => {
+                warn!(
+                    "rejected admin request {} from a non loopback host or origin",
+                    request.request_line
+                );
+                let err = AdminError::forbidden(
+                    "only loopback hosts and origins are allowed".to_string(),
+                );
+                (
+                    err.status,
+                    JSON_CONTENT_TYPE,
+                    json!({ "error": err.description }).to_string(),
+                )
+            }
+            Some(AdminRequest { request_line, .. })
+                if request_line.starts_with("GET /metrics ") =>
+            {
+                (200, PROMETHEUS_CONTENT_TYPE, self.metrics())
+            }
+            Some(AdminRequest { request_line, .. }) => {
+                info!("received admin request {}", request_line);
+                let body = match self.route(&request_line).await {
+                    Ok(body) => (200, body),
+                    Err(err) => (err.status, json!({ "error": err.description })),
+                };
+                (body.0, JSON_CONTENT_TYPE, body.1.to_string())
+            }
+            None => (
+                400,
+                JSON_CONTENT_TYPE,
+                json!({ "error": "malformed request" }).to_string(),
+            ),
+        };
+        let response = format!(
+            "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            reason_phrase(status),
+            content_type,
+            body.len(),
+            body
+        );
+        if let Err(err) = tcp_stream.write_all(response.as_bytes()).await {
+            warn!("could not write the admin response, error {:?}", err);
+        }
+    }
+
+    async fn route(&self, request_line: &str) -> Result<Value, AdminError> {
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let target = parts.next().unwrap_or_default();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let parameters: HashMap<&str, String> = query
+            .split('&')
+            .filter_map(|parameter| parameter.split_once('='))
+            .map(|(name, value)| (name, percent_decode(value)))
+            .collect();
+        let parameter = |name: &str| {
+            parameters
+                .get(name)
+                .ok_or_else(|| AdminError::bad_request(format!("missing parameter {}", name)))
+        };
+
+        match (method, path) {
+            ("GET", "/buckets") => Ok(self.buckets()),
+            ("GET", "/store") => Ok(json!(self.store.stats())),
+            ("GET", "/waiting-list") => Ok(json!({
+                "pending_responses": self.async_network.waiting_list().pending_responses_count()
+            })),
+            ("GET", "/executors") => Ok(json!({
+                "message_executor_queue_depth": self.connection_handler.message_executor_queue_depth(),
+                "add_node_executor_queue_depth": self.connection_handler.add_node_executor_queue_depth(),
+            })),
+            ("POST", "/ping") => self.ping(parameter("address")?).await,
+            ("POST", "/put") => self.put(parameter("key")?, parameter("value")?).await,
+            ("GET", "/get") => self.get(parameter("key")?).await,
+            ("GET", "/find-node") => self.find_node(parameter("id")?).await,
+            ("POST", "/evict") => self.evict(parameter("id")?),
+            ("POST", "/refresh") => self.refresh(parameter("bucket")?).await,
+            _ => Err(AdminError::not_found(format!(
+                "no admin route for {} {}",
+                method, path
+            ))),
+        }
+    }
+
+    fn metrics(&self) -> String {
+        let mut text = PrometheusText::new();
+        self.async_network.metrics().render(&mut text);
+        self.async_network
+            .waiting_list()
+            .round_trip_times()
+            .render(&mut text);
+
+        let buckets = self.routing_table.buckets();
+        text.describe(
+            "kademlia_routing_table_contacts",
+            "gauge",
+            "Contacts in the routing table",
+        );
+        text.sample(
+            "kademlia_routing_table_contacts",
+            &[],
+            buckets.iter().map(|(_, nodes)| nodes.len()).sum::<usize>(),
+        );
+        text.describe(
+            "kademlia_routing_table_bucket_contacts",
+            "gauge",
+            "Contacts in each non-empty bucket",
+        );
+        for (bucket_index, nodes) in &buckets {
+            let bucket_index = bucket_index.to_string();
+            text.sample(
+                "kademlia_routing_table_bucket_contacts",
+                &[("bucket", bucket_index.as_str())],
+                nodes.len(),
+            );
+        }
+
+        let store_stats = self.store.stats();
+        text.describe("kademlia_store_keys", "gauge", "Keys in the store");
+        text.sample("kademlia_store_keys", &[], store_stats.keys);
+        text.describe(
+            "kademlia_store_tombstones",
+            "gauge",
+            "Tombstones in the store",
+        );
+        text.sample("kademlia_store_tombstones", &[], store_stats.tombstones);
+        text.describe(
+            "kademlia_store_value_bytes",
+            "gauge",
+            "Bytes held by values in the store",
+        );
+        text.sample("kademlia_store_value_bytes", &[], store_stats.value_bytes);
+
+        text.describe(
+            "kademlia_pending_responses",
+            "gauge",
+            "Requests waiting for a reply",
+        );
+        text.sample(
+            "kademlia_pending_responses",
+            &[],
+            self.async_network.waiting_list().pending_responses_count(),
+        );
+        text.describe(
+            "kademlia_executor_queue_depth",
+            "gauge",
+            "Messages queued in each executor",
+        );
+        text.sample(
+            "kademlia_executor_queue_depth",
+            &[("executor", "message")],
+            self.connection_handler.message_executor_queue_depth(),
+        );
+        text.sample(
+            "kademlia_executor_queue_depth",
+            &[("executor", "add_node")],
+            self.connection_handler.add_node_executor_queue_depth(),
+        );
+
+        text.describe(
+            "kademlia_messages_throttled_total",
+            "counter",
+            "Messages dropped by the rate limiter",
+        );
+        for (message_type, scope, count) in
+            self.connection_handler.rate_limiter().throttled_counts()
+        {
+            let message_type = format!("{:?}", message_type);
+            let scope = format!("{:?}", scope);
+            text.sample(
+                "kademlia_messages_throttled_total",
+                &[("type", message_type.as_str()), ("scope", scope.as_str())],
+                count,
+            );
+        }
+        text.into_string()
+    }
+
+    fn buckets(&self) -> Value {
+        let buckets: Vec<Value> = self
+            .routing_table
+            .buckets()
+            .iter()
+            .map(|(bucket_index, nodes)| {
+                json!({
+                    "index": bucket_index,
+                    "nodes": nodes
+                        .iter()
+                        .map(|node| self.contact_with_liveness_of(node))
+                        .collect::<Vec<Value>>(),
+                })
+            })
+            .collect();
+        let replacements: Vec<Value> = self
+            .routing_table
+            .replacements()
+            .iter()
+            .map(contact_of)
+            .collect();
+        json!({
+            "node": contact_of(&self.current_node),
+            "buckets": buckets,
+            "replacements": replacements,
+        })
+    }
+
+    fn contact_with_liveness_of(&self, node: &Node) -> Value {
+        let mut contact = contact_of(node);
+        if let Some(liveness) = self.routing_table.liveness_of(&node.id) {
+            contact["last_seen_millis"] = json!(liveness
+                .last_seen
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_millis() as u64)
+                .unwrap_or(0));
+            contact["consecutive_failures"] = json!(liveness.consecutive_failures);
+            contact["smoothed_round_trip_time_millis"] = json!(self
+                .async_network
+                .smoothed_round_trip_time(&node.endpoint)
+                .map(|round_trip_time| round_trip_time.as_millis() as u64));
+        }
+        contact
+    }
+
+    async fn ping(&self, address: &str) -> Result<Value, AdminError> {
+        let endpoint =
+            endpoint_from(address).map_err(|err| AdminError::bad_request(err.to_string()))?;
+        match self.ownership_verifier.discover(&endpoint).await {
+            Some(node) => Ok(contact_of(&node)),
+            None => Err(AdminError::bad_gateway(format!(
+                "no reply from the node at {}",
+                address
+            ))),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<Value, AdminError> {
+        let version = self.clock.now_millis();
+        let message = Message::store_type(
+            key.as_bytes().to_vec(),
+            value.as_bytes().to_vec(),
+            version,
+            self.current_node.clone(),
+        );
+        let stored = self
+            .records
+            .put(
+                Key::new(key.as_bytes().to_vec()),
+                VersionedValue::new(value.as_bytes().to_vec(), version),
+                message,
+            )
+            .await;
+        if !stored {
+            return Err(AdminError::bad_request(format!(
+                "the value can not replace the record stored for the key {}",
+                key
+            )));
+        }
+        Ok(json!({ "stored": true }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Value, AdminError> {
+        let value = self
+            .records
+            .latest_value(key.as_bytes(), RecordType::Plain)
+            .await
+            .map(|versioned_value| String::from_utf8_lossy(&versioned_value.value).to_string());
+        Ok(json!({ "key": key, "value": value }))
+    }
+
+    async fn find_node(&self, id: &str) -> Result<Value, AdminError> {
+        let id = Id::from_hex(id)
+            .filter(|id| id.as_bytes().len() == EXPECTED_ID_LENGTH_IN_BYTES)
+            .ok_or_else(|| AdminError::bad_request(format!("{} is not a hex node id", id)))?;
+        let contacts: Vec<Value> = self
+            .lookup
+            .find_node(&id)
+            .await
+            .iter()
+            .map(contact_of)
+            .collect();
+        Ok(json!({ "contacts": contacts }))
+    }
+
+    fn evict(&self, id: &str) -> Result<Value, AdminError> {
+        let id = Id::from_hex(id)
+            .ok_or_else(|| AdminError::bad_request(format!("{} is not a hex node id", id)))?;
+        let node = self
+            .routing_table
+            .all_nodes()
+            .into_iter()
+            .find(|node| node.id == id)
+            .ok_or_else(|| AdminError::not_found(format!("no contact with id {}", id.to_hex())))?;
+        Ok(json!({ "evicted": self.routing_table.remove(&node) }))
+    }
+
+    async fn refresh(&self, bucket_index: &str) -> Result<Value, AdminError> {
+        let (bucket_index, target) = bucket_index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| Some((index, self.routing_table.random_id_in(index)?)))
+            .ok_or_else(|| {
+                AdminError::bad_request(format!("{} is not a bucket index", bucket_index))
+            })?;
+        let nodes = self.lookup.find_node(&target).await;
+        let contacts_added = add_verified_nodes(
+            nodes,
+            &self.current_node,
+            &self.puzzle_options,
+            &self.ownership_verifier,
+            &self.routing_table,
+        )
+        .await;
+        self.routing_table.record_refresh(bucket_index);
+        Ok(json!({ "bucket": bucket_index, "contacts_added": contacts_added }))
+    }
+}
+
+struct AdminRequest {
+    request_line: String,
+    headers: HashMap<String, String>,
+}
+
+impl AdminRequest {
+    // Browsers send the host and the origin of the page, so a page on another site, or a name
+    // rebound to the loopback address, can not reach the admin interface.
+    fn is_from_loopback(&self) -> bool {
+        let host_is_loopback = self
+            .headers
+            .get("host")
+            .is_some_and(|host| is_loopback_host(host));
+        let origin_is_loopback = self.headers.get("origin").is_none_or(|origin| {
+            origin
+                .split_once("://")
+                .is_some_and(|(_, host)| is_loopback_host(host))
+        });
+        host_is_loopback && origin_is_loopback
+    }
+}
+
+async fn read_request(tcp_stream: &mut TcpStream) -> Option<AdminRequest> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = tcp_stream.read(&mut buffer).await.ok()?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return None;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8(request).ok()?;
+    let mut lines = request.lines();
+    let request_line = lines.next()?.to_string();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(AdminRequest {
+        request_line,
+        headers,
+    })
+}
+
+fn is_loopback_host(host: &str) -> bool {
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !host.ends_with(']') && port.bytes().all(|b| b.is_ascii_digit()) => {
+            name
+        }
+        _ => host,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn contact_of(node: &Node) -> Value {
+    json!({ "id": node.id.to_hex(), "address": node.endpoint.address() })
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = value
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        502 => "Bad Gateway",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use crate::admin::{is_loopback_host, percent_decode, percent_encode};
+    use crate::kademlia::Kademlia;
+
+    async fn raw_request(admin_address: &str, method: &str, target: &str) -> String {
+        raw_request_with_headers(admin_address, method, target, "host: localhost\r\n").await
+    }
+
+    async fn raw_request_with_headers(
+        admin_address: &str,
+        method: &str,
+        target: &str,
+        headers: &str,
+    ) -> String {
+        let mut tcp_stream = TcpStream::connect(admin_address).await.unwrap();
+        tcp_stream
+            .write_all(format!("{} {} HTTP/1.1\r\n{}\r\n", method, target, headers).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        tcp_stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn request(admin_address: &str, method: &str, target: &str) -> (u16, Value) {
+        let response = raw_request(admin_address, method, target).await;
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn decode_a_percent_encoded_value() {
+        assert_eq!("127.0.0.1:2379", percent_decode("127.0.0.1%3A2379"));
+        assert_eq!("100%", percent_decode("100%"));
+    }
+
+    #[test]
+    fn encode_a_value_that_decodes_back() {
+        assert_eq!("127.0.0.1%3A2379", percent_encode("127.0.0.1:2379"));
+        assert_eq!("a b&c=d", percent_decode(&percent_encode("a b&c=d")));
+    }
+
+    #[test]
+    fn accept_only_loopback_hosts() {
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("127.0.0.1:2379"));
+        assert!(is_loopback_host("[::1]:2379"));
+        assert!(is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("attacker.example:2379"));
+        assert!(!is_loopback_host("10.0.0.1"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn reject_requests_from_other_hosts_and_origins() {
+        let kademlia = Kademlia::builder().admin_port(0).start().await.unwrap();
+        let admin_address = kademlia.admin_address().unwrap();
+
+        let rebound = raw_request_with_headers(
+            &admin_address,
+            "GET",
+            "/buckets",
+            "host: attacker.example\r\n",
+        )
+        .await;
+        assert!(rebound.starts_with("HTTP/1.1 403 Forbidden"));
+
+        let cross_site = raw_request_with_headers(
+            &admin_address,
+            "POST",
+            "/refresh?bucket=0",
+            "host: localhost\r\norigin: https://attacker.example\r\n",
+        )
+        .await;
+        assert!(cross_site.starts_with("HTTP/1.1 403 Forbidden"));
+
+        let without_host =
+            raw_request_with_headers(&admin_address, "POST", "/refresh?bucket=0", "").await;
+        assert!(without_host.starts_with("HTTP/1.1 403 Forbidden"));
+
+        let same_origin = raw_request_with_headers(
+            &admin_address,
+            "GET",
+            "/store",
+            "host: localhost\r\norigin: http://localhost:8080\r\n",
+        )
+        .await;
+        assert!(same_origin.starts_with("HTTP/1.1 200 OK"));
+
+        kademlia.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn inspect_a_running_node() {
+        let first = Kademlia::builder().admin_port(0).start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+        second
+            .put(b"kademlia", b"distributed hash table")
+            .await
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let admin_address = first.admin_address().unwrap();
+        let (status, buckets) = request(&admin_address, "GET", "/buckets").await;
+        assert_eq!(200, status);
+        assert_eq!(
+            second.address(),
+            buckets["buckets"][0]["nodes"][0]["address"]
+        );
+        assert_eq!(0, buckets["buckets"][0]["nodes"][0]["consecutive_failures"]);
+        assert_eq!(0, buckets["replacements"].as_array().unwrap().len());
+
+        let (_, store) = request(&admin_address, "GET", "/store").await;
+        assert_eq!(1, store["keys"]);
+
+        let (_, waiting_list) = request(&admin_address, "GET", "/waiting-list").await;
+        assert_eq!(0, waiting_list["pending_responses"]);
+
+        let (_, executors) = request(&admin_address, "GET", "/executors").await;
+        assert_eq!(0, executors["message_executor_queue_depth"]);
+
+        let (status, _) = request(&admin_address, "GET", "/unknown").await;
+        assert_eq!(404, status);
+
+        let metrics = raw_request(&admin_address, "GET", "/metrics").await;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK\r\ncontent-type: text/plain"));
+        assert!(metrics.contains("kademlia_messages_received_total{type=\"Store\"} 1\n"));
+        assert!(metrics.contains("kademlia_routing_table_contacts 1\n"));
+        assert!(metrics.contains("kademlia_store_keys 1\n"));
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn control_a_running_node() {
+        let first = Kademlia::builder().admin_port(0).start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let admin_address = first.admin_address().unwrap();
+        let (status, contact) = request(
+            &admin_address,
+            "POST",
+            &format!("/ping?address={}", second.address().replace(':', "%3A")),
+        )
+        .await;
+        assert_eq!(200, status);
+        assert_eq!(second.address(), contact["address"]);
+
+        let second_id = contact["id"].as_str().unwrap().to_string();
+        let (status, evicted) =
+            request(&admin_address, "POST", &format!("/evict?id={}", second_id)).await;
+        assert_eq!(200, status);
+        assert_eq!(true, evicted["evicted"]);
+
+        let (_, buckets) = request(&admin_address, "GET", "/buckets").await;
+        assert_eq!(0, buckets["buckets"].as_array().unwrap().len());
+
+        let (status, _) = request(&admin_address, "POST", "/refresh?bucket=160").await;
+        assert_eq!(400, status);
+
+        let (status, _) = request(&admin_address, "POST", "/ping").await;
+        assert_eq!(400, status);
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+}
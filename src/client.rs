@@ -0,0 +1,173 @@
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::admin::percent_encode;
+use crate::id::{node_id_from_hex, node_id_to_hex, EXPECTED_ID_LENGTH_IN_BYTES};
+use crate::kademlia::{Contact, KademliaError};
+
+// Sends requests to the admin interface of a running node. The node runs the lookups and the
+// replication, so the client never joins the network nor becomes a contact of the node.
+pub struct Client {
+    admin_address: String,
+}
+
+impl Client {
+    pub fn new(admin_address: impl Into<String>) -> Self {
+        Client {
+            admin_address: admin_address.into(),
+        }
+    }
+
+    pub async fn ping(&self, address: &str) -> Result<Contact, KademliaError> {
+        let reply = self
+            .request(
+                "POST",
+                &format!("/ping?address={}", percent_encode(address)),
+            )
+            .await?;
+        contact_from(&reply)
+    }
+
+    pub async fn put(&self, key: &str, value: &str) -> Result<(), KademliaError> {
+        self.request(
+            "POST",
+            &format!(
+                "/put?key={}&value={}",
+                percent_encode(key),
+                percent_encode(value)
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>, KademliaError> {
+        let reply = self
+            .request("GET", &format!("/get?key={}", percent_encode(key)))
+            .await?;
+        Ok(reply["value"].as_str().map(|value| value.to_string()))
+    }
+
+    pub async fn find_node(&self, node_id: &[u8]) -> Result<Vec<Contact>, KademliaError> {
+        if node_id.len() != EXPECTED_ID_LENGTH_IN_BYTES {
+            return Err(KademliaError::InvalidArgument(format!(
+                "node id must be {} bytes, found {}",
+                EXPECTED_ID_LENGTH_IN_BYTES,
+                node_id.len()
+            )));
+        }
+        let reply = self
+            .request("GET", &format!("/find-node?id={}", node_id_to_hex(node_id)))
+            .await?;
+        reply["contacts"]
+            .as_array()
+            .ok_or_else(|| self.unexpected_reply())?
+            .iter()
+            .map(contact_from)
+            .collect()
+    }
+
+    async fn request(&self, method: &str, target: &str) -> Result<Value, KademliaError> {
+        let mut tcp_stream = TcpStream::connect(&self.admin_address).await?;
+        tcp_stream
+            .write_all(
+                format!(
+                    "{} {} HTTP/1.1\r\nhost: {}\r\nconnection: close\r\n\r\n",
+                    method, target, self.admin_address
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        tcp_stream.read_to_string(&mut response).await?;
+
+        let status = response
+            .get(9..12)
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| self.unexpected_reply())?;
+        let body: Value = response
+            .split_once("\r\n\r\n")
+            .and_then(|(_, body)| serde_json::from_str(body).ok())
+            .ok_or_else(|| self.unexpected_reply())?;
+        let description = || body["error"].as_str().unwrap_or_default().to_string();
+        match status {
+            200 => Ok(body),
+            400 => Err(KademliaError::InvalidArgument(description())),
+            _ => Err(KademliaError::Network(description())),
+        }
+    }
+
+    fn unexpected_reply(&self) -> KademliaError {
+        KademliaError::Network(format!(
+            "unexpected reply from the admin interface at {}",
+            self.admin_address
+        ))
+    }
+}
+
+fn contact_from(reply: &Value) -> Result<Contact, KademliaError> {
+    let node_id = reply["id"].as_str().and_then(node_id_from_hex);
+    let address = reply["address"].as_str();
+    match (node_id, address) {
+        (Some(node_id), Some(address)) => Ok(Contact::new_with_id(node_id, address.to_string())),
+        _ => Err(KademliaError::Network(format!(
+            "malformed contact {} in the admin reply",
+            reply
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::client::Client;
+    use crate::kademlia::{Kademlia, KademliaError};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn put_on_one_node_and_get_from_another() {
+        let first = Kademlia::builder().admin_port(0).start().await.unwrap();
+        let second = Kademlia::builder().admin_port(0).start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        Client::new(first.admin_address().unwrap())
+            .put("kademlia", "distributed hash table")
+            .await
+            .unwrap();
+
+        let client = Client::new(second.admin_address().unwrap());
+        assert_eq!(
+            Some("distributed hash table".to_string()),
+            client.get("kademlia").await.unwrap()
+        );
+        assert_eq!(None, client.get("distributed hash table").await.unwrap());
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn ping_a_node_and_find_its_contacts() {
+        let first = Kademlia::builder().admin_port(0).start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+        let client = Client::new(first.admin_address().unwrap());
+
+        let contact = client.ping(&second.address()).await.unwrap();
+        assert_eq!(second.node_id(), contact.node_id());
+
+        let contacts = client.find_node(second.node_id()).await.unwrap();
+        assert!(contacts
+            .iter()
+            .any(|contact| contact.node_id() == second.node_id()));
+        assert!(matches!(
+            client.find_node(b"short").await,
+            Err(KademliaError::InvalidArgument(_))
+        ));
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::executor::health::HealthProberOptions;
+use crate::lookup::LookupOptions;
+use crate::net::connection::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::net::message::MessageTypes;
+use crate::net::puzzle::PuzzleOptions;
+use crate::net::rtt::{DEFAULT_MAX_RESPONSE_TIMEOUT, DEFAULT_MIN_RESPONSE_TIMEOUT};
+use crate::net::wait::WaitingListOptions;
+use crate::routing::diversity::DiversityLimits;
+use crate::routing::liveness::DEFAULT_STALE_AFTER_FAILURES;
+use crate::routing::proximity::ProximityPolicy;
+use crate::routing::{RoutingLayout, TableOptions, MAX_BUCKET_CAPACITY};
+use crate::server::limit::{RateLimit, RateLimitOptions, REQUEST_MESSAGE_TYPES};
+
+#[derive(Debug)]
+pub(crate) enum ConfigErrorKind {
+    Io(Error),
+    ParseError(String),
+    InvalidValue(String),
+}
+
+impl From<Error> for ConfigErrorKind {
+    fn from(err: Error) -> Self {
+        ConfigErrorKind::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigErrorKind {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigErrorKind::ParseError(err.to_string())
+    }
+}
+
+impl Display for ConfigErrorKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigErrorKind::Io(err) => write!(formatter, "io error: {}", err),
+            ConfigErrorKind::ParseError(description) => {
+                write!(formatter, "parse error: {}", description)
+            }
+            ConfigErrorKind::InvalidValue(description) => {
+                write!(formatter, "invalid value: {}", description)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    pub k: usize,
+    pub alpha: usize,
+    pub disjoint_paths: usize,
+    pub message_executor_queue_size: usize,
+    pub add_node_executor_queue_size: usize,
+    pub response_timeout_millis: u64,
+    pub min_response_timeout_millis: u64,
+    pub max_response_timeout_millis: u64,
+    pub expired_responses_check_interval_millis: u64,
+    pub refresh_interval_secs: u64,
+    pub republish_interval_secs: u64,
+    pub table_persist_interval_secs: u64,
+    pub max_message_size: usize,
+    pub static_puzzle_difficulty: u32,
+    pub dynamic_puzzle_difficulty: u32,
+    pub routing_layout: RoutingLayout,
+    pub proximity_policy: ProximityPolicy,
+    pub max_contacts_per_ip_in_bucket: usize,
+    pub max_contacts_per_subnet_in_bucket: usize,
+    pub max_contacts_per_ip_in_table: usize,
+    pub max_contacts_per_subnet_in_table: usize,
+    pub stale_after_failures: u32,
+    pub health_check_interval_secs: u64,
+    pub health_check_max_probes: usize,
+    pub unresponsive_after_secs: u64,
+    pub per_source_rate_limit_capacity: u32,
+    pub per_source_rate_limit_refill_per_second: u32,
+    pub per_ip_rate_limit_capacity: u32,
+    pub per_ip_rate_limit_refill_per_second: u32,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            k: MAX_BUCKET_CAPACITY,
+            alpha: 3,
+            disjoint_paths: 1,
+            message_executor_queue_size: 100,
+            add_node_executor_queue_size: 100,
+            response_timeout_millis: 120_000,
+            min_response_timeout_millis: DEFAULT_MIN_RESPONSE_TIMEOUT.as_millis() as u64,
+            max_response_timeout_millis: DEFAULT_MAX_RESPONSE_TIMEOUT.as_millis() as u64,
+            expired_responses_check_interval_millis: 100,
+            refresh_interval_secs: 3600,
+            republish_interval_secs: 86400,
+            table_persist_interval_secs: 60,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            static_puzzle_difficulty: 0,
+            dynamic_puzzle_difficulty: 0,
+            routing_layout: RoutingLayout::Fixed,
+            proximity_policy: ProximityPolicy::LowestRoundTripTime,
+            max_contacts_per_ip_in_bucket: DiversityLimits::default().per_ip_in_bucket,
+            max_contacts_per_subnet_in_bucket: DiversityLimits::default().per_subnet_in_bucket,
+            max_contacts_per_ip_in_table: DiversityLimits::default().per_ip_in_table,
+            max_contacts_per_subnet_in_table: DiversityLimits::default().per_subnet_in_table,
+            stale_after_failures: DEFAULT_STALE_AFTER_FAILURES,
+            health_check_interval_secs: 60,
+            health_check_max_probes: 8,
+            unresponsive_after_secs: 900,
+            per_source_rate_limit_capacity: 0,
+            per_source_rate_limit_refill_per_second: 0,
+            per_ip_rate_limit_capacity: 0,
+            per_ip_rate_limit_refill_per_second: 0,
+        }
+    }
+}
+
+impl NodeConfig {
+    pub(crate) fn load(file_path: &Path) -> Result<Self, ConfigErrorKind> {
+        Self::from_toml(&fs::read_to_string(file_path)?)
+    }
+
+    pub(crate) fn from_toml(content: &str) -> Result<Self, ConfigErrorKind> {
+        let config: NodeConfig = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub(crate) fn lookup_options(&self) -> LookupOptions {
+        LookupOptions::new(self.alpha, self.k, self.disjoint_paths)
+    }
+
+    pub(crate) fn waiting_list_options(&self) -> WaitingListOptions {
+        WaitingListOptions::new(
+            Duration::from_millis(self.response_timeout_millis),
+            Duration::from_millis(self.expired_responses_check_interval_millis),
+        )
+        .with_response_timeout_bounds(
+            Duration::from_millis(self.min_response_timeout_millis),
+            Duration::from_millis(self.max_response_timeout_millis),
+        )
+    }
+
+    pub(crate) fn puzzle_options(&self) -> PuzzleOptions {
+        PuzzleOptions::new(
+            self.static_puzzle_difficulty,
+            self.dynamic_puzzle_difficulty,
+        )
+    }
+
+    pub(crate) fn table_options(&self) -> TableOptions {
+        TableOptions::new(
+            self.k,
+            self.diversity_limits(),
+            self.routing_layout,
+            self.stale_after_failures,
+            self.proximity_policy,
+        )
+    }
+
+    // A zero limit leaves the contacts per ip or subnet unlimited, a single host or subnet
+    // deployment cannot fill its buckets otherwise.
+    pub(crate) fn diversity_limits(&self) -> DiversityLimits {
+        let unlimited_if_zero = |limit: usize| if limit == 0 { usize::MAX } else { limit };
+        DiversityLimits::new(
+            unlimited_if_zero(self.max_contacts_per_ip_in_bucket),
+            unlimited_if_zero(self.max_contacts_per_subnet_in_bucket),
+            unlimited_if_zero(self.max_contacts_per_ip_in_table),
+            unlimited_if_zero(self.max_contacts_per_subnet_in_table),
+        )
+    }
+
+    pub(crate) fn health_prober_options(&self) -> Option<HealthProberOptions> {
+        if self.health_check_interval_secs == 0 {
+            return None;
+        }
+        Some(HealthProberOptions::new(
+            Duration::from_secs(self.health_check_interval_secs),
+            self.health_check_max_probes,
+            Duration::from_secs(self.unresponsive_after_secs),
+        ))
+    }
+
+    // A zero capacity leaves the requests unlimited, otherwise every request type gets a bucket
+    // of its own per source node and per ip.
+    pub(crate) fn rate_limit_options(&self) -> RateLimitOptions {
+        let limits = |capacity: u32, refill_per_second: u32| -> HashMap<MessageTypes, RateLimit> {
+            if capacity == 0 {
+                return HashMap::new();
+            }
+            REQUEST_MESSAGE_TYPES
+                .iter()
+                .map(|message_type| (*message_type, RateLimit::new(capacity, refill_per_second)))
+                .collect()
+        };
+        RateLimitOptions::new(
+            limits(
+                self.per_source_rate_limit_capacity,
+                self.per_source_rate_limit_refill_per_second,
+            ),
+            limits(
+                self.per_ip_rate_limit_capacity,
+                self.per_ip_rate_limit_refill_per_second,
+            ),
+        )
+    }
+
+    pub(crate) fn refresh_interval(&self) -> Option<Duration> {
+        if self.refresh_interval_secs == 0 {
+            return None;
+        }
+        Some(Duration::from_secs(self.refresh_interval_secs))
+    }
+
+    pub(crate) fn republish_interval(&self) -> Option<Duration> {
+        if self.republish_interval_secs == 0 {
+            return None;
+        }
+        Some(Duration::from_secs(self.republish_interval_secs))
+    }
+
+    pub(crate) fn table_persist_interval(&self) -> Duration {
+        Duration::from_secs(self.table_persist_interval_secs)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ConfigErrorKind> {
+        let positive = [
+            ("k", self.k),
+            ("alpha", self.alpha),
+            ("disjoint_paths", self.disjoint_paths),
+            (
+                "message_executor_queue_size",
+                self.message_executor_queue_size,
+            ),
+            (
+                "add_node_executor_queue_size",
+                self.add_node_executor_queue_size,
+            ),
+            ("max_message_size", self.max_message_size),
+            ("stale_after_failures", self.stale_after_failures as usize),
+            ("health_check_max_probes", self.health_check_max_probes),
+            (
+                "table_persist_interval_secs",
+                self.table_persist_interval_secs as usize,
+            ),
+            (
+                "expired_responses_check_interval_millis",
+                self.expired_responses_check_interval_millis as usize,
+            ),
+        ];
+        for (name, value) in positive {
+            if value == 0 {
+                return Err(ConfigErrorKind::InvalidValue(format!(
+                    "{} must be greater than 0",
+                    name
+                )));
+            }
+        }
+        if self.disjoint_paths > self.k {
+            return Err(ConfigErrorKind::InvalidValue(format!(
+                "disjoint_paths {} must not exceed k {}",
+                self.disjoint_paths, self.k
+            )));
+        }
+        let rate_limits = [
+            (
+                "per_source_rate_limit_refill_per_second",
+                self.per_source_rate_limit_capacity,
+                self.per_source_rate_limit_refill_per_second,
+            ),
+            (
+                "per_ip_rate_limit_refill_per_second",
+                self.per_ip_rate_limit_capacity,
+                self.per_ip_rate_limit_refill_per_second,
+            ),
+        ];
+        for (name, capacity, refill_per_second) in rate_limits {
+            if capacity > 0 && refill_per_second == 0 {
+                return Err(ConfigErrorKind::InvalidValue(format!(
+                    "{} must be greater than 0 with a rate limit capacity",
+                    name
+                )));
+            }
+        }
+        if self.min_response_timeout_millis > self.max_response_timeout_millis {
+            return Err(ConfigErrorKind::InvalidValue(format!(
+                "min_response_timeout_millis {} must not exceed max_response_timeout_millis {}",
+                self.min_response_timeout_millis, self.max_response_timeout_millis
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::config::NodeConfig;
+    use crate::net::message::MessageTypes;
+    use crate::routing::diversity::DiversityLimits;
+    use crate::routing::proximity::ProximityPolicy;
+    use crate::routing::RoutingLayout;
+
+    #[test]
+    fn load_defaults_for_missing_values() {
+        let config = NodeConfig::from_toml("k = 20").unwrap();
+
+        assert_eq!(20, config.k);
+        assert_eq!(NodeConfig::default().alpha, config.alpha);
+    }
+
+    #[test]
+    fn load_a_config_file() {
+        let file_path =
+            env::temp_dir().join(format!("kademlia-{}-config.toml", std::process::id()));
+        fs::write(
+            &file_path,
+            "k = 16\nalpha = 4\nresponse_timeout_millis = 500\nmax_message_size = 1024\n",
+        )
+        .unwrap();
+
+        let config = NodeConfig::load(&file_path);
+        let _ = fs::remove_file(&file_path);
+
+        let config = config.unwrap();
+        assert_eq!(16, config.k);
+        assert_eq!(4, config.alpha);
+        assert_eq!(1024, config.max_message_size);
+        assert_eq!(
+            Duration::from_millis(500),
+            config.waiting_list_options().expire_pending_responses_after
+        );
+    }
+
+    #[test]
+    fn load_a_config_with_a_tree_routing_layout() {
+        let config = NodeConfig::from_toml("routing_layout = \"tree\"").unwrap();
+
+        assert_eq!(RoutingLayout::Tree, config.routing_layout);
+        assert_eq!(RoutingLayout::Fixed, NodeConfig::default().routing_layout);
+        assert!(NodeConfig::from_toml("routing_layout = \"flat\"").is_err());
+    }
+
+    #[test]
+    fn load_a_config_with_response_timeout_bounds() {
+        let config = NodeConfig::from_toml(
+            "min_response_timeout_millis = 50\nmax_response_timeout_millis = 5000",
+        )
+        .unwrap();
+
+        let options = config.waiting_list_options();
+        assert_eq!(Duration::from_millis(50), options.min_response_timeout);
+        assert_eq!(Duration::from_secs(5), options.max_response_timeout);
+        assert!(NodeConfig::from_toml(
+            "min_response_timeout_millis = 500\nmax_response_timeout_millis = 50"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn load_a_config_with_proximity_selection_disabled() {
+        let config = NodeConfig::from_toml("proximity_policy = \"disabled\"").unwrap();
+
+        assert_eq!(ProximityPolicy::Disabled, config.proximity_policy);
+        assert_eq!(
+            ProximityPolicy::Disabled,
+            config.table_options().proximity_policy
+        );
+        assert_eq!(
+            ProximityPolicy::LowestRoundTripTime,
+            NodeConfig::default().proximity_policy
+        );
+    }
+
+    #[test]
+    fn load_a_config_with_diversity_limits() {
+        let config = NodeConfig::from_toml(
+            "max_contacts_per_ip_in_bucket = 4\nmax_contacts_per_ip_in_table = 0",
+        )
+        .unwrap();
+
+        let limits = config.diversity_limits();
+        assert_eq!(4, limits.per_ip_in_bucket);
+        assert_eq!(usize::MAX, limits.per_ip_in_table);
+        assert_eq!(
+            DiversityLimits::default().per_subnet_in_bucket,
+            limits.per_subnet_in_bucket
+        );
+    }
+
+    #[test]
+    fn load_a_config_with_rate_limits() {
+        let config = NodeConfig::from_toml(
+            "per_source_rate_limit_capacity = 10\nper_source_rate_limit_refill_per_second = 5",
+        )
+        .unwrap();
+
+        let options = config.rate_limit_options();
+        let limit = options.per_source.get(&MessageTypes::Store).unwrap();
+        assert_eq!(10, limit.capacity);
+        assert_eq!(5, limit.refill_per_second);
+        assert!(options.per_source.contains_key(&MessageTypes::FindNode));
+        assert!(!options.per_source.contains_key(&MessageTypes::PingReply));
+        assert!(options.per_ip.is_empty());
+        assert!(NodeConfig::default()
+            .rate_limit_options()
+            .per_source
+            .is_empty());
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_a_rate_limit_that_never_refills() {
+        assert!(NodeConfig::from_toml("per_ip_rate_limit_capacity = 10").is_err());
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_unknown_keys() {
+        assert!(NodeConfig::from_toml("bucket_size = 20").is_err());
+    }
+
+    #[test]
+    fn disable_health_checks_with_a_zero_interval() {
+        let config = NodeConfig::from_toml("health_check_interval_secs = 0").unwrap();
+        assert!(config.health_prober_options().is_none());
+
+        let options = NodeConfig::default().health_prober_options().unwrap();
+        assert_eq!(Duration::from_secs(60), options.probe_every);
+        assert_eq!(8, options.max_probes_per_round);
+    }
+
+    #[test]
+    fn disable_refresh_and_republish_with_a_zero_interval() {
+        let config =
+            NodeConfig::from_toml("refresh_interval_secs = 0\nrepublish_interval_secs = 0")
+                .unwrap();
+        assert!(config.refresh_interval().is_none());
+        assert!(config.republish_interval().is_none());
+
+        let config = NodeConfig::default();
+        assert_eq!(Some(Duration::from_secs(3600)), config.refresh_interval());
+        assert_eq!(
+            Some(Duration::from_secs(86400)),
+            config.republish_interval()
+        );
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_zero_stale_after_failures() {
+        assert!(NodeConfig::from_toml("stale_after_failures = 0").is_err());
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_zero_k() {
+        assert!(NodeConfig::from_toml("k = 0").is_err());
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_zero_persist_or_expiry_check_intervals() {
+        assert!(NodeConfig::from_toml("table_persist_interval_secs = 0").is_err());
+        assert!(NodeConfig::from_toml("expired_responses_check_interval_millis = 0").is_err());
+    }
+
+    #[test]
+    fn do_not_load_a_config_with_more_disjoint_paths_than_k() {
+        assert!(NodeConfig::from_toml("k = 2\ndisjoint_paths = 3").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::net::callback::ResponseAwaitingCallback;
+use crate::net::identity::generate_nonce;
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::AsyncNetwork;
+use crate::routing::liveness::Liveness;
+use crate::routing::Table;
+use crate::time::{Clock, SystemClock};
+
+#[derive(Copy, Clone)]
+pub(crate) struct HealthProberOptions {
+    pub(crate) probe_every: Duration,
+    pub(crate) max_probes_per_round: usize,
+    pub(crate) remove_unresponsive_after: Duration,
+}
+
+impl HealthProberOptions {
+    pub(crate) fn new(
+        probe_every: Duration,
+        max_probes_per_round: usize,
+        remove_unresponsive_after: Duration,
+    ) -> Self {
+        HealthProberOptions {
+            probe_every,
+            max_probes_per_round,
+            remove_unresponsive_after,
+        }
+    }
+}
+
+pub(crate) struct HealthProber {
+    current_node: Node,
+    routing_table: Arc<Table>,
+    async_network: Arc<AsyncNetwork>,
+    options: HealthProberOptions,
+    clock: Box<dyn Clock>,
+}
+
+impl HealthProber {
+    pub(crate) fn new(
+        current_node: Node,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        options: HealthProberOptions,
+    ) -> Arc<Self> {
+        Arc::new(HealthProber {
+            current_node,
+            routing_table,
+            async_network,
+            options,
+            clock: SystemClock::new(),
+        })
+    }
+
+    pub(crate) async fn run(self: Arc<Self>) {
+        let mut rounds = interval(self.options.probe_every);
+        rounds.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        rounds.tick().await;
+        loop {
+            rounds.tick().await;
+            self.clone().probe().await;
+        }
+    }
+
+    // Only the least-recently-seen contact of each bucket is a candidate, and at most
+    // `max_probes_per_round` of them are pinged, oldest first.
+    pub(crate) async fn probe(self: Arc<Self>) -> usize {
+        let mut candidates: Vec<(Node, Liveness)> = self
+            .routing_table
+            .least_recently_seen()
+            .into_iter()
+            .filter(|(_, liveness)| self.unseen_for(liveness) >= self.options.probe_every)
+            .collect();
+        candidates.sort_by_key(|(_, liveness)| liveness.last_seen);
+        candidates.truncate(self.options.max_probes_per_round);
+
+        let handles: Vec<_> = candidates
+            .into_iter()
+            .map(|(node, _)| {
+                let prober = self.clone();
+                tokio::spawn(async move { prober.probe_contact(node).await })
+            })
+            .collect();
+
+        let total_probes = handles.len();
+        let mut total_responsive = 0;
+        for handle in handles {
+            if let Ok(true) = handle.await {
+                total_responsive += 1;
+            }
+        }
+        info!(
+            "health check probed {} contacts, {} responded",
+            total_probes, total_responsive
+        );
+        total_probes
+    }
+
+    async fn probe_contact(&self, node: Node) -> bool {
+        if self.ping(&node).await {
+            self.routing_table.mark_seen(&node);
+            return true;
+        }
+        if self.routing_table.mark_failed(&node) {
+            return false;
+        }
+        if let Some(liveness) = self.routing_table.liveness_of(&node.id) {
+            if liveness.is_stale(self.routing_table.stale_after_failures())
+                && self.unseen_for(&liveness) >= self.options.remove_unresponsive_after
+            {
+                warn!(
+                    "removing unresponsive contact {} after {} failed probes",
+                    node.endpoint, liveness.consecutive_failures
+                );
+                self.routing_table.remove(&node);
+            }
+        }
+        false
+    }
+
+    async fn ping(&self, node: &Node) -> bool {
+        let nonce = generate_nonce();
+        let callback = ResponseAwaitingCallback::new();
+        let send_result = self
+            .async_network
+            .send_with_message_id_expect_reply(
+                Message::ping_type(self.current_node.clone(), nonce.clone()),
+                &node.endpoint,
+                callback.clone(),
+            )
+            .await;
+        if send_result.is_err() {
+            warn!("could not probe {}", node.endpoint);
+            return false;
+        }
+        let _ = callback.handle().await;
+        match callback.handle().response() {
+            Some(Message::PingReply { proof, .. }) => {
+                proof.verify(&node.id, &nonce, &node.endpoint)
+            }
+            _ => false,
+        }
+    }
+
+    fn unseen_for(&self, liveness: &Liveness) -> Duration {
+        self.clock
+            .now()
+            .duration_since(liveness.last_seen)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::executor::health::{HealthProber, HealthProberOptions};
+    use crate::executor::ownership::tests::reply_to_pings;
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
+    use crate::net::node::Node;
+    use crate::net::rtt::RoundTripTimes;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::routing::diversity::DiversityLimits;
+    use crate::routing::proximity::ProximityPolicy;
+    use crate::routing::{RoutingLayout, Table, TableOptions};
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn probe_a_responsive_contact_and_record_its_round_trip_time() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([1; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6171), &identity);
+        let routing_table = routing_table(1);
+        routing_table.add(node.clone());
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        let prober = prober(routing_table.clone(), waiting_list.clone(), 8);
+        assert_eq!(1, prober.probe().await);
+        handle.await.unwrap();
+
+        let liveness = routing_table.liveness_of(&node.id).unwrap();
+        assert_eq!(0, liveness.consecutive_failures);
+        assert!(waiting_list
+            .round_trip_times()
+            .estimate_for(&node.endpoint)
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn probe_and_remove_an_unresponsive_contact() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([2; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6172), &identity);
+        let routing_table = routing_table(1);
+        routing_table.add(node.clone());
+
+        let prober = prober(routing_table.clone(), waiting_list, 8);
+        assert_eq!(1, prober.probe().await);
+
+        assert!(!routing_table.contains(&node).1);
+    }
+
+    #[tokio::test]
+    async fn keep_an_unresponsive_contact_that_is_not_stale_yet() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([3; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6173), &identity);
+        let routing_table = routing_table(2);
+        routing_table.add(node.clone());
+
+        let prober = prober(routing_table.clone(), waiting_list, 8);
+        assert_eq!(1, prober.probe().await);
+
+        assert!(routing_table.contains(&node).1);
+        assert_eq!(
+            1,
+            routing_table
+                .liveness_of(&node.id)
+                .unwrap()
+                .consecutive_failures
+        );
+    }
+
+    #[tokio::test]
+    async fn bound_the_probes_of_a_round() {
+        let waiting_list = waiting_list();
+        let routing_table = routing_table(2);
+        for (port, first_byte) in [(6174, 0x80), (6175, 0x40), (6176, 0x20)] {
+            let mut id = vec![0; 20];
+            id[0] = first_byte;
+            routing_table.add(Node::new_with_id(
+                Endpoint::new("localhost".to_string(), port),
+                Id::new(id),
+            ));
+        }
+        assert_eq!(3, routing_table.least_recently_seen().len());
+
+        let prober = prober(routing_table, waiting_list, 1);
+        assert_eq!(1, prober.probe().await);
+    }
+
+    fn routing_table(stale_after_failures: u32) -> Arc<Table> {
+        Table::new_with_options(
+            Id::new(vec![0; 20]),
+            TableOptions::new(
+                10,
+                DiversityLimits::unlimited(),
+                RoutingLayout::Fixed,
+                stale_after_failures,
+                ProximityPolicy::default(),
+            ),
+            RoundTripTimes::with_default_timeouts(),
+            SystemClock::new(),
+        )
+    }
+
+    fn prober(
+        routing_table: Arc<Table>,
+        waiting_list: Arc<WaitingList>,
+        max_probes_per_round: usize,
+    ) -> Arc<HealthProber> {
+        HealthProber::new(
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1909),
+                Id::new(vec![0; 20]),
+            ),
+            routing_table,
+            AsyncNetwork::new(waiting_list),
+            HealthProberOptions::new(Duration::ZERO, max_probes_per_round, Duration::ZERO),
+        )
+    }
+
+    fn waiting_list() -> Arc<WaitingList> {
+        WaitingList::new(
+            WaitingListOptions::new(Duration::from_millis(120), Duration::from_millis(30)),
+            SystemClock::new(),
+        )
+    }
+}
@@ -5,12 +5,15 @@ use log::{error, info, warn};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{mpsc, oneshot};
+use tracing::Instrument;
 
+use crate::config::NodeConfig;
 use crate::executor::message_action::{
-    FindNodeMessageAction, FindValueMessageAction, MessageAction, SendPingReplyMessageAction,
-    StoreKeyValueMessageAction,
+    DeleteKeyMessageAction, FindNodeMessageAction, FindValueMessageAction, MessageAction,
+    SendPingReplyMessageAction, StoreKeyValueMessageAction,
 };
 use crate::executor::response::{ChanneledMessage, MessageResponse, MessageStatus};
+use crate::net::identity::Identity;
 use crate::net::message::{Message, MessageTypes};
 use crate::net::node::Node;
 use crate::net::wait::WaitingList;
@@ -27,19 +30,27 @@ pub(crate) struct MessageExecutor {
 impl MessageExecutor {
     pub(crate) fn new(
         current_node: Node,
+        identity: Arc<Identity>,
         store: Arc<dyn Store>,
-        waiting_list: Arc<WaitingList>,
+        async_network: Arc<AsyncNetwork>,
         routing_table: Arc<Table>,
+        config: &NodeConfig,
     ) -> Self {
-        //TODO: make 100 configurable
-        let (sender, receiver) = mpsc::channel(100);
+        let (sender, receiver) = mpsc::channel(config.message_executor_queue_size);
 
         let executor = MessageExecutor {
             sender,
-            waiting_list: waiting_list.clone(),
-            async_network: AsyncNetwork::new(waiting_list),
+            waiting_list: async_network.waiting_list(),
+            async_network,
         };
-        executor.start(current_node, receiver, store, routing_table);
+        executor.start(
+            current_node,
+            identity,
+            receiver,
+            store,
+            routing_table,
+            config.k,
+        );
         executor
     }
 
@@ -54,6 +65,10 @@ impl MessageExecutor {
             .map(|_| MessageResponse::new(receiver))
     }
 
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
     pub(crate) async fn shutdown(&self) -> Result<MessageResponse, SendError<ChanneledMessage>> {
         self.submit(Message::shutdown_type()).await
     }
@@ -61,11 +76,19 @@ impl MessageExecutor {
     fn start(
         &self,
         current_node: Node,
+        identity: Arc<Identity>,
         mut receiver: Receiver<ChanneledMessage>,
         store: Arc<dyn Store>,
         routing_table: Arc<Table>,
+        number_of_neighbors: usize,
     ) {
-        let action_by_message = self.message_actions(current_node, store, routing_table);
+        let action_by_message = self.message_actions(
+            current_node,
+            identity,
+            store,
+            routing_table,
+            number_of_neighbors,
+        );
 
         let waiting_list = self.waiting_list.clone();
         tokio::spawn(async move {
@@ -78,16 +101,29 @@ impl MessageExecutor {
                                 .get(&MessageTypes::Store)
                                 .unwrap()
                                 .act_on(channeled_message.message.clone())
+                                .instrument(channeled_message.span.clone())
                                 .await;
 
                             let _ = channeled_message.send_response(MessageStatus::StoreDone);
                         }
+                        Message::Delete { .. } => {
+                            info!("working on delete message in MessageExecutor");
+                            action_by_message
+                                .get(&MessageTypes::Delete)
+                                .unwrap()
+                                .act_on(channeled_message.message.clone())
+                                .instrument(channeled_message.span.clone())
+                                .await;
+
+                            let _ = channeled_message.send_response(MessageStatus::DeleteDone);
+                        }
                         Message::FindValue { .. } => {
                             info!("working on findValue message in MessageExecutor");
                             action_by_message
                                 .get(&MessageTypes::FindValue)
                                 .unwrap()
                                 .act_on(channeled_message.message.clone())
+                                .instrument(channeled_message.span.clone())
                                 .await;
 
                             let _ = channeled_message.send_response(MessageStatus::FindValueDone);
@@ -98,6 +134,7 @@ impl MessageExecutor {
                                 .get(&MessageTypes::FindNode)
                                 .unwrap()
                                 .act_on(channeled_message.message.clone())
+                                .instrument(channeled_message.span.clone())
                                 .await;
 
                             let _ = channeled_message.send_response(MessageStatus::FindValueDone);
@@ -108,6 +145,7 @@ impl MessageExecutor {
                                 .get(&MessageTypes::Ping)
                                 .unwrap()
                                 .act_on(channeled_message.message.clone())
+                                .instrument(channeled_message.span.clone())
                                 .await;
 
                             let _ = channeled_message.send_response(MessageStatus::PingDone);
@@ -116,8 +154,12 @@ impl MessageExecutor {
                         | Message::FindValueReply { message_id, .. }
                         | Message::FindNodeReply { message_id, .. } => {
                             info!("working on a reply message in MessageExecutor");
-                            waiting_list
-                                .handle_response(message_id, Ok(channeled_message.message.clone()));
+                            channeled_message.span.in_scope(|| {
+                                waiting_list.handle_response(
+                                    message_id,
+                                    Ok(channeled_message.message.clone()),
+                                )
+                            });
 
                             let _ = channeled_message.send_response(MessageStatus::ReplyDone);
                         }
@@ -143,25 +185,40 @@ impl MessageExecutor {
     fn message_actions(
         &self,
         current_node: Node,
+        identity: Arc<Identity>,
         store: Arc<dyn Store>,
         routing_table: Arc<Table>,
+        number_of_neighbors: usize,
     ) -> HashMap<MessageTypes, Box<dyn MessageAction>> {
         let mut action_by_message: HashMap<MessageTypes, Box<dyn MessageAction>> = HashMap::new();
         action_by_message.insert(
             MessageTypes::Store,
             StoreKeyValueMessageAction::new(store.clone()),
         );
+        action_by_message.insert(
+            MessageTypes::Delete,
+            DeleteKeyMessageAction::new(store.clone()),
+        );
         action_by_message.insert(
             MessageTypes::Ping,
-            SendPingReplyMessageAction::new(current_node, self.async_network.clone()),
+            SendPingReplyMessageAction::new(current_node, identity, self.async_network.clone()),
         );
         action_by_message.insert(
             MessageTypes::FindValue,
-            FindValueMessageAction::new(store, routing_table.clone(), self.async_network.clone()),
+            FindValueMessageAction::new(
+                store,
+                routing_table.clone(),
+                self.async_network.clone(),
+                number_of_neighbors,
+            ),
         );
         action_by_message.insert(
             MessageTypes::FindNode,
-            FindNodeMessageAction::new(routing_table, self.async_network.clone()),
+            FindNodeMessageAction::new(
+                routing_table,
+                self.async_network.clone(),
+                number_of_neighbors,
+            ),
         );
         action_by_message
     }
@@ -172,15 +229,19 @@ mod store_message_executor {
     use std::sync::Arc;
     use std::time::Duration;
 
+    use crate::config::NodeConfig;
+
     use crate::executor::message::MessageExecutor;
     use crate::id::Id;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::Message;
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
-    use crate::store::{InMemoryStore, Store};
-    use crate::time::SystemClock;
+    use crate::store::{InMemoryStore, Key, Store, VersionedValue};
+    use crate::time::{Clock, SystemClock};
 
     #[tokio::test]
     async fn submit_store_message_successfully() {
@@ -191,12 +252,19 @@ mod store_message_executor {
         );
         let node_id = node.node_id();
 
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
         let submit_result = executor
             .submit(Message::store_type(
                 "kademlia".as_bytes().to_vec(),
                 "distributed hash table".as_bytes().to_vec(),
+                1,
                 Node::new(Endpoint::new("localhost".to_string(), 9090)),
             ))
             .await;
@@ -211,13 +279,20 @@ mod store_message_executor {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let submit_result = executor
             .submit(Message::store_type(
                 "kademlia".as_bytes().to_vec(),
                 "distributed hash table".as_bytes().to_vec(),
+                1,
                 Node::new(Endpoint::new("localhost".to_string(), 1909)),
             ))
             .await;
@@ -239,13 +314,20 @@ mod store_message_executor {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let submit_result = executor
             .submit(Message::store_type(
                 "kademlia".as_bytes().to_vec(),
                 "distributed hash table".as_bytes().to_vec(),
+                1,
                 Node::new(Endpoint::new("localhost".to_string(), 1909)),
             ))
             .await;
@@ -278,9 +360,11 @@ mod store_message_executor {
         let node_id = node.node_id();
         let executor = Arc::new(MessageExecutor::new(
             node,
+            Arc::new(Identity::from_bytes([7; 32])),
             store.clone(),
-            waiting_list(),
+            AsyncNetwork::new(waiting_list()),
             Table::new(node_id),
+            &NodeConfig::default(),
         ));
         let executor_clone = executor.clone();
         let store_clone = store.clone();
@@ -290,6 +374,7 @@ mod store_message_executor {
                 .submit(Message::store_type(
                     "kademlia".as_bytes().to_vec(),
                     "distributed hash table".as_bytes().to_vec(),
+                    1,
                     Node::new(Endpoint::new("localhost".to_string(), 1909)),
                 ))
                 .await;
@@ -313,6 +398,7 @@ mod store_message_executor {
                 .submit(Message::store_type(
                     "store type".as_bytes().to_vec(),
                     "in-memory".as_bytes().to_vec(),
+                    1,
                     Node::new(Endpoint::new("localhost".to_string(), 1909)),
                 ))
                 .await;
@@ -332,6 +418,47 @@ mod store_message_executor {
         other_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn submit_delete_message_with_the_key_tombstoned_in_store() {
+        let store = Arc::new(InMemoryStore::new());
+        let node = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 9090),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        );
+        let node_id = node.node_id();
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
+
+        store.put_or_update(
+            Key::new("kademlia".as_bytes().to_vec()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+        let submit_result = executor
+            .submit(Message::delete_type(
+                "kademlia".as_bytes().to_vec(),
+                SystemClock::new().now_millis(),
+                Node::new(Endpoint::new("localhost".to_string(), 1909)),
+            ))
+            .await;
+        assert!(submit_result.is_ok());
+
+        let message_status = submit_result
+            .unwrap()
+            .wait_until_response_is_received()
+            .await
+            .unwrap();
+        assert!(message_status.is_delete_done());
+
+        assert!(store.get("kademlia".as_bytes()).is_none());
+        assert!(store.is_tombstoned("kademlia".as_bytes()));
+    }
+
     #[tokio::test]
     async fn submit_a_message_after_shutdown() {
         let store = Arc::new(InMemoryStore::new());
@@ -340,8 +467,14 @@ mod store_message_executor {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let submit_result = executor.shutdown().await;
         assert!(submit_result.is_ok());
@@ -354,6 +487,7 @@ mod store_message_executor {
             .submit(Message::store_type(
                 "kademlia".as_bytes().to_vec(),
                 "distributed hash table".as_bytes().to_vec(),
+                1,
                 Node::new(Endpoint::new("localhost".to_string(), 1909)),
             ))
             .await;
@@ -375,19 +509,21 @@ mod ping_message_executor {
 
     use tokio::net::TcpListener;
 
+    use crate::config::NodeConfig;
     use crate::executor::message::ping_message_executor::setup::TestCallback;
     use crate::executor::message::MessageExecutor;
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::{Message, MessageId};
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
     use crate::store::InMemoryStore;
     use crate::time::SystemClock;
 
     mod setup {
-        use std::any::Any;
         use std::sync::{Arc, Mutex};
 
         use crate::net::callback::{Callback, ResponseError};
@@ -411,10 +547,6 @@ mod ping_message_executor {
                     self.responses.lock().unwrap().push(response.unwrap());
                 }
             }
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
@@ -432,21 +564,34 @@ mod ping_message_executor {
 
             assert!(message.is_ping_reply_type());
             if let Message::PingReply {
-                current_node: from, ..
+                current_node: from,
+                proof,
+                ..
             } = message
             {
                 assert_eq!("localhost:9090", from.endpoint().address());
+                assert!(proof.verify(
+                    &Identity::from_bytes([7; 32]).node_id(),
+                    "nonce".as_bytes(),
+                    from.endpoint()
+                ));
             }
         });
 
         let store = Arc::new(InMemoryStore::new());
         let node = Node::new(Endpoint::new("localhost".to_string(), 9090));
         let node_id = node.node_id();
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let node_sending_ping = Node::new(Endpoint::new("localhost".to_string(), 7565));
-        let mut ping_message = Message::ping_type(node_sending_ping);
+        let mut ping_message = Message::ping_type(node_sending_ping, "nonce".as_bytes().to_vec());
         ping_message.set_message_id(10);
 
         let submit_result = executor.submit(ping_message).await;
@@ -469,16 +614,20 @@ mod ping_message_executor {
         let waiting_list = waiting_list();
         let executor = MessageExecutor::new(
             node.clone(),
+            Arc::new(Identity::from_bytes([7; 32])),
             store.clone(),
-            waiting_list.clone(),
+            AsyncNetwork::new(waiting_list.clone()),
             Table::new(node_id),
+            &NodeConfig::default(),
         );
 
         let message_id: MessageId = 100;
         let callback = TestCallback::new();
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &node.endpoint, callback.clone());
 
-        let ping_reply_message = Message::ping_reply_type(node, message_id);
+        let proof =
+            Identity::from_bytes([7; 32]).prove_ownership("nonce".as_bytes(), &node.endpoint);
+        let ping_reply_message = Message::ping_reply_type(node, message_id, proof);
 
         let submit_result = executor.submit(ping_reply_message).await;
         assert!(submit_result.is_ok());
@@ -510,20 +659,22 @@ mod find_value_message_executor {
 
     use tokio::net::TcpListener;
 
+    use crate::config::NodeConfig;
     use crate::executor::message::find_value_message_executor::setup::TestCallback;
     use crate::executor::message::MessageExecutor;
     use crate::id::Id;
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::{Message, MessageId};
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
-    use crate::store::{InMemoryStore, Key, Store};
+    use crate::store::{InMemoryStore, Key, Store, VersionedValue};
     use crate::time::SystemClock;
 
     mod setup {
-        use std::any::Any;
         use std::sync::{Arc, Mutex};
 
         use crate::net::callback::{Callback, ResponseError};
@@ -547,10 +698,6 @@ mod find_value_message_executor {
                     self.responses.lock().unwrap().push(response.unwrap());
                 }
             }
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
@@ -568,14 +715,17 @@ mod find_value_message_executor {
 
             assert!(message.is_find_value_reply_type());
             if let Message::FindValueReply { value, .. } = message {
-                assert_eq!(value.unwrap(), "distributed hash table".as_bytes().to_vec());
+                assert_eq!(
+                    value.unwrap().value,
+                    "distributed hash table".as_bytes().to_vec()
+                );
             }
         });
 
         let store = Arc::new(InMemoryStore::new());
         store.put_or_update(
             Key::new("kademlia".as_bytes().to_vec()),
-            "distributed hash table".as_bytes().to_vec(),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
         );
 
         let node = Node::new_with_id(
@@ -584,8 +734,14 @@ mod find_value_message_executor {
         );
         let node_id = node.node_id();
 
-        let executor =
-            MessageExecutor::new(node, store.clone(), waiting_list(), Table::new(node_id));
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store.clone(),
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let mut find_value_message = Message::find_value_type(
             Node::new(Endpoint::new("localhost".to_string(), 9818)),
@@ -613,17 +769,22 @@ mod find_value_message_executor {
         let waiting_list = waiting_list();
         let executor = MessageExecutor::new(
             node.clone(),
+            Arc::new(Identity::from_bytes([7; 32])),
             store.clone(),
-            waiting_list.clone(),
+            AsyncNetwork::new(waiting_list.clone()),
             Table::new(node_id),
+            &NodeConfig::default(),
         );
 
         let message_id: MessageId = 100;
         let callback = TestCallback::new();
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &node.endpoint, callback.clone());
 
-        let find_value_reply =
-            Message::find_value_reply_type(message_id, Some("kademlia".as_bytes().to_vec()), None);
+        let find_value_reply = Message::find_value_reply_type(
+            message_id,
+            Some(VersionedValue::new("kademlia".as_bytes().to_vec(), 1)),
+            None,
+        );
 
         let submit_result = executor.submit(find_value_reply).await;
         assert!(submit_result.is_ok());
@@ -655,20 +816,22 @@ mod find_node_message_executor {
 
     use tokio::net::TcpListener;
 
+    use crate::config::NodeConfig;
     use crate::executor::message::find_node_message_executor::setup::TestCallback;
     use crate::executor::message::MessageExecutor;
     use crate::id::Id;
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::{Message, MessageId};
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
     use crate::store::InMemoryStore;
     use crate::time::SystemClock;
 
     mod setup {
-        use std::any::Any;
         use std::sync::{Arc, Mutex};
 
         use crate::net::callback::{Callback, ResponseError};
@@ -692,10 +855,6 @@ mod find_node_message_executor {
                     self.responses.lock().unwrap().push(response.unwrap());
                 }
             }
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
@@ -726,7 +885,14 @@ mod find_node_message_executor {
         let store = Arc::new(InMemoryStore::new());
         let node_id = node.node_id();
         let routing_table = Table::new(node_id);
-        let executor = MessageExecutor::new(node, store, waiting_list(), routing_table.clone());
+        let executor = MessageExecutor::new(
+            node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            store,
+            AsyncNetwork::new(waiting_list()),
+            routing_table.clone(),
+            &NodeConfig::default(),
+        );
         routing_table.add(Node::new_with_id(
             Endpoint::new("localhost".to_string(), 7070),
             Id::new(247u16.to_be_bytes().to_vec()),
@@ -758,14 +924,16 @@ mod find_node_message_executor {
         let waiting_list = waiting_list();
         let executor = MessageExecutor::new(
             node.clone(),
+            Arc::new(Identity::from_bytes([7; 32])),
             store,
-            waiting_list.clone(),
+            AsyncNetwork::new(waiting_list.clone()),
             Table::new(node_id),
+            &NodeConfig::default(),
         );
 
         let message_id: MessageId = 100;
         let callback = TestCallback::new();
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &node.endpoint, callback.clone());
 
         let closest_neighbors = Vec::new();
         let find_value_reply = Message::find_node_reply_type(message_id, closest_neighbors);
@@ -3,13 +3,15 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use log::warn;
 
-use crate::net::callback::{ResponseAwaitingCallback, ResponseStatus};
+use crate::executor::ownership::OwnershipVerifier;
+use crate::net::identity::Identity;
 use crate::net::message::Message::AddNode;
 use crate::net::message::{Message, Source};
 use crate::net::node::Node;
-use crate::net::{AsyncNetwork, NetworkErrorKind};
+use crate::net::puzzle::PuzzleOptions;
+use crate::net::AsyncNetwork;
 use crate::routing::Table;
-use crate::store::{Key, Store};
+use crate::store::{Key, Store, VersionedValue};
 
 #[async_trait]
 pub(crate) trait MessageAction: Send + Sync {
@@ -26,6 +28,40 @@ impl StoreKeyValueMessageAction {
     }
 }
 
+// Shared by the stores arriving from the network and the local writes of the node, a local
+// plain write must not replace a signed or content-addressed record either.
+pub(crate) fn accepts_store(store: &dyn Store, key: &[u8], value: &VersionedValue) -> bool {
+    if !value.verify(key) {
+        warn!("rejecting the store of a record that does not verify against its key");
+        return false;
+    }
+    match store.get_versioned(key) {
+        Some(stored_value) if stored_value.is_immutable() => {
+            warn!("rejecting the store over an immutable record");
+            false
+        }
+        Some(stored_value) if stored_value.is_mutable() => {
+            if !value.is_mutable() {
+                warn!("rejecting the store of a plain value over a mutable record");
+                return false;
+            }
+            if value.version < stored_value.version {
+                warn!(
+                    "rejecting the store of a mutable record with sequence {}, stored sequence is {}",
+                    value.version, stored_value.version
+                );
+                return false;
+            }
+            true
+        }
+        Some(_) if value.is_mutable() || value.is_immutable() => {
+            store.delete(key);
+            true
+        }
+        _ => true,
+    }
+}
+
 #[async_trait]
 impl MessageAction for StoreKeyValueMessageAction {
     async fn act_on(&self, message: Message) {
@@ -33,21 +69,63 @@ impl MessageAction for StoreKeyValueMessageAction {
             key, key_id, value, ..
         } = message
         {
+            if accepts_store(self.store.as_ref(), &key, &value) {
+                self.store
+                    .put_or_update(Key::new_with_id(key, key_id), value);
+            }
+        }
+    }
+}
+
+pub(crate) struct DeleteKeyMessageAction {
+    store: Arc<dyn Store>,
+}
+
+impl DeleteKeyMessageAction {
+    pub(crate) fn new(store: Arc<dyn Store>) -> Box<Self> {
+        Box::new(DeleteKeyMessageAction { store })
+    }
+}
+
+#[async_trait]
+impl MessageAction for DeleteKeyMessageAction {
+    async fn act_on(&self, message: Message) {
+        if let Message::Delete {
+            key,
+            key_id,
+            deleted_at,
+            ..
+        } = message
+        {
+            let deletes_a_record = self
+                .store
+                .get_versioned(&key)
+                .is_some_and(|stored_value| !stored_value.is_plain());
+            if deletes_a_record {
+                warn!("rejecting the delete of a mutable or an immutable record");
+                return;
+            }
             self.store
-                .put_or_update(Key::new_with_id(key, key_id), value);
+                .delete_with_tombstone(Key::new_with_id(key, key_id), deleted_at);
         }
     }
 }
 
 pub(crate) struct SendPingReplyMessageAction {
     current_node: Node,
+    identity: Arc<Identity>,
     async_network: Arc<AsyncNetwork>,
 }
 
 impl SendPingReplyMessageAction {
-    pub(crate) fn new(current_node: Node, async_network: Arc<AsyncNetwork>) -> Box<Self> {
+    pub(crate) fn new(
+        current_node: Node,
+        identity: Arc<Identity>,
+        async_network: Arc<AsyncNetwork>,
+    ) -> Box<Self> {
         Box::new(SendPingReplyMessageAction {
             current_node,
+            identity,
             async_network,
         })
     }
@@ -56,8 +134,16 @@ impl SendPingReplyMessageAction {
 #[async_trait]
 impl MessageAction for SendPingReplyMessageAction {
     async fn act_on(&self, message: Message) {
-        if let Message::Ping { message_id, from } = message {
+        if let Message::Ping {
+            message_id,
+            from,
+            nonce,
+        } = message
+        {
             let current_node = self.current_node.clone();
+            let proof = self
+                .identity
+                .prove_ownership(&nonce, &self.current_node.endpoint);
             let async_network = self.async_network.clone();
 
             tokio::spawn(async move {
@@ -65,7 +151,7 @@ impl MessageAction for SendPingReplyMessageAction {
                 let send_to = from.endpoint();
                 let _ = async_network
                     .send(
-                        Message::ping_reply_type(current_node, message_id.unwrap()),
+                        Message::ping_reply_type(current_node, message_id.unwrap(), proof),
                         send_to,
                     )
                     .await;
@@ -78,6 +164,7 @@ pub(crate) struct FindValueMessageAction {
     store: Arc<dyn Store>,
     routing_table: Arc<Table>,
     async_network: Arc<AsyncNetwork>,
+    number_of_neighbors: usize,
 }
 
 impl FindValueMessageAction {
@@ -85,11 +172,13 @@ impl FindValueMessageAction {
         store: Arc<dyn Store>,
         routing_table: Arc<Table>,
         async_network: Arc<AsyncNetwork>,
+        number_of_neighbors: usize,
     ) -> Box<Self> {
         Box::new(FindValueMessageAction {
             store,
             routing_table,
             async_network,
+            number_of_neighbors,
         })
     }
 }
@@ -108,21 +197,27 @@ impl MessageAction for FindValueMessageAction {
                 warn!("received a FindValue message with an empty message id, skipping the processing");
                 return;
             }
-            let find_value_reply = match self.store.get(&key) {
-                //TODO: remove hardcoded 5
-                None => {
-                    let neighbors = self.routing_table.closest_neighbors(&key_id, 5);
-                    let sources: Vec<Source> = neighbors
-                        .all_nodes()
-                        .iter()
-                        .map(|node| Source::new(node))
-                        .collect();
-                    Message::find_value_reply_type(message_id.unwrap(), None, Some(sources))
-                }
-                Some(value) => {
-                    Message::find_value_reply_type(message_id.unwrap(), Some(value), None)
-                }
-            };
+            let message_id = message_id.unwrap();
+            let find_value_reply =
+                match (self.store.get_versioned(&key), self.store.deleted_at(&key)) {
+                    (Some(value), _) => {
+                        Message::find_value_reply_type(message_id, Some(value), None)
+                    }
+                    (None, Some(deleted_at)) => {
+                        Message::find_value_tombstone_reply_type(message_id, deleted_at)
+                    }
+                    (None, None) => {
+                        let neighbors = self
+                            .routing_table
+                            .closest_neighbors(&key_id, self.number_of_neighbors);
+                        let sources: Vec<Source> = neighbors
+                            .all_nodes()
+                            .iter()
+                            .map(|node| Source::new(node))
+                            .collect();
+                        Message::find_value_reply_type(message_id, None, Some(sources))
+                    }
+                };
 
             let _ = self
                 .async_network
@@ -135,13 +230,19 @@ impl MessageAction for FindValueMessageAction {
 pub(crate) struct FindNodeMessageAction {
     routing_table: Arc<Table>,
     async_network: Arc<AsyncNetwork>,
+    number_of_neighbors: usize,
 }
 
 impl FindNodeMessageAction {
-    pub(crate) fn new(routing_table: Arc<Table>, async_network: Arc<AsyncNetwork>) -> Box<Self> {
+    pub(crate) fn new(
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        number_of_neighbors: usize,
+    ) -> Box<Self> {
         Box::new(FindNodeMessageAction {
             routing_table,
             async_network,
+            number_of_neighbors,
         })
     }
 }
@@ -161,8 +262,9 @@ impl MessageAction for FindNodeMessageAction {
                 );
                 return;
             }
-            //TODO: remove hardcoded 5
-            let neighbors = self.routing_table.closest_neighbors(&node_id, 5);
+            let neighbors = self
+                .routing_table
+                .closest_neighbors(&node_id, self.number_of_neighbors);
             let sources: Vec<Source> = neighbors
                 .all_nodes()
                 .iter()
@@ -179,9 +281,9 @@ impl MessageAction for FindNodeMessageAction {
 }
 
 pub(crate) struct AddNodeAction {
-    current_node: Node,
     routing_table: Arc<Table>,
-    async_network: Arc<AsyncNetwork>,
+    ownership_verifier: OwnershipVerifier,
+    puzzle_options: PuzzleOptions,
 }
 
 impl AddNodeAction {
@@ -189,55 +291,51 @@ impl AddNodeAction {
         current_node: Node,
         routing_table: Arc<Table>,
         async_network: Arc<AsyncNetwork>,
+        puzzle_options: PuzzleOptions,
     ) -> Box<Self> {
         Box::new(AddNodeAction {
-            current_node,
             routing_table,
-            async_network,
+            ownership_verifier: OwnershipVerifier::new(current_node, async_network),
+            puzzle_options,
         })
     }
-
-    async fn send_ping_to(
-        &self,
-        node: &Node,
-        callback: &Arc<ResponseAwaitingCallback>,
-    ) -> Result<(), NetworkErrorKind> {
-        self.async_network
-            .send_with_message_id_expect_reply(
-                Message::ping_type(self.current_node.clone()),
-                &node.endpoint,
-                callback.clone(),
-            )
-            .await
-    }
 }
 
 #[async_trait]
 impl MessageAction for AddNodeAction {
     async fn act_on(&self, message: Message) {
         if let AddNode { source } = message {
-            let (bucket_index, added) = self.routing_table.add(source.clone().to_node());
+            let node = source.to_node();
+            if self.routing_table.contains(&node).1 {
+                return;
+            }
+            if !self.puzzle_options.verify(&node.id, &node.puzzle_solution) {
+                warn!(
+                    "not adding {} to the routing table, its node id does not solve the puzzles",
+                    node.endpoint
+                );
+                return;
+            }
+            if !self.ownership_verifier.verify(&node).await {
+                warn!(
+                    "not adding {} to the routing table, it could not be verified",
+                    node.endpoint
+                );
+                return;
+            }
+            let (bucket_index, added) = self.routing_table.add(node.clone());
             if added {
                 return;
             }
-            //TODO: add a test to simulate ping reply from the node
-            if let Some(node) = self.routing_table.first_node_in(bucket_index) {
-                let callback = ResponseAwaitingCallback::new();
-                match self.send_ping_to(&node, &callback).await {
-                    Ok(_) => {
-                        let response_status = callback.handle().await;
-                        if let ResponseStatus::Err = response_status {
-                            self.routing_table.remove_and_add(
-                                bucket_index,
-                                &node,
-                                source.to_node(),
-                            );
-                        }
-                    }
-                    Err(_) => {
-                        self.routing_table
-                            .remove_and_add(bucket_index, &node, source.to_node())
-                    }
+            if let Some(first_node) = self.routing_table.first_node_in(bucket_index) {
+                if self
+                    .ownership_verifier
+                    .verify_by_round_trip(&first_node)
+                    .await
+                {
+                    self.routing_table.mark_seen(&first_node);
+                } else {
+                    self.routing_table.mark_failed(&first_node);
                 }
             }
         }
@@ -253,7 +351,9 @@ mod store_message_action_tests {
     use crate::net::endpoint::Endpoint;
     use crate::net::message::Message;
     use crate::net::node::Node;
+    use crate::store::record::{ImmutableRecord, MutableRecord};
     use crate::store::{InMemoryStore, Store};
+    use ed25519_dalek::SigningKey;
 
     #[tokio::test]
     async fn act_on_store_message_and_store_the_key_value_in_store() {
@@ -263,6 +363,7 @@ mod store_message_action_tests {
         let message = Message::store_type(
             "kademlia".as_bytes().to_vec(),
             "distributed hash table".as_bytes().to_vec(),
+            1,
             Node::new_with_id(
                 Endpoint::new("localhost".to_string(), 1909),
                 Id::new(511u16.to_be_bytes().to_vec()),
@@ -278,6 +379,263 @@ mod store_message_action_tests {
             String::from_utf8(value.unwrap()).unwrap()
         );
     }
+
+    #[tokio::test]
+    async fn act_on_mutable_store_message_and_store_the_signed_value_in_store() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = mutable_record(1, "distributed hash table");
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::mutable_store_type(record, source()))
+            .await;
+
+        let value = store.get(&key);
+        assert_eq!(
+            "distributed hash table",
+            String::from_utf8(value.unwrap()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn act_on_mutable_store_message_with_a_tampered_value_and_do_not_store_it() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let mut record = mutable_record(1, "distributed hash table");
+        record.value.value = "hash table".as_bytes().to_vec();
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::mutable_store_type(record, source()))
+            .await;
+
+        assert!(store.get(&key).is_none());
+    }
+
+    #[tokio::test]
+    async fn act_on_mutable_store_message_with_a_lower_sequence_and_keep_the_stored_value() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = mutable_record(2, "distributed hash table");
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::mutable_store_type(record, source()))
+            .await;
+        message_action
+            .act_on(Message::mutable_store_type(
+                mutable_record(1, "hash table"),
+                source(),
+            ))
+            .await;
+
+        let value = store.get_versioned(&key).unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert_eq!(2, value.version);
+    }
+
+    #[tokio::test]
+    async fn act_on_mutable_store_message_with_a_higher_sequence_and_update_the_stored_value() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = mutable_record(1, "hash table");
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::mutable_store_type(record, source()))
+            .await;
+        message_action
+            .act_on(Message::mutable_store_type(
+                mutable_record(2, "distributed hash table"),
+                source(),
+            ))
+            .await;
+
+        let value = store.get_versioned(&key).unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert_eq!(2, value.version);
+    }
+
+    #[tokio::test]
+    async fn act_on_plain_store_message_for_a_mutable_key_and_keep_the_stored_value() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = mutable_record(1, "distributed hash table");
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::mutable_store_type(record, source()))
+            .await;
+        message_action
+            .act_on(Message::store_type(
+                key.clone(),
+                "hash table".as_bytes().to_vec(),
+                5,
+                source(),
+            ))
+            .await;
+
+        let value = store.get_versioned(&key).unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert!(value.is_mutable());
+    }
+
+    #[tokio::test]
+    async fn act_on_immutable_store_message_and_store_the_value_under_its_hash() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::immutable_store_type(record, source()))
+            .await;
+
+        let value = store.get_versioned(&key).unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert!(value.is_immutable());
+    }
+
+    #[tokio::test]
+    async fn act_on_immutable_store_message_with_a_mismatching_hash_and_do_not_store_it() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let mut record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+        record.value.value = "poisoned".as_bytes().to_vec();
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::immutable_store_type(record, source()))
+            .await;
+
+        assert!(store.get(&key).is_none());
+    }
+
+    #[tokio::test]
+    async fn act_on_plain_store_message_for_an_immutable_key_and_keep_the_stored_value() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action = StoreKeyValueMessageAction::new(store.clone());
+
+        let record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+        let key = record.key.clone();
+        message_action
+            .act_on(Message::immutable_store_type(record, source()))
+            .await;
+        message_action
+            .act_on(Message::store_type(
+                key.clone(),
+                "poisoned".as_bytes().to_vec(),
+                5,
+                source(),
+            ))
+            .await;
+
+        let value = store.get_versioned(&key).unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert!(value.is_immutable());
+    }
+
+    fn mutable_record(sequence: u64, value: &str) -> MutableRecord {
+        MutableRecord::sign(
+            &SigningKey::from_bytes(&[7; 32]),
+            None,
+            sequence,
+            value.as_bytes().to_vec(),
+        )
+    }
+
+    fn source() -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 1909),
+            Id::new(511u16.to_be_bytes().to_vec()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod delete_message_action_tests {
+    use std::sync::Arc;
+
+    use ed25519_dalek::SigningKey;
+
+    use crate::executor::message_action::{DeleteKeyMessageAction, MessageAction};
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::message::Message;
+    use crate::net::node::Node;
+    use crate::store::record::{ImmutableRecord, MutableRecord};
+    use crate::store::{InMemoryStore, Key, Store, VersionedValue};
+    use crate::time::{Clock, SystemClock};
+
+    #[tokio::test]
+    async fn act_on_delete_message_and_tombstone_the_key_in_store() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        store.put_or_update(
+            Key::new("kademlia".as_bytes().to_vec()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+        let message_action = DeleteKeyMessageAction::new(store.clone());
+
+        let message = Message::delete_type(
+            "kademlia".as_bytes().to_vec(),
+            SystemClock::new().now_millis(),
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1909),
+                Id::new(511u16.to_be_bytes().to_vec()),
+            ),
+        );
+        message_action.act_on(message).await;
+
+        assert!(store.get("kademlia".as_bytes()).is_none());
+        assert!(store.is_tombstoned("kademlia".as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn reject_the_delete_of_a_mutable_record() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let record = MutableRecord::sign(
+            &SigningKey::from_bytes(&[7; 32]),
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        store.put_or_update(Key::new(record.key.clone()), record.value);
+        let message_action = DeleteKeyMessageAction::new(store.clone());
+
+        let message = Message::delete_type(
+            record.key.clone(),
+            SystemClock::new().now_millis(),
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1909),
+                Id::new(511u16.to_be_bytes().to_vec()),
+            ),
+        );
+        message_action.act_on(message).await;
+
+        assert!(store.get(&record.key).is_some());
+        assert!(!store.is_tombstoned(&record.key));
+    }
+
+    #[tokio::test]
+    async fn reject_the_delete_of_an_immutable_record() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+        store.put_or_update(Key::new(record.key.clone()), record.value);
+        let message_action = DeleteKeyMessageAction::new(store.clone());
+
+        let message = Message::delete_type(
+            record.key.clone(),
+            SystemClock::new().now_millis(),
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1909),
+                Id::new(511u16.to_be_bytes().to_vec()),
+            ),
+        );
+        message_action.act_on(message).await;
+
+        assert!(store.get(&record.key).is_some());
+    }
 }
 
 #[cfg(test)]
@@ -290,6 +648,7 @@ mod ping_message_action_tests {
     use crate::executor::message_action::{MessageAction, SendPingReplyMessageAction};
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::Message;
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
@@ -310,19 +669,30 @@ mod ping_message_action_tests {
 
             assert!(message.is_ping_reply_type());
             if let Message::PingReply {
-                current_node: to, ..
+                current_node: to,
+                proof,
+                ..
             } = message
             {
                 assert_eq!("localhost:7878", to.endpoint().address());
+                assert!(proof.verify(
+                    &Identity::from_bytes([7; 32]).node_id(),
+                    "nonce".as_bytes(),
+                    to.endpoint()
+                ));
             }
         });
 
         let async_network = AsyncNetwork::new(waiting_list());
         let current_node = Node::new(Endpoint::new("localhost".to_string(), 7878));
-        let message_action = SendPingReplyMessageAction::new(current_node, async_network);
+        let message_action = SendPingReplyMessageAction::new(
+            current_node,
+            Arc::new(Identity::from_bytes([7; 32])),
+            async_network,
+        );
 
         let node_sending_ping = Node::new(Endpoint::new("localhost".to_string(), 8009));
-        let mut ping_message = Message::ping_type(node_sending_ping);
+        let mut ping_message = Message::ping_type(node_sending_ping, "nonce".as_bytes().to_vec());
         ping_message.set_message_id(10);
 
         message_action.act_on(ping_message).await;
@@ -346,84 +716,133 @@ mod add_node_action_tests {
     use tokio::net::TcpListener;
 
     use crate::executor::message_action::{AddNodeAction, MessageAction};
+    use crate::executor::ownership::tests::reply_to_pings;
     use crate::id::Id;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::Message;
     use crate::net::node::Node;
+    use crate::net::puzzle::{solve_dynamic_puzzle, PuzzleOptions};
+    use crate::net::rtt::RoundTripTimes;
     use crate::net::wait::{WaitingList, WaitingListOptions};
     use crate::net::AsyncNetwork;
-    use crate::routing::Table;
+    use crate::routing::diversity::DiversityLimits;
+    use crate::routing::proximity::ProximityPolicy;
+    use crate::routing::{RoutingLayout, Table, TableOptions};
     use crate::time::SystemClock;
 
     #[tokio::test]
     async fn act_on_add_node_message_and_add_the_node_in_routing_table() {
-        let async_network = AsyncNetwork::new(waiting_list());
-        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([1; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 8434), &identity);
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
 
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
         let message_action = AddNodeAction::new(
-            Node::new_with_id(
-                Endpoint::new("localhost".to_string(), 1909),
-                Id::new(255u16.to_be_bytes().to_vec()),
-            ),
+            current_node(),
             routing_table.clone(),
-            async_network,
+            AsyncNetwork::new(waiting_list),
+            PuzzleOptions::new(0, 0),
         );
 
-        let message = Message::add_node_type(Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8434),
-            Id::new(511u16.to_be_bytes().to_vec()),
-        ));
-        message_action.act_on(message).await;
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
+
+        let (_, contains) = routing_table.contains(&node);
+        assert!(contains);
+    }
 
+    #[tokio::test]
+    async fn act_on_add_node_message_and_do_not_add_a_node_that_does_not_own_its_id() {
+        let waiting_list = waiting_list();
         let node = Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8434),
+            Endpoint::new("localhost".to_string(), 6134),
             Id::new(511u16.to_be_bytes().to_vec()),
         );
+        let handle = reply_to_pings(
+            node.clone(),
+            Identity::from_bytes([1; 32]),
+            waiting_list.clone(),
+            1,
+        )
+        .await;
+
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let message_action = AddNodeAction::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list),
+            PuzzleOptions::new(0, 0),
+        );
+
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
 
         let (_, contains) = routing_table.contains(&node);
-        assert!(contains);
+        assert!(!contains);
     }
 
     #[tokio::test]
-    async fn act_on_add_node_message_given_the_bucket_capacity_is_full() {
-        let async_network = AsyncNetwork::new(waiting_list());
-        let routing_table: Arc<Table> =
-            Table::new_with_bucket_capacity(Id::new(255u16.to_be_bytes().to_vec()), 1);
-
+    async fn act_on_add_node_message_and_do_not_add_an_unreachable_node() {
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
         let message_action = AddNodeAction::new(
-            Node::new_with_id(
-                Endpoint::new("localhost".to_string(), 1909),
-                Id::new(255u16.to_be_bytes().to_vec()),
-            ),
+            current_node(),
             routing_table.clone(),
-            async_network,
+            AsyncNetwork::new(waiting_list()),
+            PuzzleOptions::new(0, 0),
         );
 
-        let message = Message::add_node_type(Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8434),
-            Id::new(511u16.to_be_bytes().to_vec()),
-        ));
-        message_action.act_on(message).await;
+        let node = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6135),
+            &Identity::from_bytes([1; 32]),
+        );
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
 
-        let message = Message::add_node_type(Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 7878),
-            Id::new(511u16.to_be_bytes().to_vec()),
-        ));
-        message_action.act_on(message).await;
+        let (_, contains) = routing_table.contains(&node);
+        assert!(!contains);
+    }
 
-        let node = Node::new_with_id(
+    #[tokio::test]
+    async fn act_on_add_node_message_given_the_bucket_capacity_is_full() {
+        let waiting_list = waiting_list();
+        let (identity, other_identity) = identities_in_the_same_bucket();
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6136), &identity);
+        let other_node = Node::new_with_identity(
             Endpoint::new("localhost".to_string(), 7878),
-            Id::new(511u16.to_be_bytes().to_vec()),
+            &other_identity,
         );
 
-        let (_, contains) = routing_table.contains(&node);
-        assert!(contains);
-
-        let node = Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8434),
-            Id::new(511u16.to_be_bytes().to_vec()),
+        let routing_table = table_evicting_after(table_id_for(&identity), 1);
+        let message_action = AddNodeAction::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list.clone()),
+            PuzzleOptions::new(0, 0),
         );
 
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
+
+        let other_handle =
+            reply_to_pings(other_node.clone(), other_identity, waiting_list, 1).await;
+        message_action
+            .act_on(Message::add_node_type(other_node.clone()))
+            .await;
+        other_handle.await.unwrap();
+
+        let (_, contains) = routing_table.contains(&other_node);
+        assert!(contains);
+
         let (_, contains) = routing_table.contains(&node);
         assert_eq!(false, contains);
     }
@@ -431,54 +850,177 @@ mod add_node_action_tests {
     #[tokio::test]
     async fn act_on_add_node_message_given_the_bucket_capacity_is_full_and_the_node_to_ping_does_not_reply(
     ) {
-        let listener_result = TcpListener::bind("localhost:8436").await;
-        assert!(listener_result.is_ok());
-
         let waiting_list = WaitingList::new(
             WaitingListOptions::new(Duration::from_millis(120), Duration::from_millis(30)),
             SystemClock::new(),
         );
+        let (identity, other_identity) = identities_in_the_same_bucket();
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 8436), &identity);
+        let other_node = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 7880),
+            &other_identity,
+        );
 
-        let async_network = AsyncNetwork::new(waiting_list);
-        let routing_table: Arc<Table> =
-            Table::new_with_bucket_capacity(Id::new(255u16.to_be_bytes().to_vec()), 1);
+        let routing_table = table_evicting_after(table_id_for(&identity), 1);
+        let message_action = AddNodeAction::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list.clone()),
+            PuzzleOptions::new(0, 0),
+        );
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
+
+        let listener_result = TcpListener::bind("localhost:8436").await;
+        assert!(listener_result.is_ok());
+
+        let other_handle =
+            reply_to_pings(other_node.clone(), other_identity, waiting_list, 1).await;
+        message_action
+            .act_on(Message::add_node_type(other_node.clone()))
+            .await;
+        other_handle.await.unwrap();
+
+        let (_, contains) = routing_table.contains(&other_node);
+        assert!(contains);
+
+        let (_, contains) = routing_table.contains(&node);
+        assert_eq!(false, contains);
+    }
 
+    #[tokio::test]
+    async fn act_on_add_node_message_given_the_bucket_capacity_is_full_and_keep_a_node_that_is_not_stale_yet(
+    ) {
+        let waiting_list = waiting_list();
+        let (identity, other_identity) = identities_in_the_same_bucket();
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6169), &identity);
+        let other_node = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6170),
+            &other_identity,
+        );
+
+        let routing_table = table_evicting_after(table_id_for(&identity), 2);
         let message_action = AddNodeAction::new(
-            Node::new_with_id(
-                Endpoint::new("localhost".to_string(), 1909),
-                Id::new(255u16.to_be_bytes().to_vec()),
-            ),
+            current_node(),
             routing_table.clone(),
-            async_network,
+            AsyncNetwork::new(waiting_list.clone()),
+            PuzzleOptions::new(0, 0),
         );
 
-        let message = Message::add_node_type(Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8436),
-            Id::new(511u16.to_be_bytes().to_vec()),
-        ));
-        message_action.act_on(message).await;
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
 
-        let message = Message::add_node_type(Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 7880),
-            Id::new(511u16.to_be_bytes().to_vec()),
-        ));
-        message_action.act_on(message).await;
+        let other_handle =
+            reply_to_pings(other_node.clone(), other_identity, waiting_list, 1).await;
+        message_action
+            .act_on(Message::add_node_type(other_node.clone()))
+            .await;
+        other_handle.await.unwrap();
 
-        let node = Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 7880),
-            Id::new(511u16.to_be_bytes().to_vec()),
+        let (_, contains) = routing_table.contains(&node);
+        assert!(contains);
+        assert_eq!(
+            1,
+            routing_table
+                .liveness_of(&node.id)
+                .unwrap()
+                .consecutive_failures
         );
 
+        let (_, contains) = routing_table.contains(&other_node);
+        assert_eq!(false, contains);
+        assert!(routing_table.replacements().contains(&other_node));
+    }
+
+    #[tokio::test]
+    async fn act_on_add_node_message_and_add_a_node_solving_the_puzzles() {
+        let waiting_list = waiting_list();
+        let identity = Identity::generate_with_static_puzzle(4);
+        let node = Node::new_with_puzzle_solution(
+            Endpoint::new("localhost".to_string(), 6140),
+            identity.node_id(),
+            solve_dynamic_puzzle(&identity.node_id(), 8),
+        );
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let message_action = AddNodeAction::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list),
+            PuzzleOptions::new(4, 8),
+        );
+
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+        handle.await.unwrap();
+
         let (_, contains) = routing_table.contains(&node);
         assert!(contains);
+    }
 
-        let node = Node::new_with_id(
-            Endpoint::new("localhost".to_string(), 8436),
-            Id::new(511u16.to_be_bytes().to_vec()),
+    #[tokio::test]
+    async fn act_on_add_node_message_and_do_not_add_a_node_failing_the_dynamic_puzzle() {
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let message_action = AddNodeAction::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list()),
+            PuzzleOptions::new(4, 8),
         );
 
+        let identity = Identity::generate_with_static_puzzle(4);
+        let node = Node::new_with_puzzle_solution(
+            Endpoint::new("localhost".to_string(), 6141),
+            identity.node_id(),
+            vec![0; identity.node_id().len()],
+        );
+        message_action
+            .act_on(Message::add_node_type(node.clone()))
+            .await;
+
         let (_, contains) = routing_table.contains(&node);
-        assert_eq!(false, contains);
+        assert!(!contains);
+    }
+
+    fn table_evicting_after(node_id: Id, stale_after_failures: u32) -> Arc<Table> {
+        Table::new_with_options(
+            node_id,
+            TableOptions::new(
+                1,
+                DiversityLimits::default(),
+                RoutingLayout::Fixed,
+                stale_after_failures,
+                ProximityPolicy::default(),
+            ),
+            RoundTripTimes::with_default_timeouts(),
+            SystemClock::new(),
+        )
+    }
+
+    fn identities_in_the_same_bucket() -> (Identity, Identity) {
+        (Identity::from_bytes([1; 32]), Identity::from_bytes([2; 32]))
+    }
+
+    fn table_id_for(identity: &Identity) -> Id {
+        let mut id = identity.node_id().as_bytes().to_vec();
+        id[0] ^= 0x80;
+        Id::new(id)
+    }
+
+    fn current_node() -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 1909),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        )
     }
 
     fn waiting_list() -> Arc<WaitingList> {
@@ -505,8 +1047,8 @@ mod find_value_message_action_tests {
     use crate::net::wait::{WaitingList, WaitingListOptions};
     use crate::net::AsyncNetwork;
     use crate::routing::Table;
-    use crate::store::{InMemoryStore, Key, Store};
-    use crate::time::SystemClock;
+    use crate::store::{InMemoryStore, Key, Store, VersionedValue};
+    use crate::time::{Clock, SystemClock};
 
     #[tokio::test]
     async fn act_on_find_value_message_given_value_for_the_key_is_found_in_store() {
@@ -526,7 +1068,10 @@ mod find_value_message_action_tests {
             } = message
             {
                 assert_eq!(100, message_id);
-                assert_eq!("distributed hash table".as_bytes().to_vec(), value.unwrap());
+                assert_eq!(
+                    "distributed hash table".as_bytes().to_vec(),
+                    value.unwrap().value
+                );
             }
         });
 
@@ -535,11 +1080,11 @@ mod find_value_message_action_tests {
 
         let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
         let message_action =
-            FindValueMessageAction::new(store.clone(), routing_table, async_network);
+            FindValueMessageAction::new(store.clone(), routing_table, async_network, 5);
 
         store.put_or_update(
             Key::new("kademlia".as_bytes().to_vec()),
-            "distributed hash table".as_bytes().to_vec(),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
         );
 
         let mut message = Message::find_value_type(
@@ -556,6 +1101,59 @@ mod find_value_message_action_tests {
         handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn act_on_find_value_message_given_the_key_is_tombstoned_in_store() {
+        let listener_result = TcpListener::bind("localhost:6184").await;
+        assert!(listener_result.is_ok());
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let stream = tcp_listener.accept().await.unwrap();
+
+            let mut connection = AsyncTcpConnection::new(stream.0);
+            let message = connection.read().await.unwrap();
+
+            match message {
+                Message::FindValueReply {
+                    message_id,
+                    value,
+                    deleted_at,
+                    ..
+                } => {
+                    assert_eq!(100, message_id);
+                    assert!(value.is_none());
+                    assert!(deleted_at.is_some());
+                }
+                _ => panic!("expected a FindValueReply"),
+            }
+        });
+
+        let async_network = AsyncNetwork::new(waiting_list());
+        let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+        let message_action =
+            FindValueMessageAction::new(store.clone(), routing_table, async_network, 5);
+
+        store.delete_with_tombstone(
+            Key::new("kademlia".as_bytes().to_vec()),
+            SystemClock::new().now_millis(),
+        );
+
+        let mut message = Message::find_value_type(
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 6184),
+                Id::new(511u16.to_be_bytes().to_vec()),
+            ),
+            "kademlia".as_bytes().to_vec(),
+        );
+        message.set_message_id(100);
+
+        message_action.act_on(message).await;
+
+        handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn act_on_find_value_message_given_value_for_the_key_is_not_found_in_store() {
         let listener_result = TcpListener::bind("localhost:9912").await;
@@ -571,8 +1169,8 @@ mod find_value_message_action_tests {
             assert!(message.is_find_value_reply_type());
             if let Message::FindValueReply {
                 message_id,
-                value: _,
                 neighbors,
+                ..
             } = message
             {
                 assert_eq!(100, message_id);
@@ -595,7 +1193,7 @@ mod find_value_message_action_tests {
 
         let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
         let message_action =
-            FindValueMessageAction::new(store, routing_table.clone(), async_network);
+            FindValueMessageAction::new(store, routing_table.clone(), async_network, 5);
 
         routing_table.add(Node::new_with_id(
             Endpoint::new("localhost".to_string(), 7070),
@@ -681,7 +1279,7 @@ mod find_node_message_action_tests {
         let async_network = AsyncNetwork::new(waiting_list());
         let routing_table: Arc<Table> = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
 
-        let message_action = FindNodeMessageAction::new(routing_table.clone(), async_network);
+        let message_action = FindNodeMessageAction::new(routing_table.clone(), async_network, 5);
 
         routing_table.add(Node::new_with_id(
             Endpoint::new("localhost".to_string(), 7070),
@@ -1,4 +1,11 @@
+pub(crate) mod health;
 pub(crate) mod message;
-mod message_action;
+pub(crate) mod message_action;
 pub(crate) mod node;
+pub(crate) mod ownership;
+pub(crate) mod records;
+pub(crate) mod refresh;
+pub(crate) mod replicate;
+pub(crate) mod republish;
 pub(crate) mod response;
+pub(crate) mod restore;
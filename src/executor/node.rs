@@ -6,11 +6,12 @@ use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{mpsc, oneshot};
 
+use crate::config::NodeConfig;
 use crate::executor::message_action::{AddNodeAction, MessageAction};
 use crate::executor::response::{ChanneledMessage, MessageResponse, MessageStatus};
 use crate::net::message::{Message, MessageTypes};
 use crate::net::node::Node;
-use crate::net::wait::WaitingList;
+use crate::net::puzzle::PuzzleOptions;
 use crate::net::AsyncNetwork;
 use crate::routing::Table;
 
@@ -23,18 +24,18 @@ pub(crate) struct AddNodeExecutor {
 impl AddNodeExecutor {
     pub(crate) fn new(
         current_node: Node,
-        waiting_list: Arc<WaitingList>,
+        async_network: Arc<AsyncNetwork>,
         routing_table: Arc<Table>,
+        config: &NodeConfig,
     ) -> Self {
-        //TODO: make 100 configurable
-        let (sender, receiver) = mpsc::channel(100);
+        let (sender, receiver) = mpsc::channel(config.add_node_executor_queue_size);
 
         let executor = AddNodeExecutor {
             sender,
             routing_table,
-            async_network: AsyncNetwork::new(waiting_list),
+            async_network,
         };
-        executor.start(receiver, current_node);
+        executor.start(receiver, current_node, config.puzzle_options());
         executor
     }
 
@@ -49,18 +50,27 @@ impl AddNodeExecutor {
             .map(|_| MessageResponse::new(receiver))
     }
 
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
     pub(crate) async fn shutdown(&self) -> Result<MessageResponse, SendError<ChanneledMessage>> {
         self.submit(Message::shutdown_type()).await
     }
 
-    fn start(&self, mut receiver: Receiver<ChanneledMessage>, current_node: Node) {
+    fn start(
+        &self,
+        mut receiver: Receiver<ChanneledMessage>,
+        current_node: Node,
+        puzzle_options: PuzzleOptions,
+    ) {
         let routing_table = self.routing_table.clone();
         let async_network = self.async_network.clone();
 
         let mut action_by_message: HashMap<MessageTypes, Box<dyn MessageAction>> = HashMap::new();
         action_by_message.insert(
             MessageTypes::AddNode,
-            AddNodeAction::new(current_node, routing_table, async_network),
+            AddNodeAction::new(current_node, routing_table, async_network, puzzle_options),
         );
 
         tokio::spawn(async move {
@@ -102,12 +112,16 @@ mod tests {
     use std::sync::Arc;
     use std::time::Duration;
 
+    use crate::config::NodeConfig;
     use crate::executor::node::AddNodeExecutor;
+    use crate::executor::ownership::tests::reply_to_pings;
     use crate::id::Id;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::Message;
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
     use crate::time::SystemClock;
 
@@ -118,7 +132,12 @@ mod tests {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor = AddNodeExecutor::new(node, waiting_list(), Table::new(node_id));
+        let executor = AddNodeExecutor::new(
+            node,
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
         let submit_result = executor
             .submit(Message::add_node_type(Node::new(Endpoint::new(
                 "localhost".to_string(),
@@ -136,12 +155,21 @@ mod tests {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor = AddNodeExecutor::new(node, waiting_list(), Table::new(node_id));
+        let waiting_list = waiting_list();
+        let executor = AddNodeExecutor::new(
+            node,
+            AsyncNetwork::new(waiting_list.clone()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
+
+        let identity = Identity::from_bytes([1; 32]);
+        let node_to_add =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 6137), &identity);
+        let handle = reply_to_pings(node_to_add.clone(), identity, waiting_list, 1).await;
+
         let submit_result = executor
-            .submit(Message::add_node_type(Node::new(Endpoint::new(
-                "localhost".to_string(),
-                8989,
-            ))))
+            .submit(Message::add_node_type(node_to_add.clone()))
             .await;
 
         assert!(submit_result.is_ok());
@@ -151,10 +179,9 @@ mod tests {
             .wait_until_response_is_received()
             .await;
         assert!(message_response_result.is_ok());
+        handle.await.unwrap();
 
-        let node = Node::new(Endpoint::new("localhost".to_string(), 8989));
-        let (_, contains) = executor.routing_table.contains(&node);
-
+        let (_, contains) = executor.routing_table.contains(&node_to_add);
         assert!(contains);
     }
 
@@ -165,19 +192,32 @@ mod tests {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
+        let waiting_list = waiting_list();
         let executor = Arc::new(AddNodeExecutor::new(
             node,
-            waiting_list(),
+            AsyncNetwork::new(waiting_list.clone()),
             Table::new(node_id),
+            &NodeConfig::default(),
         ));
         let executor_clone = executor.clone();
 
+        let identity = Identity::from_bytes([2; 32]);
+        let node_to_add =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 6138), &identity);
+        let reply_handle =
+            reply_to_pings(node_to_add.clone(), identity, waiting_list.clone(), 1).await;
+
+        let other_identity = Identity::from_bytes([3; 32]);
+        let other_node_to_add = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6139),
+            &other_identity,
+        );
+        let other_reply_handle =
+            reply_to_pings(other_node_to_add.clone(), other_identity, waiting_list, 1).await;
+
         let handle = tokio::spawn(async move {
             let submit_result = executor
-                .submit(Message::add_node_type(Node::new(Endpoint::new(
-                    "localhost".to_string(),
-                    8989,
-                ))))
+                .submit(Message::add_node_type(node_to_add.clone()))
                 .await;
 
             assert!(submit_result.is_ok());
@@ -188,18 +228,13 @@ mod tests {
                 .await;
             assert!(message_response_result.is_ok());
 
-            let node = Node::new(Endpoint::new("localhost".to_string(), 8989));
-            let (_, contains) = executor.routing_table.contains(&node);
-
+            let (_, contains) = executor.routing_table.contains(&node_to_add);
             assert!(contains);
         });
 
         let other_handle = tokio::spawn(async move {
             let submit_result = executor_clone
-                .submit(Message::add_node_type(Node::new(Endpoint::new(
-                    "localhost".to_string(),
-                    7878,
-                ))))
+                .submit(Message::add_node_type(other_node_to_add.clone()))
                 .await;
 
             assert!(submit_result.is_ok());
@@ -210,14 +245,14 @@ mod tests {
                 .await;
             assert!(message_response_result.is_ok());
 
-            let node = Node::new(Endpoint::new("localhost".to_string(), 7878));
-            let (_, contains) = executor_clone.routing_table.contains(&node);
-
+            let (_, contains) = executor_clone.routing_table.contains(&other_node_to_add);
             assert!(contains);
         });
 
         handle.await.unwrap();
         other_handle.await.unwrap();
+        reply_handle.await.unwrap();
+        other_reply_handle.await.unwrap();
     }
 
     #[tokio::test]
@@ -227,7 +262,12 @@ mod tests {
             Id::new(255u16.to_be_bytes().to_vec()),
         );
         let node_id = node.node_id();
-        let executor = AddNodeExecutor::new(node, waiting_list(), Table::new(node_id));
+        let executor = AddNodeExecutor::new(
+            node,
+            AsyncNetwork::new(waiting_list()),
+            Table::new(node_id),
+            &NodeConfig::default(),
+        );
 
         let submit_result = executor.shutdown().await;
         assert!(submit_result.is_ok());
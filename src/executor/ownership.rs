@@ -0,0 +1,408 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use dashmap::DashMap;
+use log::{debug, warn};
+
+use crate::net::callback::ResponseAwaitingCallback;
+use crate::net::endpoint::Endpoint;
+use crate::net::identity::generate_nonce;
+use crate::net::message::Message;
+use crate::net::node::{Node, NodeId};
+use crate::net::puzzle::PuzzleOptions;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+use crate::time::{Clock, SystemClock};
+
+const VERIFIED_CONTACT_TTL: Duration = Duration::from_secs(15 * 60);
+const MAX_VERIFIED_CONTACTS: usize = 10_000;
+
+pub(crate) struct VerifiedContacts {
+    verified_at: DashMap<NodeId, (Endpoint, SystemTime)>,
+    expire_after: Duration,
+    clock: Box<dyn Clock>,
+}
+
+impl VerifiedContacts {
+    pub(crate) fn new(expire_after: Duration, clock: Box<dyn Clock>) -> Arc<Self> {
+        Arc::new(VerifiedContacts {
+            verified_at: DashMap::new(),
+            expire_after,
+            clock,
+        })
+    }
+
+    pub(crate) fn is_verified(&self, node: &Node) -> bool {
+        match self.verified_at.get(&node.id) {
+            Some(entry) => {
+                let (endpoint, verified_at) = entry.value();
+                endpoint == &node.endpoint && !self.has_expired(verified_at)
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn mark_verified(&self, node: &Node) {
+        if self.verified_at.len() >= MAX_VERIFIED_CONTACTS {
+            self.verified_at
+                .retain(|_, (_, verified_at)| !self.has_expired(verified_at));
+        }
+        self.verified_at
+            .insert(node.id.clone(), (node.endpoint.clone(), self.clock.now()));
+    }
+
+    fn has_expired(&self, verified_at: &SystemTime) -> bool {
+        self.clock
+            .now()
+            .duration_since(*verified_at)
+            .map(|elapsed| elapsed >= self.expire_after)
+            .unwrap_or(false)
+    }
+}
+
+pub(crate) struct OwnershipVerifier {
+    current_node: Node,
+    async_network: Arc<AsyncNetwork>,
+    verified_contacts: Arc<VerifiedContacts>,
+}
+
+impl OwnershipVerifier {
+    pub(crate) fn new(current_node: Node, async_network: Arc<AsyncNetwork>) -> Self {
+        Self::new_with_verified_contacts(
+            current_node,
+            async_network,
+            VerifiedContacts::new(VERIFIED_CONTACT_TTL, SystemClock::new()),
+        )
+    }
+
+    pub(crate) fn new_with_verified_contacts(
+        current_node: Node,
+        async_network: Arc<AsyncNetwork>,
+        verified_contacts: Arc<VerifiedContacts>,
+    ) -> Self {
+        OwnershipVerifier {
+            current_node,
+            async_network,
+            verified_contacts,
+        }
+    }
+
+    pub(crate) async fn verify(&self, node: &Node) -> bool {
+        if self.verified_contacts.is_verified(node) {
+            debug!("{} was verified recently", node.endpoint);
+            return true;
+        }
+        self.verify_by_round_trip(node).await
+    }
+
+    pub(crate) async fn verify_by_round_trip(&self, node: &Node) -> bool {
+        let verified = self.ping_for_proof(node).await;
+        if verified {
+            self.verified_contacts.mark_verified(node);
+        }
+        verified
+    }
+
+    pub(crate) async fn discover(&self, endpoint: &Endpoint) -> Option<Node> {
+        let nonce = generate_nonce();
+        match self.ping(endpoint, &nonce).await {
+            Some(Message::PingReply {
+                current_node,
+                proof,
+                ..
+            }) => {
+                // the proof is bound to the endpoint we dialed, the endpoint claimed in the
+                // reply is not signed and a relaying node could claim any endpoint.
+                let claimed_node = current_node.to_node();
+                if !proof.verify(&claimed_node.id, &nonce, endpoint) {
+                    warn!("{} could not prove the ownership of its node id", endpoint);
+                    return None;
+                }
+                let node = Node::new_with_puzzle_solution(
+                    endpoint.clone(),
+                    claimed_node.id,
+                    claimed_node.puzzle_solution,
+                );
+                self.verified_contacts.mark_verified(&node);
+                Some(node)
+            }
+            _ => {
+                warn!("{} did not reply to ping", endpoint);
+                None
+            }
+        }
+    }
+
+    async fn ping(&self, endpoint: &Endpoint, nonce: &[u8]) -> Option<Message> {
+        let callback = ResponseAwaitingCallback::new();
+        let send_result = self
+            .async_network
+            .send_with_message_id_expect_reply(
+                Message::ping_type(self.current_node.clone(), nonce.to_vec()),
+                endpoint,
+                callback.clone(),
+            )
+            .await;
+
+        if send_result.is_err() {
+            warn!("could not ping {}", endpoint);
+            return None;
+        }
+        let _ = callback.handle().await;
+        callback.handle().response()
+    }
+
+    async fn ping_for_proof(&self, node: &Node) -> bool {
+        let nonce = generate_nonce();
+        match self.ping(&node.endpoint, &nonce).await {
+            Some(Message::PingReply { proof, .. }) => {
+                let verified = proof.verify(&node.id, &nonce, &node.endpoint);
+                if !verified {
+                    warn!(
+                        "{} could not prove the ownership of its node id",
+                        node.endpoint
+                    );
+                }
+                verified
+            }
+            _ => {
+                warn!("{} did not reply to ping", node.endpoint);
+                false
+            }
+        }
+    }
+}
+
+pub(crate) async fn add_verified_nodes(
+    nodes: Vec<Node>,
+    current_node: &Node,
+    puzzle_options: &PuzzleOptions,
+    ownership_verifier: &OwnershipVerifier,
+    routing_table: &Table,
+) -> usize {
+    let mut added = 0;
+    for node in nodes {
+        if node.id != current_node.id
+            && puzzle_options.verify(&node.id, &node.puzzle_solution)
+            && ownership_verifier.verify(&node).await
+            && routing_table.add(node).1
+        {
+            added += 1;
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    use crate::executor::ownership::{OwnershipVerifier, VerifiedContacts};
+    use crate::id::Id;
+    use crate::net::connection::AsyncTcpConnection;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
+    use crate::net::message::Message;
+    use crate::net::node::Node;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn verify_a_node_that_proves_the_ownership_of_its_id() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6131), &identity);
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        let verifier = verifier(waiting_list);
+
+        assert!(verifier.verify(&node).await);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn do_not_verify_a_node_that_claims_an_id_it_does_not_own() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6132),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        );
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        let verifier = verifier(waiting_list);
+
+        assert!(!verifier.verify(&node).await);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn discover_the_node_behind_an_endpoint() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let node_id = identity.node_id();
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6165), &identity);
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        let verifier = verifier(waiting_list);
+
+        let discovered = verifier.discover(&node.endpoint).await;
+        handle.await.unwrap();
+
+        assert_eq!(node_id, discovered.unwrap().id);
+    }
+
+    #[tokio::test]
+    async fn do_not_discover_a_node_that_proves_the_ownership_at_another_endpoint() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let relayed_node =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 6180), &identity);
+        let relaying_endpoint = Endpoint::new("localhost".to_string(), 6179);
+
+        let handle = reply_to_pings_at(
+            relaying_endpoint.clone(),
+            relayed_node,
+            identity,
+            waiting_list.clone(),
+            1,
+        )
+        .await;
+        let verifier = verifier(waiting_list);
+
+        let discovered = verifier.discover(&relaying_endpoint).await;
+        handle.await.unwrap();
+
+        assert!(discovered.is_none());
+    }
+
+    #[tokio::test]
+    async fn do_not_ping_a_recently_verified_node_again() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6134), &identity);
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 1).await;
+        let verifier = verifier(waiting_list);
+
+        assert!(verifier.verify(&node).await);
+        handle.await.unwrap();
+        assert!(verifier.verify(&node).await);
+    }
+
+    #[tokio::test]
+    async fn verify_a_node_again_after_its_verification_expires() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6135), &identity);
+
+        let handle = reply_to_pings(node.clone(), identity, waiting_list.clone(), 2).await;
+        let verifier = OwnershipVerifier::new_with_verified_contacts(
+            current_node(),
+            AsyncNetwork::new(waiting_list),
+            VerifiedContacts::new(Duration::ZERO, SystemClock::new()),
+        );
+
+        assert!(verifier.verify(&node).await);
+        assert!(verifier.verify(&node).await);
+        handle.await.unwrap();
+    }
+
+    #[test]
+    fn do_not_trust_a_verified_node_id_at_another_endpoint() {
+        let verified_contacts = VerifiedContacts::new(Duration::from_secs(60), SystemClock::new());
+        let identity = Identity::from_bytes([7; 32]);
+        verified_contacts.mark_verified(&Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6135),
+            &identity,
+        ));
+
+        assert!(!verified_contacts.is_verified(&Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6136),
+            &identity,
+        )));
+    }
+
+    #[tokio::test]
+    async fn do_not_verify_an_unreachable_node() {
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 6133), &identity);
+
+        let verifier = verifier(waiting_list());
+        assert!(!verifier.verify(&node).await);
+    }
+
+    pub(crate) async fn reply_to_pings(
+        node: Node,
+        identity: Identity,
+        waiting_list: Arc<WaitingList>,
+        total_replies: usize,
+    ) -> JoinHandle<()> {
+        reply_to_pings_at(
+            node.endpoint.clone(),
+            node,
+            identity,
+            waiting_list,
+            total_replies,
+        )
+        .await
+    }
+
+    async fn reply_to_pings_at(
+        listening_endpoint: Endpoint,
+        node: Node,
+        identity: Identity,
+        waiting_list: Arc<WaitingList>,
+        total_replies: usize,
+    ) -> JoinHandle<()> {
+        let tcp_listener = TcpListener::bind(listening_endpoint.address())
+            .await
+            .unwrap();
+
+        tokio::spawn(async move {
+            for _ in 0..total_replies {
+                let stream = tcp_listener.accept().await.unwrap();
+                let mut connection = AsyncTcpConnection::new(stream.0);
+                let message = connection.read().await.unwrap();
+
+                if let Message::Ping {
+                    message_id, nonce, ..
+                } = message
+                {
+                    let proof = identity.prove_ownership(&nonce, &node.endpoint);
+                    waiting_list.handle_response(
+                        message_id.unwrap(),
+                        Ok(Message::ping_reply_type(
+                            node.clone(),
+                            message_id.unwrap(),
+                            proof,
+                        )),
+                    );
+                }
+            }
+        })
+    }
+
+    fn verifier(waiting_list: Arc<WaitingList>) -> OwnershipVerifier {
+        OwnershipVerifier::new(current_node(), AsyncNetwork::new(waiting_list))
+    }
+
+    fn current_node() -> Node {
+        Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6130),
+            &Identity::from_bytes([9; 32]),
+        )
+    }
+
+    fn waiting_list() -> Arc<WaitingList> {
+        WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100)),
+            SystemClock::new(),
+        )
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::executor::message_action::accepts_store;
+use crate::executor::replicate::Replicator;
+use crate::lookup::{IterativeLookup, LookupOptions};
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+use crate::store::record::RecordType;
+use crate::store::{Key, KeyId, Store, VersionedValue};
+
+// Reads and writes records through the network, the facade and the admin interface share it so
+// both look values up and replicate them the same way.
+pub(crate) struct Records {
+    current_node: Node,
+    store: Arc<dyn Store>,
+    lookup: IterativeLookup,
+    replicator: Replicator,
+}
+
+impl Records {
+    pub(crate) fn new(
+        current_node: Node,
+        store: Arc<dyn Store>,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        lookup_options: LookupOptions,
+    ) -> Arc<Self> {
+        Arc::new(Records {
+            lookup: IterativeLookup::new(
+                current_node.clone(),
+                routing_table,
+                async_network.clone(),
+                lookup_options,
+            ),
+            replicator: Replicator::new(current_node.clone(), async_network),
+            current_node,
+            store,
+        })
+    }
+
+    // A value the store does not accept is neither stored nor replicated.
+    pub(crate) async fn put(&self, key: Key, value: VersionedValue, message: Message) -> bool {
+        if !accepts_store(self.store.as_ref(), &key.key, &value) {
+            return false;
+        }
+        let key_id = key.id.clone();
+        self.store.put_or_update(key, value);
+        self.replicate(message, &key_id).await;
+        true
+    }
+
+    pub(crate) async fn delete(&self, key: Key, deleted_at: u64) {
+        let key_id = key.id.clone();
+        let message = Message::delete_type(key.key.clone(), deleted_at, self.current_node.clone());
+        self.store.delete_with_tombstone(key, deleted_at);
+        self.replicate(message, &key_id).await;
+    }
+
+    pub(crate) async fn latest_value(
+        &self,
+        key: &[u8],
+        record_type: RecordType,
+    ) -> Option<VersionedValue> {
+        let local_value = self
+            .store
+            .get_versioned(key)
+            .filter(|versioned_value| versioned_value.kind.record_type() == record_type);
+        let found = self.lookup.find_value(key.to_vec(), record_type).await;
+        let deleted_at = self.store.deleted_at(key).max(found.deleted_at);
+
+        let latest_value = match (local_value, found.value) {
+            (Some(local_value), Some(network_value)) => {
+                if network_value.is_newer_than(&local_value) {
+                    Some(network_value)
+                } else {
+                    Some(local_value)
+                }
+            }
+            (local_value, network_value) => local_value.or(network_value),
+        };
+        latest_value
+            .filter(|value| deleted_at.is_none_or(|deleted_at| !value.is_deleted_by(deleted_at)))
+    }
+
+    async fn replicate(&self, message: Message, key_id: &KeyId) {
+        let closest_nodes = self.lookup.find_node(key_id).await;
+        self.replicator
+            .replicate(message, key_id, closest_nodes)
+            .await;
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::executor::ownership::{add_verified_nodes, OwnershipVerifier};
+use crate::lookup::{IterativeLookup, LookupOptions};
+use crate::net::node::Node;
+use crate::net::puzzle::PuzzleOptions;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+
+pub(crate) struct BucketRefresher {
+    current_node: Node,
+    routing_table: Arc<Table>,
+    lookup: IterativeLookup,
+    ownership_verifier: OwnershipVerifier,
+    puzzle_options: PuzzleOptions,
+    refresh_every: Duration,
+}
+
+impl BucketRefresher {
+    pub(crate) fn new(
+        current_node: Node,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        lookup_options: LookupOptions,
+        puzzle_options: PuzzleOptions,
+        refresh_every: Duration,
+    ) -> Arc<Self> {
+        Arc::new(BucketRefresher {
+            lookup: IterativeLookup::new(
+                current_node.clone(),
+                routing_table.clone(),
+                async_network.clone(),
+                lookup_options,
+            ),
+            ownership_verifier: OwnershipVerifier::new(current_node.clone(), async_network),
+            current_node,
+            routing_table,
+            puzzle_options,
+            refresh_every,
+        })
+    }
+
+    pub(crate) async fn run(self: Arc<Self>) {
+        let mut rounds = interval(self.refresh_every);
+        rounds.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        rounds.tick().await;
+        loop {
+            rounds.tick().await;
+            self.refresh().await;
+        }
+    }
+
+    // Every bucket with contacts is refreshed by a lookup of a random id in its range, the
+    // contacts found on the way are verified before they are added.
+    pub(crate) async fn refresh(&self) -> usize {
+        let mut total_refreshed = 0;
+        let mut total_added = 0;
+        for (bucket_index, _) in self.routing_table.buckets() {
+            let target = match self.routing_table.random_id_in(bucket_index) {
+                Some(target) => target,
+                None => continue,
+            };
+            let nodes = self.lookup.find_node(&target).await;
+            total_added += add_verified_nodes(
+                nodes,
+                &self.current_node,
+                &self.puzzle_options,
+                &self.ownership_verifier,
+                &self.routing_table,
+            )
+            .await;
+            self.routing_table.record_refresh(bucket_index);
+            total_refreshed += 1;
+        }
+        info!(
+            "refreshed {} buckets, {} contacts added",
+            total_refreshed, total_added
+        );
+        total_refreshed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::executor::refresh::BucketRefresher;
+    use crate::id::Id;
+    use crate::lookup::LookupOptions;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::node::Node;
+    use crate::net::puzzle::PuzzleOptions;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::routing::event::RoutingEvent;
+    use crate::routing::Table;
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn refresh_the_buckets_with_contacts() {
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6181),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+        let (bucket_index, _) = routing_table.buckets()[0].clone();
+        let mut events = routing_table.subscribe();
+
+        let refresher = refresher(routing_table);
+        assert_eq!(1, refresher.refresh().await);
+
+        assert_eq!(
+            RoutingEvent::BucketRefreshed { bucket_index },
+            events.try_recv().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_no_buckets_given_an_empty_table() {
+        let refresher = refresher(Table::new(Id::new(255u16.to_be_bytes().to_vec())));
+        assert_eq!(0, refresher.refresh().await);
+    }
+
+    fn refresher(routing_table: Arc<Table>) -> Arc<BucketRefresher> {
+        BucketRefresher::new(
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1909),
+                Id::new(255u16.to_be_bytes().to_vec()),
+            ),
+            routing_table,
+            AsyncNetwork::new(WaitingList::new(
+                WaitingListOptions::new(Duration::from_millis(120), Duration::from_millis(30)),
+                SystemClock::new(),
+            )),
+            LookupOptions::new(3, 20, 1),
+            PuzzleOptions::new(0, 0),
+            Duration::from_secs(3600),
+        )
+    }
+}
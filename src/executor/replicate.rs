@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::AsyncNetwork;
+use crate::store::KeyId;
+
+pub(crate) struct Replicator {
+    current_node: Node,
+    async_network: Arc<AsyncNetwork>,
+}
+
+impl Replicator {
+    pub(crate) fn new(current_node: Node, async_network: Arc<AsyncNetwork>) -> Self {
+        Replicator {
+            current_node,
+            async_network,
+        }
+    }
+
+    // The closest nodes come from a lookup for the key id, so the message reaches the k
+    // closest nodes of the network rather than the closest ones in the local table.
+    pub(crate) async fn replicate(
+        &self,
+        message: Message,
+        key_id: &KeyId,
+        closest_nodes: Vec<Node>,
+    ) -> usize {
+        let mut total_replicated = 0;
+        for node in closest_nodes {
+            if node.id == self.current_node.id {
+                continue;
+            }
+            match self
+                .async_network
+                .send(message.clone(), &node.endpoint)
+                .await
+            {
+                Ok(_) => total_replicated += 1,
+                Err(err) => warn!(
+                    "could not replicate the message to {}, error {}",
+                    node.endpoint, err
+                ),
+            }
+        }
+        info!(
+            "replicated the message for the key id {:?} to {} nodes",
+            key_id, total_replicated
+        );
+        total_replicated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use crate::executor::replicate::Replicator;
+    use crate::id::Id;
+    use crate::net::connection::AsyncTcpConnection;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::message::Message;
+    use crate::net::node::Node;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::store::KeyId;
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn replicate_a_delete_message_to_the_closest_nodes() {
+        let listener_result = TcpListener::bind("localhost:6111").await;
+        assert!(listener_result.is_ok());
+
+        let other_listener_result = TcpListener::bind("localhost:6112").await;
+        assert!(other_listener_result.is_ok());
+
+        let handles: Vec<_> = vec![listener_result.unwrap(), other_listener_result.unwrap()]
+            .into_iter()
+            .map(|tcp_listener| {
+                tokio::spawn(async move {
+                    let stream = tcp_listener.accept().await.unwrap();
+
+                    let mut connection = AsyncTcpConnection::new(stream.0);
+                    let message = connection.read().await.unwrap();
+
+                    assert!(message.is_delete_type());
+                    if let Message::Delete {
+                        key, deleted_at, ..
+                    } = message
+                    {
+                        assert_eq!("kademlia", String::from_utf8(key).unwrap());
+                        assert_eq!(1000, deleted_at);
+                    }
+                })
+            })
+            .collect();
+
+        let closest_nodes = vec![
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 6111),
+                Id::new(247u16.to_be_bytes().to_vec()),
+            ),
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 6112),
+                Id::new(511u16.to_be_bytes().to_vec()),
+            ),
+        ];
+
+        let current_node = current_node();
+        let replicator = Replicator::new(current_node.clone(), AsyncNetwork::new(waiting_list()));
+        let total_replicated = replicator
+            .replicate(
+                Message::delete_type("kademlia".as_bytes().to_vec(), 1000, current_node),
+                &KeyId::generate_from_bytes("kademlia".as_bytes()),
+                closest_nodes,
+            )
+            .await;
+        assert_eq!(2, total_replicated);
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn do_not_replicate_to_the_current_node() {
+        let current_node = current_node();
+        let replicator = Replicator::new(current_node.clone(), AsyncNetwork::new(waiting_list()));
+
+        let total_replicated = replicator
+            .replicate(
+                Message::delete_type("kademlia".as_bytes().to_vec(), 1000, current_node.clone()),
+                &KeyId::generate_from_bytes("kademlia".as_bytes()),
+                vec![current_node],
+            )
+            .await;
+
+        assert_eq!(0, total_replicated);
+    }
+
+    #[tokio::test]
+    async fn replicate_to_no_nodes_given_no_closest_nodes() {
+        let current_node = current_node();
+        let replicator = Replicator::new(current_node.clone(), AsyncNetwork::new(waiting_list()));
+
+        let total_replicated = replicator
+            .replicate(
+                Message::delete_type("kademlia".as_bytes().to_vec(), 1000, current_node),
+                &KeyId::generate_from_bytes("kademlia".as_bytes()),
+                Vec::new(),
+            )
+            .await;
+
+        assert_eq!(0, total_replicated);
+    }
+
+    fn current_node() -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6110),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        )
+    }
+
+    fn waiting_list() -> Arc<WaitingList> {
+        WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100)),
+            SystemClock::new(),
+        )
+    }
+}
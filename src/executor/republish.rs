@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::executor::replicate::Replicator;
+use crate::lookup::{IterativeLookup, LookupOptions};
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+use crate::store::Store;
+
+pub(crate) struct Republisher {
+    current_node: Node,
+    store: Arc<dyn Store>,
+    lookup: IterativeLookup,
+    replicator: Replicator,
+    republish_every: Duration,
+}
+
+impl Republisher {
+    pub(crate) fn new(
+        current_node: Node,
+        store: Arc<dyn Store>,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        lookup_options: LookupOptions,
+        republish_every: Duration,
+    ) -> Arc<Self> {
+        Arc::new(Republisher {
+            lookup: IterativeLookup::new(
+                current_node.clone(),
+                routing_table,
+                async_network.clone(),
+                lookup_options,
+            ),
+            replicator: Replicator::new(current_node.clone(), async_network),
+            current_node,
+            store,
+            republish_every,
+        })
+    }
+
+    pub(crate) async fn run(self: Arc<Self>) {
+        let mut rounds = interval(self.republish_every);
+        rounds.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        rounds.tick().await;
+        loop {
+            rounds.tick().await;
+            self.republish().await;
+        }
+    }
+
+    // Every stored value is sent again to the closest nodes of its key, so it survives the
+    // nodes that stored it leaving and reaches the nodes that joined closer to the key.
+    // Tombstones are sent until they expire, so replicas that missed a delete catch up.
+    pub(crate) async fn republish(&self) -> usize {
+        let values = self.store.all_values();
+        let total_values = values.len();
+        let mut total_replicated = 0;
+        for (key, value) in values {
+            let key_id = key.id.clone();
+            let closest_nodes = self.lookup.find_node(&key_id).await;
+            let message = Message::store_versioned_type(key, value, self.current_node.clone());
+            total_replicated += self
+                .replicator
+                .replicate(message, &key_id, closest_nodes)
+                .await;
+        }
+        let tombstones = self.store.all_tombstones();
+        let total_tombstones = tombstones.len();
+        for (key, deleted_at) in tombstones {
+            let closest_nodes = self.lookup.find_node(&key.id).await;
+            let message = Message::delete_type(key.key, deleted_at, self.current_node.clone());
+            total_replicated += self
+                .replicator
+                .replicate(message, &key.id, closest_nodes)
+                .await;
+        }
+        info!(
+            "republished {} values and {} tombstones to {} nodes",
+            total_values, total_tombstones, total_replicated
+        );
+        total_values + total_tombstones
+    }
+}
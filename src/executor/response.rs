@@ -1,10 +1,13 @@
+#[cfg(test)]
 use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::oneshot::{Receiver, Sender};
+use tracing::Span;
 
 use crate::net::message::Message;
 
 pub(crate) struct ChanneledMessage {
     pub(crate) message: Message,
+    pub(crate) span: Span,
     response_sender: Sender<MessageStatus>,
 }
 
@@ -12,6 +15,7 @@ impl ChanneledMessage {
     pub(crate) fn new(message: Message, response_sender: Sender<MessageStatus>) -> Self {
         ChanneledMessage {
             message,
+            span: Span::current(),
             response_sender,
         }
     }
@@ -23,6 +27,7 @@ impl ChanneledMessage {
 
 pub(crate) enum MessageStatus {
     StoreDone,
+    DeleteDone,
     FindValueDone,
     PingDone,
     ReplyDone,
@@ -31,25 +36,41 @@ pub(crate) enum MessageStatus {
 }
 
 impl MessageStatus {
+    #[cfg(test)]
     pub(crate) fn is_store_done(&self) -> bool {
         if let MessageStatus::StoreDone = self {
             return true;
         }
         return false;
     }
+
+    #[cfg(test)]
+    pub(crate) fn is_delete_done(&self) -> bool {
+        if let MessageStatus::DeleteDone = self {
+            return true;
+        }
+        return false;
+    }
 }
 
 pub(crate) struct MessageResponse {
+    #[cfg(test)]
     receiver: Receiver<MessageStatus>,
 }
 
 impl MessageResponse {
+    // only the tests wait for the status, the executors run the messages without a caller
+    // waiting on them.
     pub(crate) fn new(response_receiver: Receiver<MessageStatus>) -> Self {
+        #[cfg(not(test))]
+        drop(response_receiver);
         MessageResponse {
+            #[cfg(test)]
             receiver: response_receiver,
         }
     }
 
+    #[cfg(test)]
     pub(crate) async fn wait_until_response_is_received(self) -> Result<MessageStatus, RecvError> {
         self.receiver.await
     }
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+use tokio::task::JoinSet;
+
+use crate::executor::ownership::OwnershipVerifier;
+use crate::net::node::Node;
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+
+pub(crate) struct RestoredContactsVerifier {
+    routing_table: Arc<Table>,
+    ownership_verifier: OwnershipVerifier,
+}
+
+impl RestoredContactsVerifier {
+    pub(crate) fn new(
+        current_node: Node,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+    ) -> Arc<Self> {
+        Arc::new(RestoredContactsVerifier {
+            routing_table,
+            ownership_verifier: OwnershipVerifier::new(current_node, async_network),
+        })
+    }
+
+    pub(crate) async fn verify(self: Arc<Self>, contacts: Vec<Node>) -> usize {
+        // the verifications are aborted along with this task when the node shuts down.
+        let mut verifications = JoinSet::new();
+        for contact in contacts {
+            let verifier = self.clone();
+            verifications.spawn(async move { verifier.verify_contact(contact).await });
+        }
+
+        let mut total_verified = 0;
+        while let Some(verified) = verifications.join_next().await {
+            if let Ok(true) = verified {
+                total_verified += 1;
+            }
+        }
+        info!("verified {} restored contacts", total_verified);
+        total_verified
+    }
+
+    async fn verify_contact(&self, contact: Node) -> bool {
+        if !self.ownership_verifier.verify(&contact).await {
+            warn!(
+                "restored contact {} could not be verified, skipping it",
+                contact.endpoint
+            );
+            return false;
+        }
+        self.routing_table.add(contact).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use crate::executor::ownership::tests::reply_to_pings;
+    use crate::executor::restore::RestoredContactsVerifier;
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
+    use crate::net::node::Node;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::routing::Table;
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn add_a_restored_contact_that_proves_the_ownership_of_its_id() {
+        let waiting_list = waiting_list();
+        let identity = Identity::from_bytes([7; 32]);
+        let contact =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 6101), &identity);
+        let handle = reply_to_pings(contact.clone(), identity, waiting_list.clone(), 1).await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let verifier = RestoredContactsVerifier::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list),
+        );
+
+        let total_verified = verifier.verify(vec![contact.clone()]).await;
+        handle.await.unwrap();
+
+        assert_eq!(1, total_verified);
+        assert!(routing_table.contains(&contact).1);
+    }
+
+    #[tokio::test]
+    async fn do_not_add_a_restored_contact_that_does_not_own_its_id() {
+        let waiting_list = waiting_list();
+        let contact = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6104),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        );
+        let handle = reply_to_pings(
+            contact.clone(),
+            Identity::from_bytes([7; 32]),
+            waiting_list.clone(),
+            1,
+        )
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let verifier = RestoredContactsVerifier::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list),
+        );
+
+        let total_verified = verifier.verify(vec![contact.clone()]).await;
+        handle.await.unwrap();
+
+        assert_eq!(0, total_verified);
+        assert!(!routing_table.contains(&contact).1);
+    }
+
+    #[tokio::test]
+    async fn do_not_add_an_unreachable_restored_contact() {
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let verifier = RestoredContactsVerifier::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(waiting_list()),
+        );
+
+        let contact = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6102),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        );
+        let total_verified = verifier.verify(vec![contact.clone()]).await;
+
+        assert_eq!(0, total_verified);
+        assert!(!routing_table.contains(&contact).1);
+    }
+
+    #[tokio::test]
+    async fn do_not_add_a_restored_contact_that_does_not_reply_to_ping() {
+        let listener_result = TcpListener::bind("localhost:6103").await;
+        assert!(listener_result.is_ok());
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let verifier = RestoredContactsVerifier::new(
+            current_node(),
+            routing_table.clone(),
+            AsyncNetwork::new(WaitingList::new(
+                WaitingListOptions::new(Duration::from_millis(50), Duration::from_millis(10)),
+                SystemClock::new(),
+            )),
+        );
+
+        let contact = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6103),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        );
+        let total_verified = verifier.verify(vec![contact.clone()]).await;
+
+        assert_eq!(0, total_verified);
+        assert!(!routing_table.contains(&contact).1);
+    }
+
+    fn current_node() -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 6100),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        )
+    }
+
+    fn waiting_list() -> Arc<WaitingList> {
+        WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100)),
+            SystemClock::new(),
+        )
+    }
+}
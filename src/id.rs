@@ -1,4 +1,5 @@
 use num_bigint::{BigInt, Sign};
+use rand::Rng;
 use ripemd::{Digest, Ripemd160};
 use serde::Deserialize;
 use serde::Serialize;
@@ -14,6 +15,7 @@ pub(crate) struct Id {
 }
 
 impl Id {
+    #[cfg(test)]
     pub(crate) fn generate_from(content: String) -> Self {
         Id::generate_from_bytes(content.as_bytes())
     }
@@ -30,6 +32,10 @@ impl Id {
         }
     }
 
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.id
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.id.len()
     }
@@ -59,7 +65,69 @@ impl Id {
         BigInt::from_bytes_be(Sign::Plus, &distance)
     }
 
-    #[cfg(test)]
+    pub(crate) fn from_hex(hex: &str) -> Option<Self> {
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        let id = (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Id::new(id))
+    }
+
+    pub(crate) fn to_hex(&self) -> String {
+        self.id.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub(crate) fn random_in_bucket(&self, bucket_index: usize) -> Id {
+        assert!(bucket_index < self.id_length_in_bits);
+        let bit_position = self.id_length_in_bits - bucket_index - 1;
+        let byte_index = bit_position / BITS_IN_BYTE;
+        let differing_bit = 1u8 << (BITS_IN_BYTE - 1 - bit_position % BITS_IN_BYTE);
+        let random_bits = differing_bit - 1;
+
+        let mut rng = rand::thread_rng();
+        let mut id = self.id.clone();
+        id[byte_index] =
+            ((id[byte_index] ^ differing_bit) & !random_bits) | (rng.gen::<u8>() & random_bits);
+        id.iter_mut()
+            .skip(byte_index + 1)
+            .for_each(|byte| *byte = rng.gen());
+        Id::new(id)
+    }
+
+    pub(crate) fn prefix(&self, prefix_length_in_bits: usize) -> Id {
+        let mut id = self.id.clone();
+        id.iter_mut().enumerate().for_each(|(byte_index, byte)| {
+            *byte &= Self::prefix_mask(prefix_length_in_bits, byte_index);
+        });
+        Id::new(id)
+    }
+
+    pub(crate) fn has_bit_set(&self, bit_position: usize) -> bool {
+        self.is_bit_set(
+            self.id[bit_position / BITS_IN_BYTE],
+            bit_position % BITS_IN_BYTE,
+        )
+    }
+
+    pub(crate) fn with_bit_set(&self, bit_position: usize) -> Id {
+        let mut id = self.id.clone();
+        id[bit_position / BITS_IN_BYTE] |= 1 << (BITS_IN_BYTE - 1 - bit_position % BITS_IN_BYTE);
+        Id::new(id)
+    }
+
+    pub(crate) fn random_with_prefix(&self, prefix_length_in_bits: usize) -> Id {
+        let mut rng = rand::thread_rng();
+        let mut id = self.id.clone();
+        id.iter_mut().enumerate().for_each(|(byte_index, byte)| {
+            let mask = Self::prefix_mask(prefix_length_in_bits, byte_index);
+            *byte = (*byte & mask) | (rng.gen::<u8>() & !mask);
+        });
+        Id::new(id)
+    }
+
     pub(crate) fn new(id: Vec<u8>) -> Self {
         let id_length_in_bits = id.len() * BITS_IN_BYTE;
         Id {
@@ -68,6 +136,14 @@ impl Id {
         }
     }
 
+    fn prefix_mask(prefix_length_in_bits: usize, byte_index: usize) -> u8 {
+        let bits_kept = prefix_length_in_bits.saturating_sub(byte_index * BITS_IN_BYTE);
+        if bits_kept >= BITS_IN_BYTE {
+            return u8::MAX;
+        }
+        !(u8::MAX >> bits_kept)
+    }
+
     fn bit_position_set_in(&self, byte: u8) -> (usize, bool) {
         for bit_position in 0..BITS_IN_BYTE {
             if self.is_bit_set(byte, bit_position) {
@@ -84,12 +160,20 @@ impl Id {
     }
 }
 
+pub fn node_id_to_hex(node_id: &[u8]) -> String {
+    Id::new(node_id.to_vec()).to_hex()
+}
+
+pub fn node_id_from_hex(hex: &str) -> Option<Vec<u8>> {
+    Id::from_hex(hex).map(|id| id.id)
+}
+
 #[cfg(test)]
 mod tests {
     use hex_literal::hex;
     use num_bigint::{BigInt, Sign};
 
-    use crate::id::{Id, EXPECTED_ID_LENGTH_IN_BYTES};
+    use crate::id::{node_id_from_hex, node_id_to_hex, Id, EXPECTED_ID_LENGTH_IN_BYTES};
 
     #[test]
     fn id_as_big_endian() {
@@ -116,6 +200,36 @@ mod tests {
         assert_eq!(id.id.len(), EXPECTED_ID_LENGTH_IN_BYTES);
     }
 
+    #[test]
+    fn prefix_of_an_id() {
+        let id = Id::new(vec![0b1011_0110, 0b1111_1111]);
+
+        assert_eq!(Id::new(vec![0, 0]), id.prefix(0));
+        assert_eq!(Id::new(vec![0b1010_0000, 0]), id.prefix(3));
+        assert_eq!(Id::new(vec![0b1011_0110, 0b1100_0000]), id.prefix(10));
+        assert_eq!(id, id.prefix(16));
+    }
+
+    #[test]
+    fn set_and_check_bits_of_an_id() {
+        let id = Id::new(vec![0, 0]);
+        let id = id.with_bit_set(0).with_bit_set(9);
+
+        assert_eq!(Id::new(vec![0b1000_0000, 0b0100_0000]), id);
+        assert!(id.has_bit_set(0));
+        assert!(id.has_bit_set(9));
+        assert!(!id.has_bit_set(1));
+    }
+
+    #[test]
+    fn random_id_with_prefix() {
+        let id = Id::generate_from("localhost:3290".to_string());
+        for prefix_length in [0, 5, 8, 100, 160] {
+            let random_id = id.random_with_prefix(prefix_length);
+            assert_eq!(id.prefix(prefix_length), random_id.prefix(prefix_length));
+        }
+    }
+
     #[test]
     fn distance_of_id_from_itself() {
         let id = Id::new(vec![0; EXPECTED_ID_LENGTH_IN_BYTES]);
@@ -167,4 +281,34 @@ mod tests {
         let differing_bit_position = id.differing_bit_position(&other_id);
         assert_eq!(0, differing_bit_position);
     }
+
+    #[test]
+    fn id_to_hex_and_back() {
+        let id = Id::new(vec![0, 15, 255, 16]);
+        assert_eq!("000fff10", id.to_hex());
+        assert_eq!(Some(id), Id::from_hex("000fff10"));
+    }
+
+    #[test]
+    fn node_id_to_hex_and_back() {
+        let node_id = vec![0, 15, 255, 16];
+        assert_eq!("000fff10", node_id_to_hex(&node_id));
+        assert_eq!(Some(node_id), node_id_from_hex("000fff10"));
+        assert_eq!(None, node_id_from_hex("abc"));
+    }
+
+    #[test]
+    fn do_not_parse_an_invalid_hex_id() {
+        assert_eq!(None, Id::from_hex("0g"));
+        assert_eq!(None, Id::from_hex("abc"));
+    }
+
+    #[test]
+    fn random_id_in_bucket() {
+        let id = Id::generate_from("kademlia".to_string());
+        for bucket_index in [0, 7, 8, 100, 159] {
+            let random_id = id.random_in_bucket(bucket_index);
+            assert_eq!(bucket_index, id.differing_bit_position(&random_id));
+        }
+    }
 }
@@ -0,0 +1,1018 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ed25519_dalek::{SigningKey, PUBLIC_KEY_LENGTH};
+use log::{error, info, warn};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinHandle;
+
+use crate::admin::{self, AdminState};
+use crate::config::{ConfigErrorKind, NodeConfig};
+use crate::executor::health::HealthProber;
+use crate::executor::ownership::{add_verified_nodes, OwnershipVerifier};
+use crate::executor::records::Records;
+use crate::executor::refresh::BucketRefresher;
+use crate::executor::republish::Republisher;
+use crate::executor::restore::RestoredContactsVerifier;
+use crate::id::{Id, EXPECTED_ID_LENGTH_IN_BYTES};
+use crate::lookup::IterativeLookup;
+use crate::net::endpoint::Endpoint;
+use crate::net::identity::{Identity, IdentityErrorKind};
+use crate::net::message::Message;
+use crate::net::node::Node;
+use crate::net::puzzle::{solve_dynamic_puzzle, PuzzleOptions};
+use crate::net::secure::SecureChannel;
+use crate::net::wait::WaitingList;
+use crate::net::{AsyncNetwork, NetworkErrorKind};
+use crate::routing::event::RoutingEvent;
+use crate::routing::persist::{load, PersistenceErrorKind, TablePersister, TablePersisterOptions};
+use crate::routing::Table;
+use crate::server::limit::RateLimiter;
+use crate::server::AsyncConnectionHandler;
+use crate::store::record::{ImmutableRecord, MutableRecord, RecordType};
+use crate::store::{InMemoryStore, Key, Store, VersionedValue};
+use crate::time::{Clock, SystemClock};
+
+const ADMIN_HOST: &str = "127.0.0.1";
+
+#[derive(Debug)]
+pub enum KademliaError {
+    Io(std::io::Error),
+    Config(String),
+    Identity(String),
+    Network(String),
+    Persistence(String),
+    InvalidArgument(String),
+    Bootstrap(String),
+}
+
+impl Display for KademliaError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KademliaError::Io(err) => write!(formatter, "io error: {}", err),
+            KademliaError::Config(description) => write!(formatter, "config err: {}", description),
+            KademliaError::Identity(description) => {
+                write!(formatter, "identity err: {}", description)
+            }
+            KademliaError::Network(description) => {
+                write!(formatter, "network err: {}", description)
+            }
+            KademliaError::Persistence(description) => {
+                write!(formatter, "persistence err: {}", description)
+            }
+            KademliaError::InvalidArgument(description) => {
+                write!(formatter, "invalid argument: {}", description)
+            }
+            KademliaError::Bootstrap(description) => {
+                write!(formatter, "bootstrap err: {}", description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KademliaError {}
+
+impl From<std::io::Error> for KademliaError {
+    fn from(err: std::io::Error) -> Self {
+        KademliaError::Io(err)
+    }
+}
+
+impl From<ConfigErrorKind> for KademliaError {
+    fn from(err: ConfigErrorKind) -> Self {
+        KademliaError::Config(err.to_string())
+    }
+}
+
+impl From<IdentityErrorKind> for KademliaError {
+    fn from(err: IdentityErrorKind) -> Self {
+        KademliaError::Identity(err.to_string())
+    }
+}
+
+impl From<NetworkErrorKind> for KademliaError {
+    fn from(err: NetworkErrorKind) -> Self {
+        KademliaError::Network(err.to_string())
+    }
+}
+
+impl From<PersistenceErrorKind> for KademliaError {
+    fn from(err: PersistenceErrorKind) -> Self {
+        KademliaError::Persistence(err.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Contact {
+    node_id: Vec<u8>,
+    address: String,
+}
+
+impl Contact {
+    pub(crate) fn new(node: &Node) -> Self {
+        Contact {
+            node_id: node.id.as_bytes().to_vec(),
+            address: node.endpoint.address(),
+        }
+    }
+
+    pub(crate) fn new_with_id(node_id: Vec<u8>, address: String) -> Self {
+        Contact { node_id, address }
+    }
+
+    pub fn node_id(&self) -> &[u8] {
+        &self.node_id
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+pub struct KademliaBuilder {
+    host: String,
+    port: u16,
+    config: NodeConfig,
+    config_file: Option<PathBuf>,
+    identity_file: Option<PathBuf>,
+    routing_table_file: Option<PathBuf>,
+    admin_port: Option<u16>,
+    secure: bool,
+}
+
+impl KademliaBuilder {
+    fn new() -> Self {
+        KademliaBuilder {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            config: NodeConfig::default(),
+            config_file: None,
+            identity_file: None,
+            routing_table_file: None,
+            admin_port: None,
+            secure: false,
+        }
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn config(mut self, config: NodeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config_file(mut self, file_path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(file_path.into());
+        self
+    }
+
+    pub fn identity_file(mut self, file_path: impl Into<PathBuf>) -> Self {
+        self.identity_file = Some(file_path.into());
+        self
+    }
+
+    pub fn routing_table_file(mut self, file_path: impl Into<PathBuf>) -> Self {
+        self.routing_table_file = Some(file_path.into());
+        self
+    }
+
+    pub fn admin_port(mut self, admin_port: u16) -> Self {
+        self.admin_port = Some(admin_port);
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub async fn start(self) -> Result<Kademlia, KademliaError> {
+        let config = match &self.config_file {
+            Some(file_path) => NodeConfig::load(file_path)?,
+            None => self.config,
+        };
+        config.validate()?;
+        let identity = Arc::new(match &self.identity_file {
+            Some(file_path) => {
+                Identity::load_or_generate(file_path, config.static_puzzle_difficulty)?
+            }
+            None => Identity::generate_with_static_puzzle(config.static_puzzle_difficulty),
+        });
+
+        let tcp_listener = TcpListener::bind((self.host.as_str(), self.port)).await?;
+        let port = tcp_listener.local_addr()?.port();
+        let node_id = identity.node_id();
+        let current_node = Node::new_with_puzzle_solution(
+            Endpoint::new(self.host, port),
+            node_id.clone(),
+            solve_dynamic_puzzle(&node_id, config.dynamic_puzzle_difficulty),
+        );
+
+        let waiting_list = WaitingList::new(config.waiting_list_options(), SystemClock::new());
+        let async_network = if self.secure {
+            AsyncNetwork::new_secure(waiting_list, SecureChannel::new(identity.clone())?)
+        } else {
+            AsyncNetwork::new(waiting_list)
+        };
+        let routing_table = Table::new_with_options(
+            node_id,
+            config.table_options(),
+            async_network.waiting_list().round_trip_times(),
+            SystemClock::new(),
+        );
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new());
+
+        let connection_handler = Arc::new(AsyncConnectionHandler::new(
+            current_node.clone(),
+            identity,
+            store.clone(),
+            async_network.clone(),
+            routing_table.clone(),
+            Arc::new(RateLimiter::new(
+                config.rate_limit_options(),
+                SystemClock::new(),
+            )),
+            &config,
+        ));
+        let accept_loop =
+            tokio::spawn(accept_connections(tcp_listener, connection_handler.clone()));
+
+        let (table_persister, contacts_verifier) = match self.routing_table_file {
+            Some(file_path) => {
+                // a corrupt table only costs the restored contacts, the node bootstraps again.
+                let contacts = load(&file_path).unwrap_or_else(|err| {
+                    warn!(
+                        "could not load the routing table from {:?}, starting with an empty table. {}",
+                        file_path, err
+                    );
+                    Vec::new()
+                });
+                let verifier = RestoredContactsVerifier::new(
+                    current_node.clone(),
+                    routing_table.clone(),
+                    async_network.clone(),
+                );
+                let contacts_verifier = tokio::spawn(verifier.verify(contacts));
+                (
+                    Some(TablePersister::new(
+                        routing_table.clone(),
+                        TablePersisterOptions::new(file_path, config.table_persist_interval()),
+                    )),
+                    Some(contacts_verifier),
+                )
+            }
+            None => (None, None),
+        };
+
+        let health_prober = config.health_prober_options().map(|options| {
+            tokio::spawn(
+                HealthProber::new(
+                    current_node.clone(),
+                    routing_table.clone(),
+                    async_network.clone(),
+                    options,
+                )
+                .run(),
+            )
+        });
+
+        let bucket_refresher = config.refresh_interval().map(|refresh_every| {
+            tokio::spawn(
+                BucketRefresher::new(
+                    current_node.clone(),
+                    routing_table.clone(),
+                    async_network.clone(),
+                    config.lookup_options(),
+                    config.puzzle_options(),
+                    refresh_every,
+                )
+                .run(),
+            )
+        });
+        let republisher = config.republish_interval().map(|republish_every| {
+            tokio::spawn(
+                Republisher::new(
+                    current_node.clone(),
+                    store.clone(),
+                    routing_table.clone(),
+                    async_network.clone(),
+                    config.lookup_options(),
+                    republish_every,
+                )
+                .run(),
+            )
+        });
+
+        let records = Records::new(
+            current_node.clone(),
+            store.clone(),
+            routing_table.clone(),
+            async_network.clone(),
+            config.lookup_options(),
+        );
+        let (admin_address, admin_loop) = match self.admin_port {
+            Some(admin_port) => {
+                let admin_listener = TcpListener::bind((ADMIN_HOST, admin_port)).await?;
+                let admin_address = admin_listener.local_addr()?.to_string();
+                let admin_state = Arc::new(AdminState {
+                    current_node: current_node.clone(),
+                    routing_table: routing_table.clone(),
+                    store: store.clone(),
+                    async_network: async_network.clone(),
+                    connection_handler: connection_handler.clone(),
+                    lookup: IterativeLookup::new(
+                        current_node.clone(),
+                        routing_table.clone(),
+                        async_network.clone(),
+                        config.lookup_options(),
+                    ),
+                    ownership_verifier: OwnershipVerifier::new(
+                        current_node.clone(),
+                        async_network.clone(),
+                    ),
+                    puzzle_options: config.puzzle_options(),
+                    records: records.clone(),
+                    clock: SystemClock::new(),
+                });
+                info!("serving the admin interface at {}", admin_address);
+                (
+                    Some(admin_address),
+                    Some(tokio::spawn(admin::serve(admin_listener, admin_state))),
+                )
+            }
+            None => (None, None),
+        };
+
+        info!("started kademlia node at {}", current_node.endpoint);
+        Ok(Kademlia {
+            lookup: IterativeLookup::new(
+                current_node.clone(),
+                routing_table.clone(),
+                async_network.clone(),
+                config.lookup_options(),
+            ),
+            ownership_verifier: OwnershipVerifier::new(current_node.clone(), async_network.clone()),
+            records,
+            puzzle_options: config.puzzle_options(),
+            current_node,
+            #[cfg(test)]
+            store,
+            routing_table,
+            async_network,
+            connection_handler,
+            table_persister,
+            contacts_verifier,
+            health_prober,
+            bucket_refresher,
+            republisher,
+            accept_loop,
+            admin_address,
+            admin_loop,
+            clock: SystemClock::new(),
+        })
+    }
+}
+
+pub struct Kademlia {
+    current_node: Node,
+    #[cfg(test)]
+    store: Arc<dyn Store>,
+    routing_table: Arc<Table>,
+    async_network: Arc<AsyncNetwork>,
+    connection_handler: Arc<AsyncConnectionHandler>,
+    lookup: IterativeLookup,
+    ownership_verifier: OwnershipVerifier,
+    records: Arc<Records>,
+    puzzle_options: PuzzleOptions,
+    table_persister: Option<Arc<TablePersister>>,
+    contacts_verifier: Option<JoinHandle<usize>>,
+    health_prober: Option<JoinHandle<()>>,
+    bucket_refresher: Option<JoinHandle<()>>,
+    republisher: Option<JoinHandle<()>>,
+    accept_loop: JoinHandle<()>,
+    admin_address: Option<String>,
+    admin_loop: Option<JoinHandle<()>>,
+    clock: Box<dyn Clock>,
+}
+
+impl Kademlia {
+    pub fn builder() -> KademliaBuilder {
+        KademliaBuilder::new()
+    }
+
+    pub fn node_id(&self) -> &[u8] {
+        self.current_node.id.as_bytes()
+    }
+
+    pub fn address(&self) -> String {
+        self.current_node.endpoint.address()
+    }
+
+    pub fn admin_address(&self) -> Option<String> {
+        self.admin_address.clone()
+    }
+
+    pub fn subscribe(&self) -> Receiver<RoutingEvent> {
+        self.routing_table.subscribe()
+    }
+
+    pub async fn bootstrap(&self, address: &str) -> Result<(), KademliaError> {
+        let endpoint = endpoint_from(address)?;
+        let bootstrap_node = self
+            .ownership_verifier
+            .discover(&endpoint)
+            .await
+            .ok_or_else(|| {
+                KademliaError::Bootstrap(format!("could not verify the node at {}", address))
+            })?;
+        if !self
+            .puzzle_options
+            .verify(&bootstrap_node.id, &bootstrap_node.puzzle_solution)
+        {
+            return Err(KademliaError::Bootstrap(format!(
+                "node at {} does not solve the node id puzzles",
+                address
+            )));
+        }
+        let (_, added) = self.routing_table.add(bootstrap_node.clone());
+        if !added && !self.routing_table.contains(&bootstrap_node).1 {
+            warn!(
+                "could not add the bootstrap node at {} to the routing table, looking up through the known contacts",
+                address
+            );
+        }
+
+        add_verified_nodes(
+            self.lookup.find_node(&self.current_node.id).await,
+            &self.current_node,
+            &self.puzzle_options,
+            &self.ownership_verifier,
+            &self.routing_table,
+        )
+        .await;
+        info!(
+            "bootstrapped from {} with {} contacts",
+            address,
+            self.routing_table.all_nodes().len()
+        );
+        Ok(())
+    }
+
+    pub async fn ping(&self, address: &str) -> Result<Contact, KademliaError> {
+        let endpoint = endpoint_from(address)?;
+        self.ownership_verifier
+            .discover(&endpoint)
+            .await
+            .map(|node| Contact::new(&node))
+            .ok_or_else(|| KademliaError::Network(format!("no reply from the node at {}", address)))
+    }
+
+    pub async fn put(&self, key: &[u8], value: &[u8]) -> Result<(), KademliaError> {
+        let version = self.clock.now_millis();
+        let message = Message::store_type(
+            key.to_vec(),
+            value.to_vec(),
+            version,
+            self.current_node.clone(),
+        );
+        self.put_record(
+            Key::new(key.to_vec()),
+            VersionedValue::new(value.to_vec(), version),
+            message,
+        )
+        .await
+    }
+
+    // The key of an immutable value is the hash of the value, it is returned to look the value up.
+    pub async fn put_immutable(&self, value: &[u8]) -> Result<Vec<u8>, KademliaError> {
+        let record = ImmutableRecord::new(value.to_vec());
+        let key = record.key.clone();
+        let value = record.value.clone();
+        let message = Message::immutable_store_type(record, self.current_node.clone());
+        self.put_record(Key::new(key.clone()), value, message)
+            .await?;
+        Ok(key)
+    }
+
+    // The key of a mutable value is the public key of the signer followed by the salt. Only the
+    // owner of the secret key can store a value under it, a higher sequence replaces the value.
+    pub async fn put_mutable(
+        &self,
+        secret_key: &[u8; 32],
+        salt: Option<&[u8]>,
+        sequence: u64,
+        value: &[u8],
+    ) -> Result<Vec<u8>, KademliaError> {
+        let record = MutableRecord::sign(
+            &SigningKey::from_bytes(secret_key),
+            salt.map(|salt| salt.to_vec()),
+            sequence,
+            value.to_vec(),
+        );
+        let key = record.key.clone();
+        let value = record.value.clone();
+        let message = Message::mutable_store_type(record, self.current_node.clone());
+        self.put_record(Key::new(key.clone()), value, message)
+            .await?;
+        Ok(key)
+    }
+
+    pub async fn delete(&self, key: &[u8]) -> Result<(), KademliaError> {
+        self.records
+            .delete(Key::new(key.to_vec()), self.clock.now_millis())
+            .await;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, KademliaError> {
+        Ok(self
+            .records
+            .latest_value(key, RecordType::Plain)
+            .await
+            .map(|versioned_value| versioned_value.value))
+    }
+
+    pub async fn get_immutable(&self, key: &[u8]) -> Result<Option<Vec<u8>>, KademliaError> {
+        if key.len() != EXPECTED_ID_LENGTH_IN_BYTES {
+            return Err(KademliaError::InvalidArgument(format!(
+                "immutable key must be {} bytes, found {}",
+                EXPECTED_ID_LENGTH_IN_BYTES,
+                key.len()
+            )));
+        }
+        Ok(self
+            .records
+            .latest_value(key, RecordType::Immutable)
+            .await
+            .map(|versioned_value| versioned_value.value))
+    }
+
+    // Returns the sequence and the value with the highest sequence.
+    pub async fn get_mutable(&self, key: &[u8]) -> Result<Option<(u64, Vec<u8>)>, KademliaError> {
+        if key.len() < PUBLIC_KEY_LENGTH {
+            return Err(KademliaError::InvalidArgument(format!(
+                "mutable key must start with a {} bytes public key, found {} bytes",
+                PUBLIC_KEY_LENGTH,
+                key.len()
+            )));
+        }
+        Ok(self
+            .records
+            .latest_value(key, RecordType::Mutable)
+            .await
+            .map(|versioned_value| (versioned_value.version, versioned_value.value)))
+    }
+
+    pub async fn find_node(&self, node_id: &[u8]) -> Result<Vec<Contact>, KademliaError> {
+        if node_id.len() != EXPECTED_ID_LENGTH_IN_BYTES {
+            return Err(KademliaError::InvalidArgument(format!(
+                "node id must be {} bytes, found {}",
+                EXPECTED_ID_LENGTH_IN_BYTES,
+                node_id.len()
+            )));
+        }
+        Ok(self
+            .lookup
+            .find_node(&Id::new(node_id.to_vec()))
+            .await
+            .iter()
+            .map(Contact::new)
+            .collect())
+    }
+
+    async fn put_record(
+        &self,
+        key: Key,
+        value: VersionedValue,
+        message: Message,
+    ) -> Result<(), KademliaError> {
+        let key_id = key.id.to_hex();
+        if !self.records.put(key, value, message).await {
+            return Err(KademliaError::InvalidArgument(format!(
+                "the value can not replace the record stored for the key id {}",
+                key_id
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn shutdown(self) -> Result<(), KademliaError> {
+        self.accept_loop.abort();
+        if let Some(admin_loop) = &self.admin_loop {
+            admin_loop.abort();
+        }
+        if let Some(contacts_verifier) = &self.contacts_verifier {
+            contacts_verifier.abort();
+        }
+        if let Some(health_prober) = &self.health_prober {
+            health_prober.abort();
+        }
+        if let Some(bucket_refresher) = &self.bucket_refresher {
+            bucket_refresher.abort();
+        }
+        if let Some(republisher) = &self.republisher {
+            republisher.abort();
+        }
+        self.connection_handler.shutdown().await;
+        self.async_network.waiting_list().stop();
+        if let Some(table_persister) = &self.table_persister {
+            table_persister.stop()?;
+        }
+        info!("stopped kademlia node at {}", self.current_node.endpoint);
+        Ok(())
+    }
+}
+
+async fn accept_connections(
+    tcp_listener: TcpListener,
+    connection_handler: Arc<AsyncConnectionHandler>,
+) {
+    loop {
+        match tcp_listener.accept().await {
+            Ok((tcp_stream, _)) => {
+                let connection_handler = connection_handler.clone();
+                tokio::spawn(async move { connection_handler.accept(tcp_stream).await });
+            }
+            Err(err) => error!("could not accept a connection {:?}", err),
+        }
+    }
+}
+
+pub(crate) fn endpoint_from(address: &str) -> Result<Endpoint, KademliaError> {
+    let invalid_address =
+        || KademliaError::InvalidArgument(format!("expected host:port, found {}", address));
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid_address)?;
+    let port = port.parse::<u16>().map_err(|_| invalid_address())?;
+    Ok(Endpoint::new(host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::config::NodeConfig;
+    use crate::kademlia::{endpoint_from, Kademlia, KademliaError};
+    use crate::routing::event::RoutingEvent;
+    use crate::store::{Key, VersionedValue};
+    use crate::time::{Clock, SystemClock};
+
+    #[test]
+    fn endpoint_from_an_address() {
+        let endpoint = endpoint_from("127.0.0.1:2379").unwrap();
+        assert_eq!("127.0.0.1:2379", endpoint.address());
+    }
+
+    #[test]
+    fn do_not_parse_an_address_without_port() {
+        assert!(matches!(
+            endpoint_from("127.0.0.1"),
+            Err(KademliaError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn put_and_get_a_value_across_nodes() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let third = Kademlia::builder().start().await.unwrap();
+
+        second.bootstrap(&first.address()).await.unwrap();
+        third.bootstrap(&first.address()).await.unwrap();
+
+        second
+            .put(b"kademlia", b"distributed hash table")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(
+            Some(b"distributed hash table".to_vec()),
+            first.get(b"kademlia").await.unwrap()
+        );
+        assert_eq!(
+            Some(b"distributed hash table".to_vec()),
+            third.get(b"kademlia").await.unwrap()
+        );
+
+        first.store.delete(b"kademlia");
+        first.store.put_or_update(
+            Key::new(b"kademlia".to_vec()),
+            VersionedValue::new(b"stale".to_vec(), 1),
+        );
+        assert_eq!(
+            Some(b"distributed hash table".to_vec()),
+            first.get(b"kademlia").await.unwrap()
+        );
+
+        let contacts = third.find_node(second.node_id()).await.unwrap();
+        assert!(contacts
+            .iter()
+            .any(|contact| contact.node_id() == second.node_id()));
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+        third.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn put_and_get_an_immutable_value_across_nodes() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let third = Kademlia::builder().start().await.unwrap();
+
+        second.bootstrap(&first.address()).await.unwrap();
+        third.bootstrap(&first.address()).await.unwrap();
+
+        let key = second
+            .put_immutable(b"distributed hash table")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        third.store.delete(&key);
+        assert_eq!(
+            Some(b"distributed hash table".to_vec()),
+            third.get_immutable(&key).await.unwrap()
+        );
+        assert_eq!(None, first.get(&key).await.unwrap());
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+        third.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn put_and_get_a_mutable_value_across_nodes() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let third = Kademlia::builder().start().await.unwrap();
+
+        second.bootstrap(&first.address()).await.unwrap();
+        third.bootstrap(&first.address()).await.unwrap();
+
+        let secret_key = [7; 32];
+        let key = second
+            .put_mutable(&secret_key, Some(b"salt"), 1, b"hash table")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let other_key = third
+            .put_mutable(&secret_key, Some(b"salt"), 2, b"distributed hash table")
+            .await
+            .unwrap();
+        assert_eq!(key, other_key);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(matches!(
+            third.put(&key, b"shadow").await,
+            Err(KademliaError::InvalidArgument(_))
+        ));
+        assert!(third.store.get_versioned(&key).unwrap().is_mutable());
+
+        assert_eq!(
+            Some((2, b"distributed hash table".to_vec())),
+            first.get_mutable(&key).await.unwrap()
+        );
+        assert_eq!(
+            Some((2, b"distributed hash table".to_vec())),
+            third.get_mutable(&key).await.unwrap()
+        );
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+        third.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn do_not_start_with_an_invalid_config() {
+        let result = Kademlia::builder()
+            .config(NodeConfig {
+                disjoint_paths: 0,
+                ..NodeConfig::default()
+            })
+            .start()
+            .await;
+        assert!(matches!(result, Err(KademliaError::Config(_))));
+
+        let result = Kademlia::builder()
+            .config(NodeConfig {
+                message_executor_queue_size: 0,
+                ..NodeConfig::default()
+            })
+            .start()
+            .await;
+        assert!(matches!(result, Err(KademliaError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn reject_a_mutable_key_shorter_than_a_public_key() {
+        let kademlia = Kademlia::builder().start().await.unwrap();
+
+        assert!(matches!(
+            kademlia.get_mutable(b"short").await,
+            Err(KademliaError::InvalidArgument(_))
+        ));
+        kademlia.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn republish_the_stored_values_periodically() {
+        let first = Kademlia::builder()
+            .config(NodeConfig {
+                republish_interval_secs: 1,
+                ..NodeConfig::default()
+            })
+            .start()
+            .await
+            .unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+
+        first.store.put_or_update(
+            Key::new(b"kademlia".to_vec()),
+            VersionedValue::new(b"distributed hash table".to_vec(), 1),
+        );
+        assert!(second.store.get_versioned(b"kademlia").is_none());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(
+            Some(b"distributed hash table".to_vec()),
+            second
+                .store
+                .get_versioned(b"kademlia")
+                .map(|versioned_value| versioned_value.value)
+        );
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn delete_a_value_across_nodes() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let third = Kademlia::builder().start().await.unwrap();
+
+        second.bootstrap(&first.address()).await.unwrap();
+        third.bootstrap(&first.address()).await.unwrap();
+
+        second
+            .put(b"kademlia", b"distributed hash table")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(first.get(b"kademlia").await.unwrap().is_some());
+
+        third.delete(b"kademlia").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(None, first.get(b"kademlia").await.unwrap());
+        assert_eq!(None, second.get(b"kademlia").await.unwrap());
+        assert_eq!(None, third.get(b"kademlia").await.unwrap());
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+        third.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn do_not_get_a_deleted_value_from_a_replica_that_missed_the_delete() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let third = Kademlia::builder().start().await.unwrap();
+
+        second.bootstrap(&first.address()).await.unwrap();
+        third.bootstrap(&first.address()).await.unwrap();
+        second.bootstrap(&third.address()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        first.store.put_or_update(
+            Key::new(b"kademlia".to_vec()),
+            VersionedValue::new(b"distributed hash table".to_vec(), 1),
+        );
+        third.store.delete_with_tombstone(
+            Key::new(b"kademlia".to_vec()),
+            SystemClock::new().now_millis(),
+        );
+
+        assert_eq!(None, first.get(b"kademlia").await.unwrap());
+        assert_eq!(None, second.get(b"kademlia").await.unwrap());
+        assert_eq!(None, third.get(b"kademlia").await.unwrap());
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+        third.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn republish_the_tombstones_periodically() {
+        let first = Kademlia::builder()
+            .config(NodeConfig {
+                republish_interval_secs: 1,
+                ..NodeConfig::default()
+            })
+            .start()
+            .await
+            .unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        second.bootstrap(&first.address()).await.unwrap();
+
+        second.store.put_or_update(
+            Key::new(b"kademlia".to_vec()),
+            VersionedValue::new(b"distributed hash table".to_vec(), 1),
+        );
+        first.store.delete_with_tombstone(
+            Key::new(b"kademlia".to_vec()),
+            SystemClock::new().now_millis(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(second.store.get_versioned(b"kademlia").is_none());
+        assert!(second.store.is_tombstoned(b"kademlia"));
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn start_with_an_empty_table_given_a_corrupt_table_file() {
+        let file_path =
+            env::temp_dir().join(format!("kademlia-{}-corrupt.table", std::process::id()));
+        fs::write(&file_path, b"not a routing table").unwrap();
+
+        let kademlia = Kademlia::builder()
+            .routing_table_file(&file_path)
+            .start()
+            .await
+            .unwrap();
+        assert!(kademlia.routing_table.all_nodes().is_empty());
+
+        kademlia.shutdown().await.unwrap();
+        let _ = fs::remove_file(&file_path);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn ping_a_node() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+
+        let contact = second.ping(&first.address()).await.unwrap();
+        assert_eq!(first.node_id(), contact.node_id());
+        assert_eq!(first.address(), contact.address());
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn notify_subscribers_of_contacts_added_while_bootstrapping() {
+        let first = Kademlia::builder().start().await.unwrap();
+        let second = Kademlia::builder().start().await.unwrap();
+        let mut events = second.subscribe();
+
+        second.bootstrap(&first.address()).await.unwrap();
+
+        assert!(matches!(
+            events.try_recv(),
+            Ok(RoutingEvent::NodeAdded { node_id, .. }) if node_id == first.node_id()
+        ));
+
+        first.shutdown().await.unwrap();
+        second.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn do_not_find_a_node_with_an_invalid_id() {
+        let kademlia = Kademlia::builder().start().await.unwrap();
+
+        assert!(matches!(
+            kademlia.find_node(b"kademlia").await,
+            Err(KademliaError::InvalidArgument(_))
+        ));
+        kademlia.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn do_not_bootstrap_from_an_unreachable_node() {
+        let kademlia = Kademlia::builder().start().await.unwrap();
+
+        assert!(matches!(
+            kademlia.bootstrap("127.0.0.1:1").await,
+            Err(KademliaError::Bootstrap(_))
+        ));
+        kademlia.shutdown().await.unwrap();
+    }
+}
@@ -1,9 +1,23 @@
 extern crate core;
 
+mod admin;
+mod client;
+mod config;
 pub(crate) mod executor;
 mod id;
+mod kademlia;
+pub(crate) mod lookup;
+mod metrics;
 pub(crate) mod net;
 mod routing;
 mod server;
 mod store;
 mod time;
+
+pub use crate::client::Client;
+pub use crate::config::NodeConfig;
+pub use crate::id::{node_id_from_hex, node_id_to_hex};
+pub use crate::kademlia::{Contact, Kademlia, KademliaBuilder, KademliaError};
+pub use crate::routing::event::RoutingEvent;
+pub use crate::routing::proximity::ProximityPolicy;
+pub use crate::routing::RoutingLayout;
@@ -0,0 +1,778 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::{info, warn};
+use tracing::field::{display, Empty};
+use tracing::{info_span, instrument, Instrument, Span};
+
+use crate::id::Id;
+use crate::lookup::shortlist::Shortlist;
+use crate::net::callback::ResponseAwaitingCallback;
+use crate::net::message::{Message, Source};
+use crate::net::node::{Node, NodeId};
+use crate::net::AsyncNetwork;
+use crate::routing::Table;
+use crate::store::record::RecordType;
+use crate::store::{KeyId, VersionedValue};
+
+mod shortlist;
+
+#[derive(Copy, Clone)]
+pub(crate) struct LookupOptions {
+    pub(crate) alpha: usize,
+    pub(crate) k: usize,
+    pub(crate) disjoint_paths: usize,
+}
+
+impl LookupOptions {
+    pub(crate) fn new(alpha: usize, k: usize, disjoint_paths: usize) -> Self {
+        assert!(disjoint_paths > 0);
+        LookupOptions {
+            alpha,
+            k,
+            disjoint_paths,
+        }
+    }
+}
+
+// The newest value found, unless a tombstone found along with it deletes it. The latest
+// tombstone is returned as well, it also deletes the older values the caller knows of.
+pub(crate) struct FoundValue {
+    pub(crate) value: Option<VersionedValue>,
+    pub(crate) deleted_at: Option<u64>,
+}
+
+pub(crate) struct IterativeLookup {
+    current_node: Node,
+    routing_table: Arc<Table>,
+    async_network: Arc<AsyncNetwork>,
+    options: LookupOptions,
+}
+
+impl IterativeLookup {
+    pub(crate) fn new(
+        current_node: Node,
+        routing_table: Arc<Table>,
+        async_network: Arc<AsyncNetwork>,
+        options: LookupOptions,
+    ) -> Self {
+        IterativeLookup {
+            current_node,
+            routing_table,
+            async_network,
+            options,
+        }
+    }
+
+    #[instrument(name = "lookup", skip_all, fields(kind = "find_node", target = %node_id.to_hex(), hops = Empty))]
+    pub(crate) async fn find_node(&self, node_id: &NodeId) -> Vec<Node> {
+        let message = Message::find_node_type(self.current_node.clone(), node_id.clone());
+        let mut paths = self.initial_paths(node_id);
+        let active_paths = vec![true; paths.len()];
+        let mut queried_node_ids = HashSet::new();
+
+        let mut hops = 0;
+        loop {
+            let replies = self
+                .query_next(&paths, &active_paths, &mut queried_node_ids, &message)
+                .await;
+            if replies.is_none() {
+                break;
+            }
+            hops += 1;
+            for (path_index, node, reply) in replies.unwrap() {
+                match reply {
+                    Some(Message::FindNodeReply { neighbors, .. }) => {
+                        paths[path_index].add_missing(self.to_nodes(neighbors))
+                    }
+                    _ => paths[path_index].remove(&node),
+                }
+            }
+        }
+
+        self.async_network.metrics().observe_lookup_hops(hops);
+        Span::current().record("hops", hops);
+
+        let mut merged = Shortlist::new(node_id.clone(), self.options.k);
+        paths
+            .iter()
+            .for_each(|path| merged.add_missing(path.closest().to_vec()));
+
+        let closest = merged.closest().to_vec();
+        info!(
+            "found {} closest nodes for the id {:?} over {} disjoint paths",
+            closest.len(),
+            node_id,
+            paths.len()
+        );
+        closest
+    }
+
+    #[instrument(name = "lookup", skip_all, fields(kind = "find_value", target = Empty, hops = Empty))]
+    pub(crate) async fn find_value(&self, key: Vec<u8>, record_type: RecordType) -> FoundValue {
+        let key_id = KeyId::generate_from_bytes(&key);
+        Span::current().record("target", display(key_id.to_hex()));
+        let message = Message::find_value_type(self.current_node.clone(), key.clone());
+        let mut paths = self.initial_paths(&key_id);
+        let mut active_paths = vec![true; paths.len()];
+        let mut queried_node_ids = HashSet::new();
+        let mut values: Vec<VersionedValue> = Vec::new();
+        let mut deleted_at: Option<u64> = None;
+
+        let mut hops = 0;
+        loop {
+            let replies = self
+                .query_next(&paths, &active_paths, &mut queried_node_ids, &message)
+                .await;
+            if replies.is_none() {
+                break;
+            }
+            hops += 1;
+            for (path_index, node, reply) in replies.unwrap() {
+                match reply {
+                    Some(Message::FindValueReply {
+                        value,
+                        deleted_at: tombstone_deleted_at,
+                        neighbors,
+                        ..
+                    }) => {
+                        if let Some(tombstone_deleted_at) = tombstone_deleted_at {
+                            deleted_at = deleted_at.max(Some(tombstone_deleted_at));
+                            active_paths[path_index] = false;
+                        }
+                        match value {
+                            Some(value) if value.kind.record_type() != record_type => {
+                                warn!(
+                                    "discarding a value of type {:?} while looking for a {:?} record from {}",
+                                    value.kind.record_type(),
+                                    record_type,
+                                    node.endpoint
+                                )
+                            }
+                            Some(value) if value.verify(&key) => {
+                                values.push(value);
+                                active_paths[path_index] = false;
+                            }
+                            Some(_) => {
+                                warn!(
+                                    "discarding a value that does not verify against its key from {}",
+                                    node.endpoint
+                                )
+                            }
+                            None => {}
+                        }
+                        if let Some(neighbors) = neighbors {
+                            paths[path_index].add_missing(self.to_nodes(neighbors));
+                        }
+                    }
+                    _ => paths[path_index].remove(&node),
+                }
+            }
+        }
+        self.async_network.metrics().observe_lookup_hops(hops);
+        Span::current().record("hops", hops);
+        info!(
+            "received {} values for the key id {:?} over {} disjoint paths",
+            values.len(),
+            key_id,
+            paths.len()
+        );
+        let value = values
+            .into_iter()
+            .max_by(|value, other| {
+                if value.is_newer_than(other) {
+                    Ordering::Greater
+                } else if other.is_newer_than(value) {
+                    Ordering::Less
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .filter(|value| deleted_at.is_none_or(|deleted_at| !value.is_deleted_by(deleted_at)));
+        FoundValue { value, deleted_at }
+    }
+
+    fn initial_paths(&self, target: &Id) -> Vec<Shortlist> {
+        let mut paths: Vec<Shortlist> = (0..self.options.disjoint_paths)
+            .map(|_| Shortlist::new(target.clone(), self.options.k))
+            .collect();
+
+        let closest_neighbors = self.routing_table.closest_neighbors(target, self.options.k);
+        closest_neighbors
+            .all_nodes()
+            .iter()
+            .enumerate()
+            .for_each(|(index, node)| {
+                paths[index % self.options.disjoint_paths].add_missing(vec![node.clone()])
+            });
+        paths
+    }
+
+    async fn query_next(
+        &self,
+        paths: &[Shortlist],
+        active_paths: &[bool],
+        queried_node_ids: &mut HashSet<NodeId>,
+        message: &Message,
+    ) -> Option<Vec<(usize, Node, Option<Message>)>> {
+        let nodes: Vec<(usize, Node)> = paths
+            .iter()
+            .enumerate()
+            .filter(|(path_index, _)| active_paths[*path_index])
+            .flat_map(|(path_index, path)| {
+                path.next_unqueried(self.options.alpha, queried_node_ids, |node| {
+                    self.routing_table
+                        .proximity_policy()
+                        .preference_key(self.async_network.smoothed_round_trip_time(&node.endpoint))
+                })
+                .into_iter()
+                .map(move |node| (path_index, node))
+            })
+            .collect();
+        if nodes.is_empty() {
+            return None;
+        }
+        let handles: Vec<_> = nodes
+            .into_iter()
+            .map(|(path_index, node)| {
+                let async_network = self.async_network.clone();
+                let routing_table = self.routing_table.clone();
+                let message = message.clone();
+                let rpc_span = info_span!(
+                    "rpc",
+                    r#type = ?message.message_type(),
+                    endpoint = %node.endpoint,
+                    message_id = Empty
+                );
+                tokio::spawn(
+                    async move {
+                        let callback = ResponseAwaitingCallback::new();
+                        let send_result = async_network
+                            .send_with_message_id_expect_reply(
+                                message,
+                                &node.endpoint,
+                                callback.clone(),
+                            )
+                            .await;
+                        if send_result.is_err() {
+                            warn!("could not send the lookup message to {}", node.endpoint);
+                            routing_table.mark_failed(&node);
+                            return (path_index, node, None);
+                        }
+                        let _ = callback.handle().await;
+                        let reply = callback.handle().response();
+                        match reply {
+                            Some(_) => routing_table.mark_seen(&node),
+                            None => {
+                                routing_table.mark_failed(&node);
+                            }
+                        }
+                        (path_index, node, reply)
+                    }
+                    .instrument(rpc_span),
+                )
+            })
+            .collect();
+
+        let mut replies = Vec::with_capacity(handles.len());
+        for handle in handles {
+            if let Ok(reply) = handle.await {
+                replies.push(reply);
+            }
+        }
+        Some(replies)
+    }
+
+    fn to_nodes(&self, sources: Vec<Source>) -> Vec<Node> {
+        sources
+            .into_iter()
+            .map(|source| source.to_node())
+            .filter(|node| node.id != self.current_node.id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use ed25519_dalek::SigningKey;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{self, Attributes, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::{Layer, Registry};
+
+    use crate::id::Id;
+    use crate::lookup::{IterativeLookup, LookupOptions};
+    use crate::net::connection::AsyncTcpConnection;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::message::{Message, Source};
+    use crate::net::node::Node;
+    use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
+    use crate::routing::Table;
+    use crate::store::record::{MutableRecord, RecordType};
+    use crate::store::VersionedValue;
+    use crate::time::SystemClock;
+
+    #[tokio::test]
+    async fn find_value_with_the_highest_version() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6121, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new("hash table".as_bytes().to_vec(), 1)),
+                None,
+            )
+        })
+        .await;
+        let other_handle = reply_from_peer(6122, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new(
+                    "distributed hash table".as_bytes().to_vec(),
+                    2,
+                )),
+                None,
+            )
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6121, 247));
+        routing_table.add(node(6122, 249));
+
+        let lookup = lookup(routing_table, waiting_list);
+        let value = lookup
+            .find_value("kademlia".as_bytes().to_vec(), RecordType::Plain)
+            .await;
+
+        handle.await.unwrap();
+        other_handle.await.unwrap();
+
+        let value = value.value.unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert_eq!(2, value.version);
+    }
+
+    #[tokio::test]
+    async fn find_the_same_value_as_the_store_given_equal_versions() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6187, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new("b".as_bytes().to_vec(), 1)),
+                None,
+            )
+        })
+        .await;
+        let other_handle = reply_from_peer(6188, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new("a".as_bytes().to_vec(), 1)),
+                None,
+            )
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6187, 247));
+        routing_table.add(node(6188, 249));
+
+        let lookup = lookup(routing_table, waiting_list);
+        let found = lookup
+            .find_value("kademlia".as_bytes().to_vec(), RecordType::Plain)
+            .await;
+
+        handle.await.unwrap();
+        other_handle.await.unwrap();
+
+        assert_eq!(
+            Some(VersionedValue::new("b".as_bytes().to_vec(), 1)),
+            found.value
+        );
+    }
+
+    #[tokio::test]
+    async fn find_no_value_when_a_peer_has_a_newer_tombstone() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6185, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new(
+                    "distributed hash table".as_bytes().to_vec(),
+                    1,
+                )),
+                None,
+            )
+        })
+        .await;
+        let tombstone_handle = reply_from_peer(6186, waiting_list.clone(), |message_id| {
+            Message::find_value_tombstone_reply_type(message_id, 2)
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6185, 247));
+        routing_table.add(node(6186, 249));
+
+        let lookup = lookup(routing_table, waiting_list);
+        let found = lookup
+            .find_value("kademlia".as_bytes().to_vec(), RecordType::Plain)
+            .await;
+
+        handle.await.unwrap();
+        tombstone_handle.await.unwrap();
+
+        assert!(found.value.is_none());
+        assert_eq!(Some(2), found.deleted_at);
+    }
+
+    #[tokio::test]
+    async fn find_value_of_the_expected_record_type() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6177, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(message_id, Some(mutable_record().value), None)
+        })
+        .await;
+        let shadowing_handle = reply_from_peer(6178, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new("shadow".as_bytes().to_vec(), 100)),
+                None,
+            )
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6177, 247));
+        routing_table.add(node(6178, 249));
+
+        let record = mutable_record();
+        let lookup = lookup(routing_table, waiting_list);
+        let value = lookup
+            .find_value(record.key.clone(), RecordType::Mutable)
+            .await;
+
+        handle.await.unwrap();
+        shadowing_handle.await.unwrap();
+
+        assert_eq!(Some(record.value), value.value);
+    }
+
+    #[tokio::test]
+    async fn find_value_that_does_not_exist() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6123, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(message_id, None, Some(Vec::new()))
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6123, 247));
+
+        let lookup = lookup(routing_table, waiting_list);
+        let value = lookup
+            .find_value("kademlia".as_bytes().to_vec(), RecordType::Plain)
+            .await;
+        handle.await.unwrap();
+
+        assert!(value.value.is_none());
+    }
+
+    #[tokio::test]
+    async fn find_node_through_the_neighbors_of_a_peer() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6124, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, vec![Source::new(&node(6125, 249))])
+        })
+        .await;
+        let other_handle = reply_from_peer(6125, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, Vec::new())
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6124, 247));
+
+        let lookup = lookup(routing_table, waiting_list);
+        let nodes = lookup
+            .find_node(&Id::new(250u16.to_be_bytes().to_vec()))
+            .await;
+
+        handle.await.unwrap();
+        other_handle.await.unwrap();
+
+        assert_eq!(2, nodes.len());
+        assert_eq!(Id::new(249u16.to_be_bytes().to_vec()), nodes[0].id);
+        assert_eq!(Id::new(247u16.to_be_bytes().to_vec()), nodes[1].id);
+    }
+
+    #[tokio::test]
+    async fn find_node_without_unreachable_peers() {
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6126, 247));
+
+        let lookup = lookup(routing_table, waiting_list());
+        let nodes = lookup
+            .find_node(&Id::new(250u16.to_be_bytes().to_vec()))
+            .await;
+
+        assert!(nodes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn find_node_through_an_honest_disjoint_path() {
+        let waiting_list = waiting_list();
+        let malicious_handle = reply_from_peer(6150, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, vec![Source::new(&node(6152, 252))])
+        })
+        .await;
+        let sybil_handle = reply_from_peer(6152, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, Vec::new())
+        })
+        .await;
+        let honest_handle = reply_from_peer(6151, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, vec![Source::new(&node(6154, 248))])
+        })
+        .await;
+        let honest_neighbor_handle = reply_from_peer(6154, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, Vec::new())
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6150, 251));
+        routing_table.add(node(6151, 240));
+
+        let lookup = IterativeLookup::new(
+            node(6120, 255),
+            routing_table,
+            AsyncNetwork::new(waiting_list),
+            LookupOptions::new(1, 2, 2),
+        );
+        let nodes = lookup
+            .find_node(&Id::new(250u16.to_be_bytes().to_vec()))
+            .await;
+
+        for handle in [
+            malicious_handle,
+            sybil_handle,
+            honest_handle,
+            honest_neighbor_handle,
+        ] {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(2, nodes.len());
+        assert_eq!(Id::new(251u16.to_be_bytes().to_vec()), nodes[0].id);
+        assert_eq!(Id::new(248u16.to_be_bytes().to_vec()), nodes[1].id);
+    }
+
+    #[tokio::test]
+    async fn find_value_with_the_highest_version_across_disjoint_paths() {
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6155, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new("hash table".as_bytes().to_vec(), 1)),
+                None,
+            )
+        })
+        .await;
+        let other_handle = reply_from_peer(6156, waiting_list.clone(), |message_id| {
+            Message::find_value_reply_type(
+                message_id,
+                Some(VersionedValue::new(
+                    "distributed hash table".as_bytes().to_vec(),
+                    2,
+                )),
+                None,
+            )
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6155, 247));
+        routing_table.add(node(6156, 249));
+
+        let lookup = IterativeLookup::new(
+            node(6120, 255),
+            routing_table,
+            AsyncNetwork::new(waiting_list),
+            LookupOptions::new(1, 5, 2),
+        );
+        let value = lookup
+            .find_value("kademlia".as_bytes().to_vec(), RecordType::Plain)
+            .await;
+
+        handle.await.unwrap();
+        other_handle.await.unwrap();
+
+        let value = value.value.unwrap();
+        assert_eq!("distributed hash table".as_bytes().to_vec(), value.value);
+        assert_eq!(2, value.version);
+    }
+
+    #[tokio::test]
+    async fn trace_a_lookup_with_a_span_per_rpc() {
+        let recorded_spans = RecordedSpans::default();
+        let _default =
+            tracing::subscriber::set_default(Registry::default().with(recorded_spans.clone()));
+
+        let waiting_list = waiting_list();
+        let handle = reply_from_peer(6166, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, vec![Source::new(&node(6167, 249))])
+        })
+        .await;
+        let other_handle = reply_from_peer(6167, waiting_list.clone(), |message_id| {
+            Message::find_node_reply_type(message_id, Vec::new())
+        })
+        .await;
+
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(node(6166, 247));
+
+        let lookup = lookup(routing_table, waiting_list);
+        lookup
+            .find_node(&Id::new(250u16.to_be_bytes().to_vec()))
+            .await;
+
+        handle.await.unwrap();
+        other_handle.await.unwrap();
+
+        let spans = recorded_spans.spans.lock().unwrap();
+        let lookup_spans: Vec<&RecordedSpan> =
+            spans.iter().filter(|span| span.name == "lookup").collect();
+        assert_eq!(1, lookup_spans.len());
+        assert_eq!("find_node", lookup_spans[0].fields["kind"]);
+        assert_eq!("2", lookup_spans[0].fields["hops"]);
+
+        let rpc_spans: Vec<&RecordedSpan> =
+            spans.iter().filter(|span| span.name == "rpc").collect();
+        assert_eq!(2, rpc_spans.len());
+        for rpc_span in rpc_spans {
+            assert_eq!(Some("lookup"), rpc_span.parent);
+            assert!(rpc_span.fields.contains_key("message_id"));
+        }
+    }
+
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: HashMap<String, String>,
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordedSpans {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    }
+
+    #[derive(Default)]
+    struct FieldVisitor {
+        fields: HashMap<String, String>,
+    }
+
+    impl Visit for FieldVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordedSpans {
+        fn on_new_span(&self, attributes: &Attributes<'_>, id: &span::Id, context: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            attributes.record(&mut visitor);
+
+            let span = context.span(id).unwrap();
+            let mut spans = self.spans.lock().unwrap();
+            span.extensions_mut().insert(spans.len());
+            spans.push(RecordedSpan {
+                name: span.name(),
+                parent: span.parent().map(|parent| parent.name()),
+                fields: visitor.fields,
+            });
+        }
+
+        fn on_record(&self, id: &span::Id, values: &Record<'_>, context: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            values.record(&mut visitor);
+
+            let span = context.span(id).unwrap();
+            let index = *span.extensions().get::<usize>().unwrap();
+            self.spans.lock().unwrap()[index]
+                .fields
+                .extend(visitor.fields);
+        }
+    }
+
+    fn mutable_record() -> MutableRecord {
+        MutableRecord::sign(
+            &SigningKey::from_bytes(&[7; 32]),
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        )
+    }
+
+    async fn reply_from_peer(
+        port: u16,
+        waiting_list: Arc<WaitingList>,
+        reply: fn(i64) -> Message,
+    ) -> JoinHandle<()> {
+        let tcp_listener = TcpListener::bind(format!("localhost:{}", port))
+            .await
+            .unwrap();
+
+        tokio::spawn(async move {
+            let stream = tcp_listener.accept().await.unwrap();
+            let mut connection = AsyncTcpConnection::new(stream.0);
+            let message = connection.read().await.unwrap();
+
+            let message_id = match message {
+                Message::FindValue { message_id, .. } | Message::FindNode { message_id, .. } => {
+                    message_id.unwrap()
+                }
+                _ => panic!("Expected a lookup message, but was not"),
+            };
+            waiting_list.handle_response(message_id, Ok(reply(message_id)));
+        })
+    }
+
+    fn lookup(routing_table: Arc<Table>, waiting_list: Arc<WaitingList>) -> IterativeLookup {
+        IterativeLookup::new(
+            node(6120, 255),
+            routing_table,
+            AsyncNetwork::new(waiting_list),
+            LookupOptions::new(3, 5, 1),
+        )
+    }
+
+    fn node(port: u16, id: u16) -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), port),
+            Id::new(id.to_be_bytes().to_vec()),
+        )
+    }
+
+    fn waiting_list() -> Arc<WaitingList> {
+        WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100)),
+            SystemClock::new(),
+        )
+    }
+}
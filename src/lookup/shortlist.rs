@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::id::Id;
+use crate::net::node::{Node, NodeId};
+
+pub(crate) struct Shortlist {
+    target: Id,
+    nodes: Vec<Node>,
+    node_ids: HashSet<NodeId>,
+    maximum_capacity: usize,
+}
+
+impl Shortlist {
+    pub(crate) fn new(target: Id, maximum_capacity: usize) -> Self {
+        Shortlist {
+            target,
+            nodes: Vec::new(),
+            node_ids: HashSet::new(),
+            maximum_capacity,
+        }
+    }
+
+    pub(crate) fn add_missing(&mut self, nodes: Vec<Node>) {
+        for node in nodes {
+            if self.node_ids.insert(node.id.clone()) {
+                self.nodes.push(node);
+            }
+        }
+        self.nodes
+            .sort_by_key(|node| node.id.distance_from(&self.target));
+    }
+
+    // Nodes that are equally far from the target by bucket, that is by the highest differing
+    // bit, are queried in the order of their proximity key.
+    pub(crate) fn next_unqueried(
+        &self,
+        count: usize,
+        queried_node_ids: &mut HashSet<NodeId>,
+        proximity_key: impl Fn(&Node) -> Duration,
+    ) -> Vec<Node> {
+        let mut unqueried: Vec<Node> = self
+            .closest()
+            .iter()
+            .filter(|node| !queried_node_ids.contains(&node.id))
+            .cloned()
+            .collect();
+        unqueried.sort_by_key(|node| {
+            (
+                node.id.distance_from(&self.target).bits(),
+                proximity_key(node),
+            )
+        });
+        unqueried.truncate(count);
+
+        unqueried.iter().for_each(|node| {
+            queried_node_ids.insert(node.id.clone());
+        });
+        unqueried
+    }
+
+    pub(crate) fn remove(&mut self, node: &Node) {
+        self.nodes
+            .retain(|existing_node| existing_node.id != node.id);
+    }
+
+    pub(crate) fn closest(&self) -> &[Node] {
+        let total = self.nodes.len().min(self.maximum_capacity);
+        &self.nodes[..total]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use crate::id::Id;
+    use crate::lookup::shortlist::Shortlist;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::node::Node;
+    use crate::routing::proximity::ProximityPolicy;
+
+    #[test]
+    fn add_nodes_sorted_by_distance_from_target() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 2);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 255)]);
+
+        let closest = shortlist.closest();
+        assert_eq!(Id::new(255u16.to_be_bytes().to_vec()), closest[0].id);
+        assert_eq!(Id::new(511u16.to_be_bytes().to_vec()), closest[1].id);
+    }
+
+    #[test]
+    fn add_unique_nodes() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 511)]);
+
+        assert_eq!(1, shortlist.closest().len());
+    }
+
+    #[test]
+    fn closest_nodes_are_bounded_by_capacity() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 1);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 255)]);
+
+        assert_eq!(1, shortlist.closest().len());
+        assert_eq!(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            shortlist.closest()[0].id
+        );
+    }
+
+    #[test]
+    fn next_unqueried_nodes() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 255)]);
+        let mut queried_node_ids = HashSet::new();
+
+        let unqueried = shortlist.next_unqueried(1, &mut queried_node_ids, |_| Duration::ZERO);
+        assert_eq!(Id::new(255u16.to_be_bytes().to_vec()), unqueried[0].id);
+
+        let unqueried = shortlist.next_unqueried(1, &mut queried_node_ids, |_| Duration::ZERO);
+        assert_eq!(Id::new(511u16.to_be_bytes().to_vec()), unqueried[0].id);
+
+        let unqueried = shortlist.next_unqueried(1, &mut queried_node_ids, |_| Duration::ZERO);
+        assert!(unqueried.is_empty());
+    }
+
+    #[test]
+    fn next_unqueried_nodes_skip_nodes_queried_elsewhere() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 255)]);
+
+        let mut queried_node_ids = HashSet::new();
+        queried_node_ids.insert(Id::new(255u16.to_be_bytes().to_vec()));
+
+        let unqueried = shortlist.next_unqueried(2, &mut queried_node_ids, |_| Duration::ZERO);
+        assert_eq!(1, unqueried.len());
+        assert_eq!(Id::new(511u16.to_be_bytes().to_vec()), unqueried[0].id);
+    }
+
+    #[test]
+    fn next_unqueried_nodes_prefer_faster_nodes_in_the_same_bucket() {
+        let mut shortlist = Shortlist::new(Id::new(0u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![
+            node(1243, 0x0102),
+            node(1239, 0x0103),
+            node(1240, 0x0001),
+        ]);
+        let proximity_key = |node: &Node| {
+            ProximityPolicy::LowestRoundTripTime.preference_key(round_trip_time(node))
+        };
+
+        let unqueried = shortlist.next_unqueried(3, &mut HashSet::new(), proximity_key);
+        let ids: Vec<Id> = unqueried.into_iter().map(|node| node.id).collect();
+        assert_eq!(
+            vec![
+                Id::new(0x0001u16.to_be_bytes().to_vec()),
+                Id::new(0x0103u16.to_be_bytes().to_vec()),
+                Id::new(0x0102u16.to_be_bytes().to_vec()),
+            ],
+            ids
+        );
+    }
+
+    #[test]
+    fn next_unqueried_nodes_keep_the_distance_order_without_proximity() {
+        let mut shortlist = Shortlist::new(Id::new(0u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![node(1243, 0x0102), node(1239, 0x0103)]);
+        let proximity_key =
+            |node: &Node| ProximityPolicy::Disabled.preference_key(round_trip_time(node));
+
+        let unqueried = shortlist.next_unqueried(1, &mut HashSet::new(), proximity_key);
+        assert_eq!(Id::new(0x0102u16.to_be_bytes().to_vec()), unqueried[0].id);
+    }
+
+    #[test]
+    fn remove_a_node() {
+        let mut shortlist = Shortlist::new(Id::new(247u16.to_be_bytes().to_vec()), 5);
+        shortlist.add_missing(vec![node(1243, 511), node(1239, 255)]);
+        shortlist.remove(&node(1239, 255));
+
+        assert_eq!(1, shortlist.closest().len());
+        assert_eq!(
+            Id::new(511u16.to_be_bytes().to_vec()),
+            shortlist.closest()[0].id
+        );
+    }
+
+    fn round_trip_time(node: &Node) -> Option<Duration> {
+        match node.endpoint.address().as_str() {
+            "localhost:1239" => Some(Duration::from_millis(10)),
+            "localhost:1243" => Some(Duration::from_millis(80)),
+            _ => None,
+        }
+    }
+
+    fn node(port: u16, id: u16) -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), port),
+            Id::new(id.to_be_bytes().to_vec()),
+        )
+    }
+}
@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use log::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+use kademlia::{
+    node_id_from_hex, node_id_to_hex, Client, Kademlia, KademliaBuilder, KademliaError,
+};
+
+const IDENTITY_FILE_NAME: &str = "identity";
+const ROUTING_TABLE_FILE_NAME: &str = "routing_table";
+
+#[derive(Parser)]
+#[command(name = "kademlia", version, about = "Run and query a kademlia node")]
+struct Cli {
+    #[arg(long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    Start(StartArgs),
+    Put {
+        #[command(flatten)]
+        client: ClientArgs,
+        key: String,
+        value: String,
+    },
+    Get {
+        #[command(flatten)]
+        client: ClientArgs,
+        key: String,
+    },
+    FindNode {
+        #[command(flatten)]
+        client: ClientArgs,
+        node_id: String,
+    },
+    Ping {
+        #[command(flatten)]
+        client: ClientArgs,
+        address: String,
+    },
+}
+
+#[derive(Args)]
+struct StartArgs {
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    #[arg(long)]
+    port: u16,
+    #[arg(long = "seed")]
+    seeds: Vec<String>,
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[arg(long)]
+    admin_port: Option<u16>,
+    #[arg(long)]
+    secure: bool,
+}
+
+// The commands go through the admin interface of a running node, which looks the values up
+// and replicates them.
+#[derive(Args)]
+struct ClientArgs {
+    #[arg(long)]
+    admin: String,
+}
+
+impl ClientArgs {
+    fn client(&self) -> Client {
+        Client::new(self.admin.clone())
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_max_level(cli.log_level)
+        .init();
+
+    match run(cli.command).await {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<ExitCode, KademliaError> {
+    match command {
+        Command::Start(args) => {
+            start(args).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Put {
+            client: args,
+            key,
+            value,
+        } => {
+            args.client().put(&key, &value).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Get { client: args, key } => match args.client().get(&key).await? {
+            Some(value) => {
+                println!("{}", value);
+                Ok(ExitCode::SUCCESS)
+            }
+            None => {
+                eprintln!("key {} not found", key);
+                Ok(ExitCode::FAILURE)
+            }
+        },
+        Command::FindNode {
+            client: args,
+            node_id,
+        } => {
+            let node_id = node_id_from_hex(&node_id).ok_or_else(|| {
+                KademliaError::InvalidArgument(format!("{} is not a hex node id", node_id))
+            })?;
+            for contact in args.client().find_node(&node_id).await? {
+                println!(
+                    "{} {}",
+                    node_id_to_hex(contact.node_id()),
+                    contact.address()
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Ping {
+            client: args,
+            address,
+        } => {
+            let contact = args.client().ping(&address).await?;
+            println!(
+                "{} {}",
+                node_id_to_hex(contact.node_id()),
+                contact.address()
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+async fn start(args: StartArgs) -> Result<(), KademliaError> {
+    if let Some(data_dir) = &args.data_dir {
+        fs::create_dir_all(data_dir)?;
+    }
+    let kademlia = builder_for(&args).start().await?;
+    println!(
+        "node {} listening at {}",
+        node_id_to_hex(kademlia.node_id()),
+        kademlia.address()
+    );
+    if let Some(admin_address) = kademlia.admin_address() {
+        println!("admin interface listening at {}", admin_address);
+    }
+    for seed in &args.seeds {
+        if let Err(err) = kademlia.bootstrap(seed).await {
+            warn!("could not bootstrap from seed {}, error {}", seed, err);
+        }
+    }
+
+    tokio::signal::ctrl_c().await?;
+    info!("shutting down node at {}", kademlia.address());
+    kademlia.shutdown().await
+}
+
+fn builder_for(args: &StartArgs) -> KademliaBuilder {
+    let mut builder = Kademlia::builder()
+        .host(args.host.clone())
+        .port(args.port)
+        .secure(args.secure);
+    if let Some(config) = &args.config {
+        builder = builder.config_file(config);
+    }
+    if let Some(admin_port) = args.admin_port {
+        builder = builder.admin_port(admin_port);
+    }
+    if let Some(data_dir) = &args.data_dir {
+        builder = builder
+            .identity_file(data_dir.join(IDENTITY_FILE_NAME))
+            .routing_table_file(data_dir.join(ROUTING_TABLE_FILE_NAME));
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+
+    use crate::{Cli, Command};
+
+    #[test]
+    fn verify_the_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_a_start_command_with_seeds() {
+        let cli = Cli::parse_from([
+            "kademlia",
+            "start",
+            "--port",
+            "2379",
+            "--seed",
+            "127.0.0.1:2380",
+            "--seed",
+            "127.0.0.1:2381",
+        ]);
+        match cli.command {
+            Command::Start(args) => {
+                assert_eq!(2379, args.port);
+                assert_eq!(vec!["127.0.0.1:2380", "127.0.0.1:2381"], args.seeds);
+            }
+            _ => panic!("expected a start command"),
+        }
+    }
+
+    #[test]
+    fn parse_a_get_command_for_a_node() {
+        let cli = Cli::parse_from(["kademlia", "get", "--admin", "127.0.0.1:9379", "kademlia"]);
+        match cli.command {
+            Command::Get { client, key } => {
+                assert_eq!("127.0.0.1:9379", client.admin);
+                assert_eq!("kademlia", key);
+            }
+            _ => panic!("expected a get command"),
+        }
+    }
+}
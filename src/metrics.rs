@@ -0,0 +1,243 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use dashmap::DashMap;
+
+use crate::net::message::MessageTypes;
+
+const LOOKUP_HOPS_BUCKETS: [f64; 8] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0];
+
+struct HistogramState {
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+pub(crate) struct Histogram {
+    upper_bounds: &'static [f64],
+    state: Mutex<HistogramState>,
+}
+
+impl Histogram {
+    pub(crate) fn new(upper_bounds: &'static [f64]) -> Self {
+        Histogram {
+            upper_bounds,
+            state: Mutex::new(HistogramState {
+                bucket_counts: vec![0; upper_bounds.len()],
+                sum: 0.0,
+                count: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn observe(&self, value: f64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = self
+            .upper_bounds
+            .iter()
+            .position(|upper_bound| value <= *upper_bound)
+        {
+            state.bucket_counts[index] += 1;
+        }
+        state.sum += value;
+        state.count += 1;
+    }
+
+    pub(crate) fn render(&self, text: &mut PrometheusText, name: &str, labels: &[(&str, &str)]) {
+        let state = self.state.lock().unwrap();
+        let bucket_name = format!("{}_bucket", name);
+        let mut cumulative_count = 0;
+        for (upper_bound, bucket_count) in self.upper_bounds.iter().zip(&state.bucket_counts) {
+            cumulative_count += bucket_count;
+            let upper_bound = upper_bound.to_string();
+            text.sample(
+                &bucket_name,
+                &[labels, &[("le", upper_bound.as_str())]].concat(),
+                cumulative_count,
+            );
+        }
+        text.sample(
+            &bucket_name,
+            &[labels, &[("le", "+Inf")]].concat(),
+            state.count,
+        );
+        text.sample(&format!("{}_sum", name), labels, state.sum);
+        text.sample(&format!("{}_count", name), labels, state.count);
+    }
+}
+
+pub(crate) struct Metrics {
+    messages_sent: DashMap<MessageTypes, AtomicU64>,
+    messages_received: DashMap<MessageTypes, AtomicU64>,
+    lookup_hops: Histogram,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Metrics {
+            messages_sent: DashMap::new(),
+            messages_received: DashMap::new(),
+            lookup_hops: Histogram::new(&LOOKUP_HOPS_BUCKETS),
+        })
+    }
+
+    pub(crate) fn record_sent(&self, message_type: MessageTypes) {
+        Self::increment(&self.messages_sent, message_type);
+    }
+
+    pub(crate) fn record_received(&self, message_type: MessageTypes) {
+        Self::increment(&self.messages_received, message_type);
+    }
+
+    pub(crate) fn observe_lookup_hops(&self, hops: usize) {
+        self.lookup_hops.observe(hops as f64);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn messages_sent(&self, message_type: MessageTypes) -> u64 {
+        Self::count(&self.messages_sent, message_type)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn messages_received(&self, message_type: MessageTypes) -> u64 {
+        Self::count(&self.messages_received, message_type)
+    }
+
+    pub(crate) fn render(&self, text: &mut PrometheusText) {
+        text.describe(
+            "kademlia_messages_sent_total",
+            "counter",
+            "Messages sent by message type",
+        );
+        Self::render_counts(text, "kademlia_messages_sent_total", &self.messages_sent);
+
+        text.describe(
+            "kademlia_messages_received_total",
+            "counter",
+            "Messages received by message type",
+        );
+        Self::render_counts(
+            text,
+            "kademlia_messages_received_total",
+            &self.messages_received,
+        );
+
+        text.describe(
+            "kademlia_lookup_hops",
+            "histogram",
+            "Query rounds taken by iterative lookups",
+        );
+        self.lookup_hops.render(text, "kademlia_lookup_hops", &[]);
+    }
+
+    fn increment(counts: &DashMap<MessageTypes, AtomicU64>, message_type: MessageTypes) {
+        counts
+            .entry(message_type)
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[cfg(test)]
+    fn count(counts: &DashMap<MessageTypes, AtomicU64>, message_type: MessageTypes) -> u64 {
+        counts
+            .get(&message_type)
+            .map(|count| count.load(Ordering::SeqCst))
+            .unwrap_or(0)
+    }
+
+    fn render_counts(
+        text: &mut PrometheusText,
+        name: &str,
+        counts: &DashMap<MessageTypes, AtomicU64>,
+    ) {
+        let mut counts: Vec<(MessageTypes, u64)> = counts
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().load(Ordering::SeqCst)))
+            .collect();
+        counts.sort_by_key(|(message_type, _)| *message_type as u8);
+        for (message_type, count) in counts {
+            let label = format!("{:?}", message_type);
+            text.sample(name, &[("type", label.as_str())], count);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PrometheusText {
+    text: String,
+}
+
+impl PrometheusText {
+    pub(crate) fn new() -> Self {
+        PrometheusText::default()
+    }
+
+    pub(crate) fn describe(&mut self, name: &str, kind: &str, help: &str) {
+        self.text.push_str(&format!("# HELP {} {}\n", name, help));
+        self.text.push_str(&format!("# TYPE {} {}\n", name, kind));
+    }
+
+    pub(crate) fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, value))
+                .collect();
+            self.text.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.text.push_str(&format!(" {}\n", value));
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{Metrics, PrometheusText};
+    use crate::net::message::MessageTypes;
+
+    #[test]
+    fn count_messages_by_type() {
+        let metrics = Metrics::new();
+        metrics.record_sent(MessageTypes::Ping);
+        metrics.record_sent(MessageTypes::Ping);
+        metrics.record_received(MessageTypes::Store);
+
+        assert_eq!(2, metrics.messages_sent(MessageTypes::Ping));
+        assert_eq!(0, metrics.messages_sent(MessageTypes::Store));
+        assert_eq!(1, metrics.messages_received(MessageTypes::Store));
+    }
+
+    #[test]
+    fn render_counters_in_prometheus_text_format() {
+        let metrics = Metrics::new();
+        metrics.record_sent(MessageTypes::FindNode);
+
+        let mut text = PrometheusText::new();
+        metrics.render(&mut text);
+        let text = text.into_string();
+
+        assert!(text.contains("# TYPE kademlia_messages_sent_total counter\n"));
+        assert!(text.contains("kademlia_messages_sent_total{type=\"FindNode\"} 1\n"));
+    }
+
+    #[test]
+    fn render_cumulative_histogram_buckets() {
+        let metrics = Metrics::new();
+        metrics.observe_lookup_hops(2);
+        metrics.observe_lookup_hops(4);
+
+        let mut text = PrometheusText::new();
+        metrics.render(&mut text);
+        let text = text.into_string();
+
+        assert!(text.contains("kademlia_lookup_hops_bucket{le=\"2\"} 1\n"));
+        assert!(text.contains("kademlia_lookup_hops_bucket{le=\"4\"} 2\n"));
+        assert!(text.contains("kademlia_lookup_hops_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("kademlia_lookup_hops_sum 6\n"));
+    }
+}
@@ -1,4 +1,3 @@
-use std::any::Any;
 use std::error::Error;
 use std::future::Future;
 use std::ops::Deref;
@@ -12,7 +11,6 @@ pub(crate) type ResponseError = Box<dyn Error + Send + Sync + 'static>;
 
 pub(crate) trait Callback: Send + Sync + 'static {
     fn on_response(&self, response: Result<Message, ResponseError>);
-    fn as_any(&self) -> &dyn Any;
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -46,10 +44,6 @@ impl Callback for ResponseAwaitingCallback {
     fn on_response(&self, response: Result<Message, ResponseError>) {
         self.handle.on_response(response);
     }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
 }
 
 impl ResponseAwaitingCallbackHandle {
@@ -60,6 +54,13 @@ impl ResponseAwaitingCallbackHandle {
         }
     }
 
+    pub(crate) fn response(&self) -> Option<Message> {
+        match self.response.read().unwrap().deref() {
+            Some(Ok(message)) => Some(message.clone()),
+            _ => None,
+        }
+    }
+
     fn on_response(&self, response: Result<Message, ResponseError>) {
         let mut guard = self.response.write().unwrap();
         *guard = Some(response);
@@ -117,6 +118,22 @@ mod tests {
         assert_eq!(ResponseStatus::Ok, response_status);
     }
 
+    #[tokio::test]
+    async fn get_the_response_after_awaiting_on_callback() {
+        let response_awaiting_callback = ResponseAwaitingCallback::new();
+        let response_awaiting_callback_clone = response_awaiting_callback.clone();
+
+        tokio::spawn(async move {
+            response_awaiting_callback.on_response(Ok(Message::shutdown_type()));
+        });
+
+        let handle = response_awaiting_callback_clone.handle();
+        let _ = handle.await;
+
+        let response = handle.response();
+        assert!(response.unwrap().is_shutdown_type());
+    }
+
     #[tokio::test]
     async fn await_on_callback_with_error_response() {
         let response_awaiting_callback = ResponseAwaitingCallback::new();
@@ -1,4 +1,5 @@
 use std::io::Error;
+use std::net::IpAddr;
 
 use log::debug;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -6,10 +7,16 @@ use tokio::net::TcpStream;
 
 use crate::net::endpoint::Endpoint;
 use crate::net::message::{Message, RESERVED_MESSAGE_SIZE};
+use crate::net::node::NodeId;
+use crate::net::secure::{SecureChannel, SecureSession};
 use crate::net::NetworkErrorKind;
 
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
 pub(crate) struct AsyncTcpConnection {
     tcp_stream: TcpStream,
+    secure_session: Option<SecureSession>,
+    max_message_size: usize,
 }
 
 impl AsyncTcpConnection {
@@ -17,18 +24,71 @@ impl AsyncTcpConnection {
         debug!("establishing connection with {}", endpoint.address());
         TcpStream::connect(endpoint.address())
             .await
-            .map(|tcp_stream| AsyncTcpConnection { tcp_stream })
+            .map(AsyncTcpConnection::new)
+    }
+
+    pub(crate) async fn establish_secure_with(
+        endpoint: &Endpoint,
+        secure_channel: &SecureChannel,
+    ) -> Result<AsyncTcpConnection, NetworkErrorKind> {
+        debug!("establishing secure connection with {}", endpoint.address());
+        let mut tcp_stream = TcpStream::connect(endpoint.address()).await?;
+        let secure_session = secure_channel.initiate(&mut tcp_stream).await?;
+        Ok(AsyncTcpConnection {
+            tcp_stream,
+            secure_session: Some(secure_session),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        })
     }
 
     pub(crate) fn new(tcp_stream: TcpStream) -> AsyncTcpConnection {
-        AsyncTcpConnection { tcp_stream }
+        AsyncTcpConnection {
+            tcp_stream,
+            secure_session: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    pub(crate) fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    pub(crate) async fn accept_secure(
+        mut tcp_stream: TcpStream,
+        secure_channel: &SecureChannel,
+    ) -> Result<AsyncTcpConnection, NetworkErrorKind> {
+        let secure_session = secure_channel.respond(&mut tcp_stream).await?;
+        Ok(AsyncTcpConnection {
+            tcp_stream,
+            secure_session: Some(secure_session),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        })
+    }
+
+    pub(crate) fn peer_ip(&self) -> Option<IpAddr> {
+        self.tcp_stream.peer_addr().ok().map(|address| address.ip())
+    }
+
+    pub(crate) fn authenticated_node_id(&self) -> Option<&NodeId> {
+        self.secure_session
+            .as_ref()
+            .map(|secure_session| secure_session.remote_node_id())
     }
 
     pub(crate) async fn read(&mut self) -> Result<Message, NetworkErrorKind> {
+        if let Some(secure_session) = self.secure_session.as_mut() {
+            return secure_session
+                .read(&mut self.tcp_stream, self.max_message_size)
+                .await;
+        }
         let mut message_size: [u8; RESERVED_MESSAGE_SIZE] = [0; RESERVED_MESSAGE_SIZE];
         let _ = self.tcp_stream.peek(&mut message_size).await?;
 
         let message_size = u32::from_be_bytes(message_size) as usize;
+        if message_size > self.max_message_size {
+            return Err(NetworkErrorKind::MessageTooLarge(message_size));
+        }
         let mut message = Vec::with_capacity(message_size + RESERVED_MESSAGE_SIZE);
 
         let _ = self.tcp_stream.read_buf(&mut message).await?;
@@ -36,6 +96,9 @@ impl AsyncTcpConnection {
     }
 
     pub(crate) async fn write(&mut self, message: &Message) -> Result<(), NetworkErrorKind> {
+        if let Some(secure_session) = self.secure_session.as_mut() {
+            return secure_session.write(&mut self.tcp_stream, message).await;
+        }
         let serialized = message.serialize()?;
         self.tcp_stream.write_all(&serialized).await?;
         Ok(())
@@ -44,12 +107,17 @@ impl AsyncTcpConnection {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use tokio::net::TcpListener;
 
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::message::Message;
     use crate::net::node::Node;
+    use crate::net::secure::SecureChannel;
+    use crate::net::NetworkErrorKind;
 
     #[tokio::test]
     async fn read_from_connection_successfully() {
@@ -100,6 +168,86 @@ mod tests {
         assert!(write_result.is_ok());
     }
 
+    #[tokio::test]
+    async fn do_not_read_a_message_larger_than_the_maximum_size() {
+        let listener_result = TcpListener::bind("localhost:6164").await;
+        assert!(listener_result.is_ok());
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let stream = tcp_listener.accept().await.unwrap();
+
+            let mut connection = AsyncTcpConnection::new(stream.0).with_max_message_size(16);
+            let read_result = connection.read().await;
+
+            assert!(matches!(
+                read_result,
+                Err(NetworkErrorKind::MessageTooLarge(_))
+            ));
+        });
+
+        let mut tcp_connection =
+            AsyncTcpConnection::establish_with(&Endpoint::new("localhost".to_string(), 6164))
+                .await
+                .unwrap();
+        let node = Node::new(Endpoint::new("localhost".to_string(), 1010));
+        let payload = Message::find_value_type(node, b"Kademlia".to_vec());
+
+        let write_result = tcp_connection.write(&payload).await;
+        assert!(write_result.is_ok());
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_from_secure_connection_successfully() {
+        let listener_result = TcpListener::bind("localhost:6160").await;
+        assert!(listener_result.is_ok());
+
+        let client_identity = Identity::from_bytes([1; 32]);
+        let server_identity = Identity::from_bytes([2; 32]);
+        let client_node_id = client_identity.node_id();
+        let server_node_id = server_identity.node_id();
+        let server_channel = SecureChannel::new(Arc::new(server_identity)).unwrap();
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let stream = tcp_listener.accept().await.unwrap();
+
+            let mut connection = AsyncTcpConnection::accept_secure(stream.0, &server_channel)
+                .await
+                .unwrap();
+            assert_eq!(Some(&client_node_id), connection.authenticated_node_id());
+
+            let message = connection.read().await.unwrap();
+            if let Message::Store { value, .. } = message {
+                assert_eq!(vec![7; 200_000], value.value);
+            } else {
+                panic!("expected a store message");
+            }
+        });
+
+        let client_channel = SecureChannel::new(Arc::new(client_identity)).unwrap();
+        let mut tcp_connection = AsyncTcpConnection::establish_secure_with(
+            &Endpoint::new("localhost".to_string(), 6160),
+            &client_channel,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            Some(&server_node_id),
+            tcp_connection.authenticated_node_id()
+        );
+
+        let node = Node::new(Endpoint::new("localhost".to_string(), 1010));
+        let payload = Message::store_type(b"Kademlia".to_vec(), vec![7; 200_000], 1, node);
+
+        let write_result = tcp_connection.write(&payload).await;
+        assert!(write_result.is_ok());
+
+        handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn connect_to_endpoint_fails() {
         let tcp_connection_result =
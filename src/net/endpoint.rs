@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -23,10 +24,20 @@ impl Endpoint {
     pub(crate) fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    pub(crate) fn ip(&self) -> Option<IpAddr> {
+        self.host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .ok()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::net::{IpAddr, Ipv6Addr};
+
     use crate::net::endpoint::Endpoint;
 
     #[test]
@@ -40,4 +51,16 @@ mod tests {
         let endpoint = Endpoint::new("127.0.0.1".to_string(), 2379);
         assert_eq!("127.0.0.1:2379", endpoint.address())
     }
+
+    #[test]
+    fn ip_of_an_endpoint() {
+        let endpoint = Endpoint::new("[::1]".to_string(), 2379);
+        assert_eq!(Some(IpAddr::V6(Ipv6Addr::LOCALHOST)), endpoint.ip());
+    }
+
+    #[test]
+    fn no_ip_for_an_endpoint_with_host_name() {
+        let endpoint = Endpoint::new("localhost".to_string(), 2379);
+        assert!(endpoint.ip().is_none());
+    }
 }
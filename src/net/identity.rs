@@ -0,0 +1,303 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::id::Id;
+use crate::net::endpoint::Endpoint;
+use crate::net::node::NodeId;
+use crate::net::puzzle::solves_static_puzzle;
+
+const SECRET_KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 32;
+// Separates ownership proofs from every other content signed with the key of the node.
+const OWNERSHIP_PROOF_CONTEXT: &[u8] = b"kademlia-ownership-v1";
+
+#[derive(Debug)]
+pub(crate) enum IdentityErrorKind {
+    Io(Error),
+    InvalidKey(String),
+}
+
+impl From<Error> for IdentityErrorKind {
+    fn from(err: Error) -> Self {
+        IdentityErrorKind::Io(err)
+    }
+}
+
+impl Display for IdentityErrorKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentityErrorKind::Io(err) => write!(formatter, "io error: {}", err),
+            IdentityErrorKind::InvalidKey(description) => {
+                write!(formatter, "invalid key: {}", description)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Identity {
+    signing_key: SigningKey,
+}
+
+impl Identity {
+    pub(crate) fn generate() -> Self {
+        Identity {
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    pub(crate) fn generate_with_static_puzzle(difficulty: u32) -> Self {
+        loop {
+            let identity = Identity::generate();
+            if solves_static_puzzle(&identity.node_id(), difficulty) {
+                return identity;
+            }
+        }
+    }
+
+    pub(crate) fn from_bytes(secret_key: [u8; SECRET_KEY_LENGTH]) -> Self {
+        Identity {
+            signing_key: SigningKey::from_bytes(&secret_key),
+        }
+    }
+
+    pub(crate) fn load_or_generate(
+        file_path: &Path,
+        static_difficulty: u32,
+    ) -> Result<Self, IdentityErrorKind> {
+        match fs::read(file_path) {
+            Ok(bytes) => {
+                let secret_key: [u8; SECRET_KEY_LENGTH] =
+                    bytes.as_slice().try_into().map_err(|_| {
+                        IdentityErrorKind::InvalidKey(format!(
+                            "expected {} bytes in {:?}, found {}",
+                            SECRET_KEY_LENGTH,
+                            file_path,
+                            bytes.len()
+                        ))
+                    })?;
+                let identity = Identity::from_bytes(secret_key);
+                if !solves_static_puzzle(&identity.node_id(), static_difficulty) {
+                    return Err(IdentityErrorKind::InvalidKey(format!(
+                        "key in {:?} does not solve the static puzzle of difficulty {}",
+                        file_path, static_difficulty
+                    )));
+                }
+                Ok(identity)
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let identity = Identity::generate_with_static_puzzle(static_difficulty);
+                identity.save(file_path)?;
+                Ok(identity)
+            }
+            Err(err) => Err(IdentityErrorKind::Io(err)),
+        }
+    }
+
+    pub(crate) fn public_key(&self) -> Vec<u8> {
+        self.signing_key.verifying_key().to_bytes().to_vec()
+    }
+
+    pub(crate) fn node_id(&self) -> NodeId {
+        node_id_of(&self.public_key())
+    }
+
+    pub(crate) fn prove_ownership(&self, nonce: &[u8], endpoint: &Endpoint) -> OwnershipProof {
+        OwnershipProof {
+            public_key: self.public_key(),
+            signature: self.sign(&signable(nonce, endpoint)),
+        }
+    }
+
+    pub(crate) fn sign(&self, content: &[u8]) -> Vec<u8> {
+        self.signing_key.sign(content).to_bytes().to_vec()
+    }
+
+    fn save(&self, file_path: &Path) -> Result<(), IdentityErrorKind> {
+        let temporary_file_path = file_path.with_extension("tmp");
+        fs::write(&temporary_file_path, self.signing_key.to_bytes())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&temporary_file_path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::rename(&temporary_file_path, file_path)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct OwnershipProof {
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl OwnershipProof {
+    pub(crate) fn verify(&self, node_id: &NodeId, nonce: &[u8], endpoint: &Endpoint) -> bool {
+        if &node_id_of(&self.public_key) != node_id {
+            return false;
+        }
+        verify_signature(
+            &self.public_key,
+            &signable(nonce, endpoint),
+            &self.signature,
+        )
+    }
+}
+
+pub(crate) fn node_id_of(public_key: &[u8]) -> NodeId {
+    Id::generate_from_bytes(public_key)
+}
+
+pub(crate) fn verify_signature(public_key: &[u8], content: &[u8], signature: &[u8]) -> bool {
+    let public_key: [u8; 32] = match public_key.try_into() {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    let verifying_key = match VerifyingKey::from_bytes(&public_key) {
+        Ok(verifying_key) => verifying_key,
+        Err(_) => return false,
+    };
+    let signature = match Signature::from_slice(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    verifying_key.verify(content, &signature).is_ok()
+}
+
+pub(crate) fn generate_nonce() -> Vec<u8> {
+    rand::random::<[u8; NONCE_LENGTH]>().to_vec()
+}
+
+fn signable(nonce: &[u8], endpoint: &Endpoint) -> Vec<u8> {
+    let mut signable = OWNERSHIP_PROOF_CONTEXT.to_vec();
+    signable.extend_from_slice(nonce);
+    signable.extend_from_slice(endpoint.address().as_bytes());
+    signable
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::identity::{generate_nonce, node_id_of, Identity, OwnershipProof};
+
+    #[test]
+    fn node_id_is_derived_from_public_key() {
+        let identity = Identity::from_bytes([7; 32]);
+        assert_eq!(
+            Id::generate_from_bytes(&identity.public_key()),
+            identity.node_id()
+        );
+    }
+
+    #[test]
+    fn node_id_does_not_change_with_endpoint() {
+        let identity = Identity::from_bytes([7; 32]);
+        let other_identity = Identity::from_bytes([7; 32]);
+        assert_eq!(identity.node_id(), other_identity.node_id());
+    }
+
+    #[test]
+    fn verify_a_proof_of_ownership() {
+        let identity = Identity::from_bytes([7; 32]);
+        let endpoint = Endpoint::new("localhost".to_string(), 2330);
+        let nonce = generate_nonce();
+
+        let proof = identity.prove_ownership(&nonce, &endpoint);
+        assert!(proof.verify(&identity.node_id(), &nonce, &endpoint));
+    }
+
+    #[test]
+    fn do_not_verify_a_proof_of_ownership_for_another_node_id() {
+        let identity = Identity::from_bytes([7; 32]);
+        let endpoint = Endpoint::new("localhost".to_string(), 2330);
+        let nonce = generate_nonce();
+
+        let proof = identity.prove_ownership(&nonce, &endpoint);
+        assert!(!proof.verify(&node_id_of("kademlia".as_bytes()), &nonce, &endpoint));
+    }
+
+    #[test]
+    fn do_not_verify_a_proof_of_ownership_for_another_nonce() {
+        let identity = Identity::from_bytes([7; 32]);
+        let endpoint = Endpoint::new("localhost".to_string(), 2330);
+
+        let proof = identity.prove_ownership(&generate_nonce(), &endpoint);
+        assert!(!proof.verify(&identity.node_id(), &generate_nonce(), &endpoint));
+    }
+
+    #[test]
+    fn do_not_verify_a_proof_of_ownership_for_another_endpoint() {
+        let identity = Identity::from_bytes([7; 32]);
+        let nonce = generate_nonce();
+
+        let proof = identity.prove_ownership(&nonce, &Endpoint::new("localhost".to_string(), 2330));
+        assert!(!proof.verify(
+            &identity.node_id(),
+            &nonce,
+            &Endpoint::new("localhost".to_string(), 1982)
+        ));
+    }
+
+    #[test]
+    fn do_not_verify_a_signature_made_outside_of_an_ownership_proof() {
+        let identity = Identity::from_bytes([7; 32]);
+        let endpoint = Endpoint::new("localhost".to_string(), 2330);
+        let nonce = generate_nonce();
+
+        let mut content = nonce.clone();
+        content.extend_from_slice(endpoint.address().as_bytes());
+        let proof = OwnershipProof {
+            public_key: identity.public_key(),
+            signature: identity.sign(&content),
+        };
+        assert!(!proof.verify(&identity.node_id(), &nonce, &endpoint));
+    }
+
+    #[test]
+    fn load_a_generated_identity() {
+        let file_path = env::temp_dir().join(format!("kademlia-{}-identity", std::process::id()));
+        let _ = fs::remove_file(&file_path);
+
+        let identity = Identity::load_or_generate(&file_path, 0).unwrap();
+        let loaded_identity = Identity::load_or_generate(&file_path, 0).unwrap();
+        let _ = fs::remove_file(&file_path);
+
+        assert_eq!(identity.node_id(), loaded_identity.node_id());
+    }
+
+    #[test]
+    fn do_not_load_an_identity_with_an_invalid_key() {
+        let file_path =
+            env::temp_dir().join(format!("kademlia-{}-invalid-identity", std::process::id()));
+        fs::write(&file_path, "kademlia".as_bytes()).unwrap();
+
+        let identity = Identity::load_or_generate(&file_path, 0);
+        let _ = fs::remove_file(&file_path);
+
+        assert!(identity.is_err());
+    }
+
+    #[test]
+    fn do_not_load_an_identity_that_does_not_solve_the_static_puzzle() {
+        let file_path =
+            env::temp_dir().join(format!("kademlia-{}-unsolved-identity", std::process::id()));
+        fs::write(&file_path, [7; 32]).unwrap();
+
+        let identity = Identity::load_or_generate(&file_path, 20);
+        let _ = fs::remove_file(&file_path);
+
+        assert!(identity.is_err());
+    }
+}
@@ -5,16 +5,20 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use crate::net::endpoint::Endpoint;
+use crate::net::identity::OwnershipProof;
 use crate::net::message::Message::{
-    AddNode, FindNode, FindNodeReply, FindValue, FindValueReply, Ping, PingReply, ShutDown, Store,
+    AddNode, Delete, FindNode, FindNodeReply, FindValue, FindValueReply, Ping, PingReply, ShutDown,
+    Store,
 };
 use crate::net::node::{Node, NodeId};
-use crate::store::KeyId;
+use crate::store::record::{ImmutableRecord, MutableRecord};
+use crate::store::{Key, KeyId, Version, VersionedValue};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct Source {
     node_endpoint: Endpoint,
     node_id: NodeId,
+    puzzle_solution: Vec<u8>,
 }
 
 impl Source {
@@ -23,17 +27,19 @@ impl Source {
         Source {
             node_endpoint: node.node_endpoint(),
             node_id: node.node_id(),
+            puzzle_solution: node.puzzle_solution,
         }
     }
 
     pub(crate) fn to_node(self) -> Node {
-        Node::new_with_id(self.node_endpoint, self.node_id)
+        Node::new_with_puzzle_solution(self.node_endpoint, self.node_id, self.puzzle_solution)
     }
 
     pub(crate) fn endpoint(&self) -> &Endpoint {
         &self.node_endpoint
     }
 
+    #[cfg(test)]
     pub(crate) fn node_id(&self) -> &NodeId {
         &self.node_id
     }
@@ -43,7 +49,7 @@ pub(crate) const RESERVED_MESSAGE_SIZE: usize = size_of::<u32>();
 
 pub(crate) type MessageId = i64;
 
-#[derive(Hash, Eq, PartialEq)]
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
 pub(crate) enum MessageTypes {
     Store = 1,
     AddNode = 2,
@@ -52,6 +58,9 @@ pub(crate) enum MessageTypes {
     Ping = 5,
     PingReply = 6,
     Shutdown = 7,
+    Delete = 8,
+    FindValueReply = 9,
+    FindNodeReply = 10,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -59,7 +68,7 @@ pub(crate) enum Message {
     Store {
         key: Vec<u8>,
         key_id: KeyId,
-        value: Vec<u8>,
+        value: VersionedValue,
         source: Source,
     },
     AddNode {
@@ -73,7 +82,8 @@ pub(crate) enum Message {
     },
     FindValueReply {
         message_id: MessageId,
-        value: Option<Vec<u8>>,
+        value: Option<VersionedValue>,
+        deleted_at: Option<u64>,
         neighbors: Option<Vec<Source>>,
     },
     FindNode {
@@ -88,25 +98,62 @@ pub(crate) enum Message {
     Ping {
         message_id: Option<MessageId>,
         from: Source,
+        nonce: Vec<u8>,
     },
     PingReply {
         message_id: MessageId,
         current_node: Source,
+        proof: OwnershipProof,
+    },
+    Delete {
+        key: Vec<u8>,
+        key_id: KeyId,
+        deleted_at: u64,
+        source: Source,
     },
     ShutDown,
 }
 
 impl Message {
-    pub(crate) fn store_type(key: Vec<u8>, value: Vec<u8>, source: Node) -> Self {
+    pub(crate) fn store_type(key: Vec<u8>, value: Vec<u8>, version: Version, source: Node) -> Self {
         let key_id = KeyId::generate_from_bytes(&key);
         Store {
             key,
             key_id,
+            value: VersionedValue::new(value, version),
+            source: Source::new(&source),
+        }
+    }
+
+    pub(crate) fn store_versioned_type(key: Key, value: VersionedValue, source: Node) -> Self {
+        Store {
+            key: key.key,
+            key_id: key.id,
             value,
             source: Source::new(&source),
         }
     }
 
+    pub(crate) fn mutable_store_type(record: MutableRecord, source: Node) -> Self {
+        let key_id = KeyId::generate_from_bytes(&record.key);
+        Store {
+            key: record.key,
+            key_id,
+            value: record.value,
+            source: Source::new(&source),
+        }
+    }
+
+    pub(crate) fn immutable_store_type(record: ImmutableRecord, source: Node) -> Self {
+        let key_id = KeyId::generate_from_bytes(&record.key);
+        Store {
+            key: record.key,
+            key_id,
+            value: record.value,
+            source: Source::new(&source),
+        }
+    }
+
     pub(crate) fn add_node_type(source: Node) -> Self {
         AddNode {
             source: Source::new(&source),
@@ -125,17 +172,27 @@ impl Message {
 
     pub(crate) fn find_value_reply_type(
         message_id: MessageId,
-        value: Option<Vec<u8>>,
+        value: Option<VersionedValue>,
         closest_neighbors: Option<Vec<Source>>,
     ) -> Self {
         assert!(value.is_some() || closest_neighbors.is_some());
         FindValueReply {
             message_id,
             value,
+            deleted_at: None,
             neighbors: closest_neighbors,
         }
     }
 
+    pub(crate) fn find_value_tombstone_reply_type(message_id: MessageId, deleted_at: u64) -> Self {
+        FindValueReply {
+            message_id,
+            value: None,
+            deleted_at: Some(deleted_at),
+            neighbors: None,
+        }
+    }
+
     pub(crate) fn find_node_type(source: Node, node_id: NodeId) -> Self {
         FindNode {
             source: Source::new(&source),
@@ -154,17 +211,33 @@ impl Message {
         }
     }
 
-    pub(crate) fn ping_type(current_node: Node) -> Self {
+    pub(crate) fn ping_type(current_node: Node, nonce: Vec<u8>) -> Self {
         Ping {
             message_id: None,
             from: Source::new(&current_node),
+            nonce,
         }
     }
 
-    pub(crate) fn ping_reply_type(current_node: Node, message_id: MessageId) -> Self {
+    pub(crate) fn ping_reply_type(
+        current_node: Node,
+        message_id: MessageId,
+        proof: OwnershipProof,
+    ) -> Self {
         PingReply {
             message_id,
             current_node: Source::new(&current_node),
+            proof,
+        }
+    }
+
+    pub(crate) fn delete_type(key: Vec<u8>, deleted_at: u64, source: Node) -> Self {
+        let key_id = KeyId::generate_from_bytes(&key);
+        Delete {
+            key,
+            key_id,
+            deleted_at,
+            source: Source::new(&source),
         }
     }
 
@@ -172,6 +245,7 @@ impl Message {
         ShutDown
     }
 
+    #[cfg(test)]
     pub(crate) fn is_find_value_type(&self) -> bool {
         if let FindValue { .. } = self {
             return true;
@@ -179,6 +253,7 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
     pub(crate) fn is_find_value_reply_type(&self) -> bool {
         if let FindValueReply { .. } = self {
             return true;
@@ -186,6 +261,7 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
     pub(crate) fn is_find_node_type(&self) -> bool {
         if let FindNode { .. } = self {
             return true;
@@ -193,6 +269,7 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
     pub(crate) fn is_find_node_reply_type(&self) -> bool {
         if let FindNodeReply { .. } = self {
             return true;
@@ -200,6 +277,7 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
     pub(crate) fn is_ping_type(&self) -> bool {
         if let Ping { .. } = self {
             return true;
@@ -207,6 +285,7 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
     pub(crate) fn is_ping_reply_type(&self) -> bool {
         if let PingReply { .. } = self {
             return true;
@@ -214,6 +293,15 @@ impl Message {
         return false;
     }
 
+    #[cfg(test)]
+    pub(crate) fn is_delete_type(&self) -> bool {
+        if let Delete { .. } = self {
+            return true;
+        }
+        return false;
+    }
+
+    #[cfg(test)]
     pub(crate) fn is_shutdown_type(&self) -> bool {
         if let ShutDown = self {
             return true;
@@ -248,9 +336,37 @@ impl Message {
         }
     }
 
+    pub(crate) fn message_id(&self) -> Option<MessageId> {
+        match self {
+            FindValue { message_id, .. }
+            | FindNode { message_id, .. }
+            | Ping { message_id, .. } => *message_id,
+            FindValueReply { message_id, .. }
+            | FindNodeReply { message_id, .. }
+            | PingReply { message_id, .. } => Some(*message_id),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn message_type(&self) -> MessageTypes {
+        match self {
+            Store { .. } => MessageTypes::Store,
+            AddNode { .. } => MessageTypes::AddNode,
+            FindValue { .. } => MessageTypes::FindValue,
+            FindValueReply { .. } => MessageTypes::FindValueReply,
+            FindNode { .. } => MessageTypes::FindNode,
+            FindNodeReply { .. } => MessageTypes::FindNodeReply,
+            Ping { .. } => MessageTypes::Ping,
+            PingReply { .. } => MessageTypes::PingReply,
+            Delete { .. } => MessageTypes::Delete,
+            ShutDown => MessageTypes::Shutdown,
+        }
+    }
+
     pub(crate) fn source(&self) -> Option<Node> {
         match self {
             Store { source, .. }
+            | Delete { source, .. }
             | AddNode { source, .. }
             | FindValue { source, .. }
             | FindNode { source, .. } => Some(source.clone().to_node()),
@@ -259,6 +375,7 @@ impl Message {
         }
     }
 
+    #[cfg(test)]
     fn is_store_type(&self) -> bool {
         if let Store { .. } = self {
             return true;
@@ -269,16 +386,22 @@ impl Message {
 
 #[cfg(test)]
 mod tests {
+    use ed25519_dalek::SigningKey;
+
     use crate::id::Id;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::generate_nonce;
     use crate::net::message::{Message, Source};
     use crate::net::node::Node;
+    use crate::store::record::{ImmutableRecord, MutableRecord};
+    use crate::store::VersionedValue;
 
     #[test]
     fn serialize_deserialize_a_store_message() {
         let store_type = Message::store_type(
             "kademlia".as_bytes().to_vec(),
             "distributed hash table".as_bytes().to_vec(),
+            10,
             Node::new_with_id(
                 Endpoint::new("localhost".to_string(), 1010),
                 Id::new(vec![10, 20]),
@@ -296,7 +419,11 @@ mod tests {
                 source,
             } => {
                 assert_eq!("kademlia", String::from_utf8(key).unwrap());
-                assert_eq!("distributed hash table", String::from_utf8(value).unwrap());
+                assert_eq!(
+                    "distributed hash table",
+                    String::from_utf8(value.value).unwrap()
+                );
+                assert_eq!(10, value.version);
                 assert_eq!(Id::new(vec![10, 20]), source.node_id);
             }
             _ => {
@@ -305,6 +432,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn serialize_deserialize_a_mutable_store_message() {
+        let record = MutableRecord::sign(
+            &SigningKey::from_bytes(&[7; 32]),
+            None,
+            3,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        let store_type = Message::mutable_store_type(
+            record,
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1010),
+                Id::new(vec![10, 20]),
+            ),
+        );
+        let serialized = store_type.serialize().unwrap();
+        let deserialized = Message::deserialize_from(&serialized).unwrap();
+
+        match deserialized {
+            Message::Store { key, value, .. } => {
+                assert!(value.is_mutable());
+                assert_eq!(3, value.version);
+                assert!(value.verify(&key));
+            }
+            _ => {
+                panic!("Expected store type message, but was not");
+            }
+        }
+    }
+
+    #[test]
+    fn serialize_deserialize_an_immutable_store_message() {
+        let store_type = Message::immutable_store_type(
+            ImmutableRecord::new("distributed hash table".as_bytes().to_vec()),
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1010),
+                Id::new(vec![10, 20]),
+            ),
+        );
+        let serialized = store_type.serialize().unwrap();
+        let deserialized = Message::deserialize_from(&serialized).unwrap();
+
+        match deserialized {
+            Message::Store { key, value, .. } => {
+                assert!(value.is_immutable());
+                assert!(value.verify(&key));
+            }
+            _ => {
+                panic!("Expected store type message, but was not");
+            }
+        }
+    }
+
     #[test]
     fn serialize_deserialize_a_find_value_message() {
         let node = Node::new(Endpoint::new("localhost".to_string(), 1010));
@@ -362,8 +542,11 @@ mod tests {
 
     #[test]
     fn serialize_deserialize_a_find_value_reply_message_with_value() {
-        let find_value_reply_type =
-            Message::find_value_reply_type(10, Some("kademlia".as_bytes().to_vec()), None);
+        let find_value_reply_type = Message::find_value_reply_type(
+            10,
+            Some(VersionedValue::new("kademlia".as_bytes().to_vec(), 5)),
+            None,
+        );
 
         let serialized = find_value_reply_type.serialize().unwrap();
         let deserialized = Message::deserialize_from(&serialized).unwrap();
@@ -374,9 +557,38 @@ mod tests {
                 message_id,
                 value,
                 neighbors,
+                ..
+            } => {
+                assert_eq!(10, message_id);
+                assert_eq!(
+                    Some(VersionedValue::new("kademlia".as_bytes().to_vec(), 5)),
+                    value
+                );
+                assert!(neighbors.is_none());
+            }
+            _ => {
+                panic!("Expected findValueReply type message, but was not");
+            }
+        }
+    }
+
+    #[test]
+    fn serialize_deserialize_a_find_value_reply_message_with_tombstone() {
+        let find_value_reply_type = Message::find_value_tombstone_reply_type(10, 5);
+
+        let serialized = find_value_reply_type.serialize().unwrap();
+        let deserialized = Message::deserialize_from(&serialized).unwrap();
+
+        match deserialized {
+            Message::FindValueReply {
+                message_id,
+                value,
+                deleted_at,
+                neighbors,
             } => {
                 assert_eq!(10, message_id);
-                assert_eq!(Some("kademlia".as_bytes().to_vec()), value);
+                assert!(value.is_none());
+                assert_eq!(Some(5), deleted_at);
                 assert!(neighbors.is_none());
             }
             _ => {
@@ -402,6 +614,7 @@ mod tests {
                 message_id,
                 value,
                 neighbors,
+                ..
             } => {
                 assert_eq!(10, message_id);
                 assert_eq!(value, None);
@@ -434,6 +647,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn serialize_deserialize_a_delete_message() {
+        let delete_type = Message::delete_type(
+            "kademlia".as_bytes().to_vec(),
+            1000,
+            Node::new_with_id(
+                Endpoint::new("localhost".to_string(), 1010),
+                Id::new(vec![10, 20]),
+            ),
+        );
+        let serialized = delete_type.serialize().unwrap();
+        let deserialized = Message::deserialize_from(&serialized).unwrap();
+
+        assert!(deserialized.is_delete_type());
+        match deserialized {
+            Message::Delete {
+                key,
+                deleted_at,
+                source,
+                ..
+            } => {
+                assert_eq!("kademlia", String::from_utf8(key).unwrap());
+                assert_eq!(1000, deleted_at);
+                assert_eq!(Id::new(vec![10, 20]), source.node_id);
+            }
+            _ => {
+                panic!("Expected delete type message, but was not");
+            }
+        }
+    }
+
     #[test]
     fn set_message_id_in_find_value() {
         let node = Node::new(Endpoint::new("localhost".to_string(), 1010));
@@ -459,10 +703,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn message_id_of_requests_and_replies() {
+        let node = Node::new(Endpoint::new("localhost".to_string(), 1010));
+        let mut find_node_type =
+            Message::find_node_type(node.clone(), Id::new(511u16.to_be_bytes().to_vec()));
+        assert_eq!(None, find_node_type.message_id());
+
+        find_node_type.set_message_id(100);
+        assert_eq!(Some(100), find_node_type.message_id());
+        assert_eq!(
+            Some(100),
+            Message::find_node_reply_type(100, Vec::new()).message_id()
+        );
+        assert_eq!(
+            None,
+            Message::store_type(b"kademlia".to_vec(), b"dht".to_vec(), 1, node).message_id()
+        );
+    }
+
     #[test]
     fn set_message_id_in_ping() {
-        let mut ping_type =
-            Message::ping_type(Node::new(Endpoint::new("localhost".to_string(), 2334)));
+        let mut ping_type = Message::ping_type(
+            Node::new(Endpoint::new("localhost".to_string(), 2334)),
+            generate_nonce(),
+        );
         ping_type.set_message_id(100);
 
         assert!(ping_type.is_ping_type());
@@ -2,24 +2,36 @@ use std::fmt::{Display, Formatter};
 use std::io::Error;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use rand::Rng;
+use tracing::Span;
+
+use crate::metrics::Metrics;
 use crate::net::callback::Callback;
 use crate::net::connection::AsyncTcpConnection;
 use crate::net::endpoint::Endpoint;
 use crate::net::message::{Message, MessageId};
+use crate::net::secure::SecureChannel;
 use crate::net::wait::WaitingList;
 
 pub(crate) mod callback;
 pub(crate) mod connection;
 pub(crate) mod endpoint;
+pub(crate) mod identity;
 pub(crate) mod message;
 pub(crate) mod node;
+pub(crate) mod puzzle;
+pub(crate) mod rtt;
+pub(crate) mod secure;
 pub(crate) mod wait;
 
 #[derive(Debug)]
 pub(crate) enum NetworkErrorKind {
     Io(Error),
     SerializationError(String),
+    SecureChannelError(String),
+    MessageTooLarge(usize),
 }
 
 impl From<Error> for NetworkErrorKind {
@@ -34,6 +46,12 @@ impl From<bincode::Error> for NetworkErrorKind {
     }
 }
 
+impl From<snow::Error> for NetworkErrorKind {
+    fn from(value: snow::Error) -> Self {
+        NetworkErrorKind::SecureChannelError(value.to_string())
+    }
+}
+
 impl Display for NetworkErrorKind {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -41,6 +59,12 @@ impl Display for NetworkErrorKind {
             NetworkErrorKind::SerializationError(description) => {
                 write!(formatter, "serialization err: {}", description)
             }
+            NetworkErrorKind::SecureChannelError(description) => {
+                write!(formatter, "secure channel err: {}", description)
+            }
+            NetworkErrorKind::MessageTooLarge(size) => {
+                write!(formatter, "message of {} bytes is too large", size)
+            }
         }
     }
 }
@@ -48,16 +72,51 @@ impl Display for NetworkErrorKind {
 pub(crate) struct AsyncNetwork {
     waiting_list: Arc<WaitingList>,
     next_message_id: AtomicI64,
+    secure_channel: Option<Arc<SecureChannel>>,
+    metrics: Arc<Metrics>,
 }
 
 impl AsyncNetwork {
     pub(crate) fn new(waiting_list: Arc<WaitingList>) -> Arc<Self> {
         Arc::new(AsyncNetwork {
             waiting_list,
-            next_message_id: AtomicI64::new(1),
+            next_message_id: AtomicI64::new(random_first_message_id()),
+            secure_channel: None,
+            metrics: Metrics::new(),
+        })
+    }
+
+    pub(crate) fn new_secure(
+        waiting_list: Arc<WaitingList>,
+        secure_channel: Arc<SecureChannel>,
+    ) -> Arc<Self> {
+        Arc::new(AsyncNetwork {
+            waiting_list,
+            next_message_id: AtomicI64::new(random_first_message_id()),
+            secure_channel: Some(secure_channel),
+            metrics: Metrics::new(),
         })
     }
 
+    pub(crate) fn waiting_list(&self) -> Arc<WaitingList> {
+        self.waiting_list.clone()
+    }
+
+    pub(crate) fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub(crate) fn smoothed_round_trip_time(&self, endpoint: &Endpoint) -> Option<Duration> {
+        self.waiting_list
+            .round_trip_times()
+            .estimate_for(endpoint)
+            .map(|estimate| estimate.smoothed)
+    }
+
+    pub(crate) fn secure_channel(&self) -> Option<Arc<SecureChannel>> {
+        self.secure_channel.clone()
+    }
+
     pub(crate) async fn send(
         &self,
         message: Message,
@@ -66,12 +125,15 @@ impl AsyncNetwork {
         self.connect_and_write(message, endpoint).await
     }
 
+    #[cfg(test)]
     pub(crate) async fn send_with_message_id(
         &self,
         mut message: Message,
         endpoint: &Endpoint,
     ) -> Result<(), NetworkErrorKind> {
-        message.set_message_id(self.generate_next_message_id());
+        let message_id = self.generate_next_message_id();
+        message.set_message_id(message_id);
+        Span::current().record("message_id", message_id);
         self.connect_and_write(message, endpoint).await
     }
 
@@ -83,10 +145,13 @@ impl AsyncNetwork {
     ) -> Result<(), NetworkErrorKind> {
         let message_id = self.generate_next_message_id();
         message.set_message_id(message_id);
+        Span::current().record("message_id", message_id);
 
+        self.waiting_list.add(message_id, endpoint, callback);
         let send_result = self.connect_and_write(message, endpoint).await;
-        self.waiting_list.add(message_id, callback);
-
+        if send_result.is_err() {
+            self.waiting_list.remove(&message_id);
+        }
         send_result
     }
 
@@ -95,8 +160,14 @@ impl AsyncNetwork {
         message: Message,
         endpoint: &Endpoint,
     ) -> Result<(), NetworkErrorKind> {
-        let mut tcp_connection = AsyncTcpConnection::establish_with(endpoint).await?;
+        let mut tcp_connection = match &self.secure_channel {
+            Some(secure_channel) => {
+                AsyncTcpConnection::establish_secure_with(endpoint, secure_channel).await?
+            }
+            None => AsyncTcpConnection::establish_with(endpoint).await?,
+        };
         tcp_connection.write(&message).await?;
+        self.metrics.record_sent(message.message_type());
         Ok(())
     }
 
@@ -105,6 +176,12 @@ impl AsyncNetwork {
     }
 }
 
+// Every node starting at the same id would make message ids in traces collide across nodes.
+// Seeding below u32::MAX leaves room to count up without overflowing.
+fn random_first_message_id() -> MessageId {
+    rand::thread_rng().gen_range(1..=u32::MAX as MessageId)
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -117,6 +194,7 @@ mod tests {
     use crate::net::callback::ResponseAwaitingCallback;
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::generate_nonce;
     use crate::net::message::{Message, MessageId};
     use crate::net::node::Node;
     use crate::net::wait::{WaitingList, WaitingListOptions};
@@ -133,6 +211,7 @@ mod tests {
                 Message::store_type(
                     "kademlia".as_bytes().to_vec(),
                     "distributed hash table".as_bytes().to_vec(),
+                    1,
                     Node::new_with_id(
                         Endpoint::new("localhost".to_string(), 2389),
                         Id::new(vec![10, 20]),
@@ -159,13 +238,16 @@ mod tests {
 
             assert!(message.is_ping_type());
             if let Message::Ping { message_id, .. } = message {
-                assert_eq!(Some(1), message_id);
+                assert!(message_id.is_some());
             }
         });
 
         let network_send_result = AsyncNetwork::new(waiting_list())
             .send_with_message_id(
-                Message::ping_type(Node::new(Endpoint::new("localhost".to_string(), 5665))),
+                Message::ping_type(
+                    Node::new(Endpoint::new("localhost".to_string(), 5665)),
+                    generate_nonce(),
+                ),
                 &Endpoint::new("localhost".to_string(), 8861),
             )
             .await;
@@ -182,14 +264,35 @@ mod tests {
         let waiting_list = waiting_list();
         let network_send_result = AsyncNetwork::new(waiting_list.clone())
             .send_with_message_id_expect_reply(
-                Message::ping_type(Node::new(Endpoint::new("localhost".to_string(), 5665))),
+                Message::ping_type(
+                    Node::new(Endpoint::new("localhost".to_string(), 5665)),
+                    generate_nonce(),
+                ),
                 &Endpoint::new("localhost".to_string(), 2334),
                 ResponseAwaitingCallback::new(),
             )
             .await;
 
         assert!(network_send_result.is_ok());
-        assert!(waiting_list.contains(&1));
+        assert_eq!(1, waiting_list.pending_responses_count());
+    }
+
+    #[tokio::test]
+    async fn send_message_with_id_expect_reply_to_an_unreachable_endpoint() {
+        let waiting_list = waiting_list();
+        let network_send_result = AsyncNetwork::new(waiting_list.clone())
+            .send_with_message_id_expect_reply(
+                Message::ping_type(
+                    Node::new(Endpoint::new("localhost".to_string(), 5665)),
+                    generate_nonce(),
+                ),
+                &Endpoint::new("localhost".to_string(), 6127),
+                ResponseAwaitingCallback::new(),
+            )
+            .await;
+
+        assert!(network_send_result.is_err());
+        assert_eq!(0, waiting_list.pending_responses_count());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -208,7 +311,22 @@ mod tests {
         }
         message_ids.sort();
 
-        assert_eq!((1..100).collect::<Vec<MessageId>>(), message_ids);
+        let first_message_id = message_ids[0];
+        assert_eq!(
+            (first_message_id..first_message_id + 99).collect::<Vec<MessageId>>(),
+            message_ids
+        );
+    }
+
+    #[test]
+    fn seed_the_message_ids_of_each_network_randomly() {
+        let first = AsyncNetwork::new(waiting_list());
+        let second = AsyncNetwork::new(waiting_list());
+
+        assert_ne!(
+            first.generate_next_message_id(),
+            second.generate_next_message_id()
+        );
     }
 
     fn waiting_list() -> Arc<WaitingList> {
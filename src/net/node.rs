@@ -1,26 +1,64 @@
+#[cfg(test)]
 use num_bigint::BigInt;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::id::Id;
 use crate::net::endpoint::Endpoint;
+#[cfg(test)]
+use crate::net::identity::Identity;
 
 pub(crate) type NodeId = Id;
 
-#[derive(Eq, PartialEq, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct Node {
     pub(crate) id: NodeId,
     pub(crate) endpoint: Endpoint,
+    pub(crate) puzzle_solution: Vec<u8>,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.endpoint == other.endpoint
+    }
 }
 
+impl Eq for Node {}
+
 impl Node {
+    #[cfg(test)]
     pub(crate) fn new(endpoint: Endpoint) -> Self {
         Node {
             id: Id::generate_from(endpoint.address()),
             endpoint,
+            puzzle_solution: Vec::new(),
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn new_with_identity(endpoint: Endpoint, identity: &Identity) -> Self {
+        Node {
+            id: identity.node_id(),
+            endpoint,
+            puzzle_solution: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
     pub(crate) fn new_with_id(endpoint: Endpoint, id: NodeId) -> Self {
-        Node { id, endpoint }
+        Self::new_with_puzzle_solution(endpoint, id, Vec::new())
+    }
+
+    pub(crate) fn new_with_puzzle_solution(
+        endpoint: Endpoint,
+        id: NodeId,
+        puzzle_solution: Vec<u8>,
+    ) -> Self {
+        Node {
+            id,
+            endpoint,
+            puzzle_solution,
+        }
     }
 
     pub(crate) fn node_id(&self) -> NodeId {
@@ -31,6 +69,7 @@ impl Node {
         self.endpoint.clone()
     }
 
+    #[cfg(test)]
     fn distance_from(&self, other: &Node) -> BigInt {
         self.id.distance_from(&other.id)
     }
@@ -41,6 +80,7 @@ mod tests {
     use num_bigint::BigInt;
 
     use crate::net::endpoint::Endpoint;
+    use crate::net::identity::Identity;
     use crate::net::node::Node;
 
     #[test]
@@ -81,4 +121,23 @@ mod tests {
         let expected_distance: u8 = 0;
         assert!(distance.eq(&BigInt::from(expected_distance)));
     }
+
+    #[test]
+    fn node_with_identity_keeps_its_id_across_endpoints() {
+        let identity = Identity::from_bytes([7; 32]);
+        let node = Node::new_with_identity(Endpoint::new("localhost".to_string(), 2330), &identity);
+        let other_node =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 1982), &identity);
+
+        assert_eq!(identity.node_id(), node.id);
+        assert_eq!(node.id, other_node.id);
+    }
+
+    #[test]
+    fn node_equality_does_not_depend_on_puzzle_solution() {
+        let node = Node::new(Endpoint::new("localhost".to_string(), 2330));
+        let other_node =
+            Node::new_with_puzzle_solution(node.endpoint.clone(), node.id.clone(), vec![1, 2]);
+        assert!(node.eq(&other_node))
+    }
 }
@@ -0,0 +1,136 @@
+use crate::id::Id;
+use crate::net::node::NodeId;
+
+#[derive(Copy, Clone)]
+pub(crate) struct PuzzleOptions {
+    pub(crate) static_difficulty: u32,
+    pub(crate) dynamic_difficulty: u32,
+}
+
+impl PuzzleOptions {
+    pub(crate) fn new(static_difficulty: u32, dynamic_difficulty: u32) -> Self {
+        PuzzleOptions {
+            static_difficulty,
+            dynamic_difficulty,
+        }
+    }
+
+    pub(crate) fn verify(&self, node_id: &NodeId, dynamic_solution: &[u8]) -> bool {
+        solves_static_puzzle(node_id, self.static_difficulty)
+            && solves_dynamic_puzzle(node_id, dynamic_solution, self.dynamic_difficulty)
+    }
+}
+
+pub(crate) fn solves_static_puzzle(node_id: &NodeId, difficulty: u32) -> bool {
+    if difficulty == 0 {
+        return true;
+    }
+    leading_zero_bits(&Id::generate_from_bytes(node_id.as_bytes())) >= difficulty
+}
+
+pub(crate) fn solve_dynamic_puzzle(node_id: &NodeId, difficulty: u32) -> Vec<u8> {
+    if difficulty == 0 {
+        return Vec::new();
+    }
+    let mut counter: u64 = 0;
+    loop {
+        let solution = dynamic_solution_from(counter, node_id.len());
+        if solves_dynamic_puzzle(node_id, &solution, difficulty) {
+            return solution;
+        }
+        counter += 1;
+    }
+}
+
+pub(crate) fn solves_dynamic_puzzle(node_id: &NodeId, solution: &[u8], difficulty: u32) -> bool {
+    if difficulty == 0 {
+        return true;
+    }
+    if solution.len() != node_id.len() {
+        return false;
+    }
+    let mixed: Vec<u8> = node_id
+        .as_bytes()
+        .iter()
+        .zip(solution)
+        .map(|(id_byte, solution_byte)| id_byte ^ solution_byte)
+        .collect();
+    leading_zero_bits(&Id::generate_from_bytes(&mixed)) >= difficulty
+}
+
+fn dynamic_solution_from(counter: u64, length: usize) -> Vec<u8> {
+    let mut solution = vec![0; length];
+    let counter_bytes = counter.to_be_bytes();
+    let total = counter_bytes.len().min(length);
+    solution[length - total..].copy_from_slice(&counter_bytes[counter_bytes.len() - total..]);
+    solution
+}
+
+fn leading_zero_bits(id: &Id) -> u32 {
+    let mut total = 0;
+    for byte in id.as_bytes() {
+        if *byte != 0 {
+            return total + byte.leading_zeros();
+        }
+        total += 8;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id::Id;
+    use crate::net::identity::Identity;
+    use crate::net::puzzle::{
+        leading_zero_bits, solve_dynamic_puzzle, solves_dynamic_puzzle, solves_static_puzzle,
+        PuzzleOptions,
+    };
+
+    #[test]
+    fn leading_zero_bits_of_an_id() {
+        assert_eq!(11, leading_zero_bits(&Id::new(vec![0, 16, 255])));
+    }
+
+    #[test]
+    fn any_node_id_solves_puzzles_without_difficulty() {
+        let node_id = Identity::from_bytes([7; 32]).node_id();
+        assert!(PuzzleOptions::new(0, 0).verify(&node_id, &[]));
+    }
+
+    #[test]
+    fn identity_generated_with_static_puzzle_solves_it() {
+        let identity = Identity::generate_with_static_puzzle(6);
+        assert!(solves_static_puzzle(&identity.node_id(), 6));
+    }
+
+    #[test]
+    fn solve_a_dynamic_puzzle() {
+        let node_id = Identity::from_bytes([7; 32]).node_id();
+        let solution = solve_dynamic_puzzle(&node_id, 8);
+
+        assert!(solves_dynamic_puzzle(&node_id, &solution, 8));
+    }
+
+    #[test]
+    fn dynamic_puzzle_solution_does_not_apply_to_another_node_id() {
+        let node_id = Identity::from_bytes([7; 32]).node_id();
+        let other_node_id = Identity::from_bytes([9; 32]).node_id();
+        let solution = solve_dynamic_puzzle(&node_id, 12);
+
+        assert!(!solves_dynamic_puzzle(&other_node_id, &solution, 12));
+    }
+
+    #[test]
+    fn do_not_verify_a_node_id_without_a_dynamic_puzzle_solution() {
+        let identity = Identity::generate_with_static_puzzle(4);
+        assert!(!PuzzleOptions::new(4, 8).verify(&identity.node_id(), &[]));
+    }
+
+    #[test]
+    fn verify_a_node_id_solving_both_puzzles() {
+        let identity = Identity::generate_with_static_puzzle(4);
+        let solution = solve_dynamic_puzzle(&identity.node_id(), 8);
+
+        assert!(PuzzleOptions::new(4, 8).verify(&identity.node_id(), &solution));
+    }
+}
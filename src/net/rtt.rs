@@ -0,0 +1,348 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::metrics::{Histogram, PrometheusText};
+use crate::net::endpoint::Endpoint;
+use crate::net::message::MessageTypes;
+
+pub(crate) const DEFAULT_MIN_RESPONSE_TIMEOUT: Duration = Duration::from_millis(200);
+pub(crate) const DEFAULT_MAX_RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+const SMOOTHED_ROUND_TRIP_TIME_GAIN: f64 = 0.125;
+const ROUND_TRIP_TIME_VARIATION_GAIN: f64 = 0.25;
+const ROUND_TRIP_TIME_VARIATION_FACTOR: u32 = 4;
+const MAX_BACKOFF_SHIFT: u32 = 6;
+const MAX_ROUND_TRIP_TIME_ESTIMATES: usize = 4096;
+const RPC_LATENCY_BUCKETS_IN_SECONDS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+// Smoothed round-trip time and its variation, estimated as TCP does (RFC 6298).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct RoundTripTimeEstimate {
+    pub(crate) smoothed: Duration,
+    pub(crate) variation: Duration,
+    backoff_shift: u32,
+}
+
+impl RoundTripTimeEstimate {
+    fn new(sample: Duration) -> Self {
+        RoundTripTimeEstimate {
+            smoothed: sample,
+            variation: sample / 2,
+            backoff_shift: 0,
+        }
+    }
+
+    fn observe(&mut self, sample: Duration) {
+        let deviation = self.smoothed.abs_diff(sample);
+        self.variation = self.variation.mul_f64(1.0 - ROUND_TRIP_TIME_VARIATION_GAIN)
+            + deviation.mul_f64(ROUND_TRIP_TIME_VARIATION_GAIN);
+        self.smoothed = self.smoothed.mul_f64(1.0 - SMOOTHED_ROUND_TRIP_TIME_GAIN)
+            + sample.mul_f64(SMOOTHED_ROUND_TRIP_TIME_GAIN);
+        self.backoff_shift = 0;
+    }
+
+    fn back_off(&mut self) {
+        self.backoff_shift = (self.backoff_shift + 1).min(MAX_BACKOFF_SHIFT);
+    }
+
+    fn timeout(&self) -> Duration {
+        (self.smoothed + self.variation * ROUND_TRIP_TIME_VARIATION_FACTOR)
+            * (1 << self.backoff_shift)
+    }
+}
+
+struct TrackedEstimate {
+    estimate: RoundTripTimeEstimate,
+    last_observed: u64,
+}
+
+// Estimates are kept for at most max_estimates peers, the least recently observed peer is
+// forgotten first, so peers that come and go do not grow the estimates without bound. The
+// latency and timeout counters are exported with the other metrics of the node.
+pub(crate) struct RoundTripTimes {
+    estimates: DashMap<String, TrackedEstimate>,
+    observations: AtomicU64,
+    rpc_latency: DashMap<MessageTypes, Histogram>,
+    rpc_timeouts: AtomicU64,
+    max_estimates: usize,
+    initial_timeout: Duration,
+    min_timeout: Duration,
+    max_timeout: Duration,
+}
+
+impl RoundTripTimes {
+    pub(crate) fn new(
+        initial_timeout: Duration,
+        min_timeout: Duration,
+        max_timeout: Duration,
+    ) -> Self {
+        RoundTripTimes {
+            estimates: DashMap::new(),
+            observations: AtomicU64::new(0),
+            rpc_latency: DashMap::new(),
+            rpc_timeouts: AtomicU64::new(0),
+            max_estimates: MAX_ROUND_TRIP_TIME_ESTIMATES,
+            initial_timeout,
+            min_timeout,
+            max_timeout,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_default_timeouts() -> Arc<Self> {
+        Arc::new(RoundTripTimes::new(
+            DEFAULT_MAX_RESPONSE_TIMEOUT,
+            DEFAULT_MIN_RESPONSE_TIMEOUT,
+            DEFAULT_MAX_RESPONSE_TIMEOUT,
+        ))
+    }
+
+    #[cfg(test)]
+    fn with_max_estimates(mut self, max_estimates: usize) -> Self {
+        self.max_estimates = max_estimates;
+        self
+    }
+
+    pub(crate) fn observe_reply(
+        &self,
+        endpoint: &Endpoint,
+        reply_type: MessageTypes,
+        sample: Duration,
+    ) {
+        self.observe(endpoint, sample);
+        self.rpc_latency
+            .entry(reply_type)
+            .or_insert_with(|| Histogram::new(&RPC_LATENCY_BUCKETS_IN_SECONDS))
+            .observe(sample.as_secs_f64());
+    }
+
+    pub(crate) fn record_timeout(&self, endpoint: &Endpoint) {
+        self.back_off(endpoint);
+        self.rpc_timeouts.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn observe(&self, endpoint: &Endpoint, sample: Duration) {
+        let address = endpoint.address();
+        if !self.estimates.contains_key(&address) && self.estimates.len() >= self.max_estimates {
+            self.forget_least_recently_observed();
+        }
+        let last_observed = self.observations.fetch_add(1, Ordering::AcqRel);
+        self.estimates
+            .entry(address)
+            .and_modify(|tracked| {
+                tracked.estimate.observe(sample);
+                tracked.last_observed = last_observed;
+            })
+            .or_insert_with(|| TrackedEstimate {
+                estimate: RoundTripTimeEstimate::new(sample),
+                last_observed,
+            });
+    }
+
+    // A timed out request doubles the timeout of the peer until a reply is measured again,
+    // otherwise a peer that became slower would never get a chance to answer in time.
+    fn back_off(&self, endpoint: &Endpoint) {
+        if let Some(mut tracked) = self.estimates.get_mut(&endpoint.address()) {
+            tracked.estimate.back_off();
+        }
+    }
+
+    pub(crate) fn timeout_for(&self, endpoint: &Endpoint) -> Duration {
+        match self.estimates.get(&endpoint.address()) {
+            Some(tracked) => tracked
+                .estimate
+                .timeout()
+                .clamp(self.min_timeout, self.max_timeout),
+            None => self.initial_timeout,
+        }
+    }
+
+    pub(crate) fn estimate_for(&self, endpoint: &Endpoint) -> Option<RoundTripTimeEstimate> {
+        self.estimates
+            .get(&endpoint.address())
+            .map(|tracked| tracked.estimate)
+    }
+
+    pub(crate) fn rpc_timeouts(&self) -> u64 {
+        self.rpc_timeouts.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn render(&self, text: &mut PrometheusText) {
+        text.describe(
+            "kademlia_rpc_latency_seconds",
+            "histogram",
+            "Time from sending a request to receiving its reply, by reply type",
+        );
+        let mut reply_types: Vec<MessageTypes> =
+            self.rpc_latency.iter().map(|entry| *entry.key()).collect();
+        reply_types.sort_by_key(|message_type| *message_type as u8);
+        for reply_type in reply_types {
+            if let Some(histogram) = self.rpc_latency.get(&reply_type) {
+                let label = format!("{:?}", reply_type);
+                histogram.render(
+                    text,
+                    "kademlia_rpc_latency_seconds",
+                    &[("type", label.as_str())],
+                );
+            }
+        }
+
+        text.describe(
+            "kademlia_rpc_timeouts_total",
+            "counter",
+            "Requests that expired without a reply",
+        );
+        text.sample("kademlia_rpc_timeouts_total", &[], self.rpc_timeouts());
+    }
+
+    fn forget_least_recently_observed(&self) {
+        let least_recently_observed = self
+            .estimates
+            .iter()
+            .min_by_key(|entry| entry.last_observed)
+            .map(|entry| entry.key().clone());
+        if let Some(address) = least_recently_observed {
+            self.estimates.remove(&address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::PrometheusText;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::message::MessageTypes;
+    use crate::net::rtt::RoundTripTimes;
+
+    #[test]
+    fn use_the_initial_timeout_for_an_unknown_peer() {
+        let round_trip_times = round_trip_times();
+        assert_eq!(
+            Duration::from_secs(5),
+            round_trip_times.timeout_for(&endpoint(2379))
+        );
+        assert!(round_trip_times.estimate_for(&endpoint(2379)).is_none());
+    }
+
+    #[test]
+    fn derive_the_timeout_from_the_first_sample() {
+        let round_trip_times = round_trip_times();
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+
+        let estimate = round_trip_times.estimate_for(&endpoint(2379)).unwrap();
+        assert_eq!(Duration::from_millis(100), estimate.smoothed);
+        assert_eq!(Duration::from_millis(50), estimate.variation);
+        assert_eq!(
+            Duration::from_millis(300),
+            round_trip_times.timeout_for(&endpoint(2379))
+        );
+    }
+
+    #[test]
+    fn smooth_subsequent_samples() {
+        let round_trip_times = round_trip_times();
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(180));
+
+        let estimate = round_trip_times.estimate_for(&endpoint(2379)).unwrap();
+        assert_eq!(Duration::from_millis(110), estimate.smoothed);
+        assert_eq!(Duration::from_micros(57_500), estimate.variation);
+        assert_eq!(
+            Duration::from_millis(340),
+            round_trip_times.timeout_for(&endpoint(2379))
+        );
+    }
+
+    #[test]
+    fn bound_the_timeout() {
+        let round_trip_times = round_trip_times();
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(1));
+        round_trip_times.observe(&endpoint(2380), Duration::from_secs(60));
+
+        assert_eq!(
+            Duration::from_millis(50),
+            round_trip_times.timeout_for(&endpoint(2379))
+        );
+        assert_eq!(
+            Duration::from_secs(10),
+            round_trip_times.timeout_for(&endpoint(2380))
+        );
+    }
+
+    #[test]
+    fn back_off_after_a_timeout_until_the_next_sample() {
+        let round_trip_times = round_trip_times();
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+
+        round_trip_times.back_off(&endpoint(2379));
+        assert_eq!(
+            Duration::from_millis(600),
+            round_trip_times.timeout_for(&endpoint(2379))
+        );
+
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+        assert!(round_trip_times.timeout_for(&endpoint(2379)) < Duration::from_millis(600));
+    }
+
+    #[test]
+    fn forget_the_least_recently_observed_peer_beyond_the_max_estimates() {
+        let round_trip_times = round_trip_times().with_max_estimates(2);
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+        round_trip_times.observe(&endpoint(2380), Duration::from_millis(100));
+        round_trip_times.observe(&endpoint(2379), Duration::from_millis(100));
+        round_trip_times.observe(&endpoint(2381), Duration::from_millis(100));
+
+        assert!(round_trip_times.estimate_for(&endpoint(2379)).is_some());
+        assert!(round_trip_times.estimate_for(&endpoint(2380)).is_none());
+        assert!(round_trip_times.estimate_for(&endpoint(2381)).is_some());
+    }
+
+    #[test]
+    fn render_the_reply_latencies_and_timeouts() {
+        let round_trip_times = round_trip_times();
+        round_trip_times.observe_reply(
+            &endpoint(2379),
+            MessageTypes::PingReply,
+            Duration::from_millis(3),
+        );
+        round_trip_times.observe_reply(
+            &endpoint(2379),
+            MessageTypes::PingReply,
+            Duration::from_millis(30),
+        );
+        round_trip_times.record_timeout(&endpoint(2379));
+
+        let mut text = PrometheusText::new();
+        round_trip_times.render(&mut text);
+        let text = text.into_string();
+
+        assert!(text
+            .contains("kademlia_rpc_latency_seconds_bucket{type=\"PingReply\",le=\"0.005\"} 1\n"));
+        assert!(text
+            .contains("kademlia_rpc_latency_seconds_bucket{type=\"PingReply\",le=\"0.05\"} 2\n"));
+        assert!(text
+            .contains("kademlia_rpc_latency_seconds_bucket{type=\"PingReply\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("kademlia_rpc_latency_seconds_count{type=\"PingReply\"} 2\n"));
+        assert!(text.contains("kademlia_rpc_timeouts_total 1\n"));
+    }
+
+    fn round_trip_times() -> RoundTripTimes {
+        RoundTripTimes::new(
+            Duration::from_secs(5),
+            Duration::from_millis(50),
+            Duration::from_secs(10),
+        )
+    }
+
+    fn endpoint(port: u16) -> Endpoint {
+        Endpoint::new("localhost".to_string(), port)
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde::Serialize;
+use snow::params::NoiseParams;
+use snow::{Builder, HandshakeState, Keypair, TransportState};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::net::identity::{node_id_of, verify_signature, Identity};
+use crate::net::message::{Message, RESERVED_MESSAGE_SIZE};
+use crate::net::node::NodeId;
+use crate::net::NetworkErrorKind;
+
+const NOISE_PATTERN: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+const MAX_NOISE_MESSAGE_SIZE: usize = 65535;
+const NOISE_TAG_SIZE: usize = 16;
+const MAX_PLAINTEXT_CHUNK_SIZE: usize = MAX_NOISE_MESSAGE_SIZE - NOISE_TAG_SIZE;
+const FRAME_LENGTH_SIZE: usize = 2;
+const STATIC_KEY_SIGNATURE_CONTEXT: &[u8] = b"kademlia-noise-static-key:";
+
+#[derive(Serialize, Deserialize)]
+struct IdentityPayload {
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+pub(crate) struct SecureChannel {
+    identity: Arc<Identity>,
+    static_keypair: Keypair,
+}
+
+impl SecureChannel {
+    pub(crate) fn new(identity: Arc<Identity>) -> Result<Arc<Self>, NetworkErrorKind> {
+        let static_keypair = Builder::new(noise_params()).generate_keypair()?;
+        Ok(Arc::new(SecureChannel {
+            identity,
+            static_keypair,
+        }))
+    }
+
+    pub(crate) async fn initiate(
+        &self,
+        tcp_stream: &mut TcpStream,
+    ) -> Result<SecureSession, NetworkErrorKind> {
+        let mut handshake = Builder::new(noise_params())
+            .local_private_key(&self.static_keypair.private)
+            .build_initiator()?;
+
+        write_handshake_message(&mut handshake, tcp_stream, &[]).await?;
+        let payload = read_handshake_message(&mut handshake, tcp_stream).await?;
+        let remote_node_id = authenticate(&handshake, &payload)?;
+        write_handshake_message(&mut handshake, tcp_stream, &self.identity_payload()?).await?;
+
+        Ok(SecureSession {
+            transport: handshake.into_transport_mode()?,
+            remote_node_id,
+        })
+    }
+
+    pub(crate) async fn respond(
+        &self,
+        tcp_stream: &mut TcpStream,
+    ) -> Result<SecureSession, NetworkErrorKind> {
+        let mut handshake = Builder::new(noise_params())
+            .local_private_key(&self.static_keypair.private)
+            .build_responder()?;
+
+        read_handshake_message(&mut handshake, tcp_stream).await?;
+        write_handshake_message(&mut handshake, tcp_stream, &self.identity_payload()?).await?;
+        let payload = read_handshake_message(&mut handshake, tcp_stream).await?;
+        let remote_node_id = authenticate(&handshake, &payload)?;
+
+        Ok(SecureSession {
+            transport: handshake.into_transport_mode()?,
+            remote_node_id,
+        })
+    }
+
+    fn identity_payload(&self) -> Result<Vec<u8>, NetworkErrorKind> {
+        let payload = IdentityPayload {
+            public_key: self.identity.public_key(),
+            signature: self
+                .identity
+                .sign(&static_key_signable(&self.static_keypair.public)),
+        };
+        Ok(bincode::serialize(&payload)?)
+    }
+}
+
+pub(crate) struct SecureSession {
+    transport: TransportState,
+    remote_node_id: NodeId,
+}
+
+impl SecureSession {
+    pub(crate) fn remote_node_id(&self) -> &NodeId {
+        &self.remote_node_id
+    }
+
+    pub(crate) async fn read(
+        &mut self,
+        tcp_stream: &mut TcpStream,
+        max_message_size: usize,
+    ) -> Result<Message, NetworkErrorKind> {
+        let mut message = Vec::new();
+        let mut chunk = vec![0; MAX_NOISE_MESSAGE_SIZE];
+        loop {
+            let frame = read_frame(tcp_stream).await?;
+            let length = self.transport.read_message(&frame, &mut chunk)?;
+            message.extend_from_slice(&chunk[..length]);
+
+            if message.len() >= RESERVED_MESSAGE_SIZE {
+                let mut message_size: [u8; RESERVED_MESSAGE_SIZE] = [0; RESERVED_MESSAGE_SIZE];
+                message_size.copy_from_slice(&message[..RESERVED_MESSAGE_SIZE]);
+                let message_size = u32::from_be_bytes(message_size) as usize;
+                if message_size > max_message_size {
+                    return Err(NetworkErrorKind::MessageTooLarge(message_size));
+                }
+                if message.len() >= message_size + RESERVED_MESSAGE_SIZE {
+                    return Ok(Message::deserialize_from(&message)?);
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn write(
+        &mut self,
+        tcp_stream: &mut TcpStream,
+        message: &Message,
+    ) -> Result<(), NetworkErrorKind> {
+        let serialized = message.serialize()?;
+        let mut frame = vec![0; MAX_NOISE_MESSAGE_SIZE];
+        for chunk in serialized.chunks(MAX_PLAINTEXT_CHUNK_SIZE) {
+            let length = self.transport.write_message(chunk, &mut frame)?;
+            write_frame(tcp_stream, &frame[..length]).await?;
+        }
+        Ok(())
+    }
+}
+
+fn noise_params() -> NoiseParams {
+    NOISE_PATTERN.parse().unwrap()
+}
+
+fn static_key_signable(static_public_key: &[u8]) -> Vec<u8> {
+    let mut signable = STATIC_KEY_SIGNATURE_CONTEXT.to_vec();
+    signable.extend_from_slice(static_public_key);
+    signable
+}
+
+fn authenticate(handshake: &HandshakeState, payload: &[u8]) -> Result<NodeId, NetworkErrorKind> {
+    let remote_static = handshake.get_remote_static().ok_or_else(|| {
+        NetworkErrorKind::SecureChannelError("remote did not send a static key".to_string())
+    })?;
+    let payload: IdentityPayload = bincode::deserialize(payload)?;
+    if !verify_signature(
+        &payload.public_key,
+        &static_key_signable(remote_static),
+        &payload.signature,
+    ) {
+        return Err(NetworkErrorKind::SecureChannelError(
+            "remote static key is not signed by its node key".to_string(),
+        ));
+    }
+    Ok(node_id_of(&payload.public_key))
+}
+
+async fn write_handshake_message(
+    handshake: &mut HandshakeState,
+    tcp_stream: &mut TcpStream,
+    payload: &[u8],
+) -> Result<(), NetworkErrorKind> {
+    let mut message = vec![0; MAX_NOISE_MESSAGE_SIZE];
+    let length = handshake.write_message(payload, &mut message)?;
+    write_frame(tcp_stream, &message[..length]).await
+}
+
+async fn read_handshake_message(
+    handshake: &mut HandshakeState,
+    tcp_stream: &mut TcpStream,
+) -> Result<Vec<u8>, NetworkErrorKind> {
+    let frame = read_frame(tcp_stream).await?;
+    let mut payload = vec![0; MAX_NOISE_MESSAGE_SIZE];
+    let length = handshake.read_message(&frame, &mut payload)?;
+    payload.truncate(length);
+    Ok(payload)
+}
+
+async fn write_frame(tcp_stream: &mut TcpStream, frame: &[u8]) -> Result<(), NetworkErrorKind> {
+    tcp_stream
+        .write_all(&(frame.len() as u16).to_be_bytes())
+        .await?;
+    tcp_stream.write_all(frame).await?;
+    Ok(())
+}
+
+async fn read_frame(tcp_stream: &mut TcpStream) -> Result<Vec<u8>, NetworkErrorKind> {
+    let mut frame_length: [u8; FRAME_LENGTH_SIZE] = [0; FRAME_LENGTH_SIZE];
+    tcp_stream.read_exact(&mut frame_length).await?;
+
+    let mut frame = vec![0; u16::from_be_bytes(frame_length) as usize];
+    tcp_stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
@@ -8,7 +8,9 @@ use std::time::{Duration, SystemTime};
 use dashmap::DashMap;
 
 use crate::net::callback::{Callback, ResponseError};
+use crate::net::endpoint::Endpoint;
 use crate::net::message::{Message, MessageId};
+use crate::net::rtt::{RoundTripTimes, DEFAULT_MAX_RESPONSE_TIMEOUT, DEFAULT_MIN_RESPONSE_TIMEOUT};
 use crate::time::Clock;
 
 #[derive(Debug)]
@@ -27,13 +29,22 @@ impl Error for ResponseTimeoutError {}
 pub(crate) struct TimedCallback {
     callback: Arc<dyn Callback>,
     creation_time: SystemTime,
+    expiry_after: Duration,
+    endpoint: Endpoint,
 }
 
 impl TimedCallback {
-    fn new(callback: Arc<dyn Callback>, creation_time: SystemTime) -> Self {
+    fn new(
+        callback: Arc<dyn Callback>,
+        creation_time: SystemTime,
+        expiry_after: Duration,
+        endpoint: Endpoint,
+    ) -> Self {
         TimedCallback {
             callback,
             creation_time,
+            expiry_after,
+            endpoint,
         }
     }
 
@@ -48,13 +59,10 @@ impl TimedCallback {
             })));
     }
 
-    fn has_expired(&self, clock: &Box<dyn Clock>, expiry_after: &Duration) -> bool {
-        clock.duration_since(self.creation_time).gt(expiry_after)
-    }
-
-    #[cfg(test)]
-    fn get_callback(&self) -> &Arc<dyn Callback> {
-        &self.callback
+    fn has_expired(&self, clock: &dyn Clock) -> bool {
+        clock
+            .duration_since(self.creation_time)
+            .gt(&self.expiry_after)
     }
 }
 
@@ -62,6 +70,8 @@ impl TimedCallback {
 pub(crate) struct WaitingListOptions {
     pub(crate) expire_pending_responses_after: Duration,
     pub(crate) run_expired_pending_responses_checker_every: Duration,
+    pub(crate) min_response_timeout: Duration,
+    pub(crate) max_response_timeout: Duration,
 }
 
 impl WaitingListOptions {
@@ -72,13 +82,26 @@ impl WaitingListOptions {
         WaitingListOptions {
             expire_pending_responses_after,
             run_expired_pending_responses_checker_every,
+            min_response_timeout: DEFAULT_MIN_RESPONSE_TIMEOUT,
+            max_response_timeout: DEFAULT_MAX_RESPONSE_TIMEOUT,
         }
     }
+
+    pub(crate) fn with_response_timeout_bounds(
+        mut self,
+        min_response_timeout: Duration,
+        max_response_timeout: Duration,
+    ) -> Self {
+        self.min_response_timeout = min_response_timeout;
+        self.max_response_timeout = max_response_timeout;
+        self
+    }
 }
 
 pub(crate) struct WaitingList {
     pending_responses: Arc<DashMap<MessageId, TimedCallback>>,
     expired_pending_responses_cleaner: Arc<ExpiredPendingResponsesCleaner>,
+    round_trip_times: Arc<RoundTripTimes>,
     clock: Box<dyn Clock>,
 }
 
@@ -88,27 +111,46 @@ impl WaitingList {
         clock: Box<dyn Clock>,
     ) -> Arc<Self> {
         let pending_responses = Arc::new(DashMap::new());
+        let round_trip_times = Arc::new(RoundTripTimes::new(
+            waiting_list_options.expire_pending_responses_after,
+            waiting_list_options.min_response_timeout,
+            waiting_list_options.max_response_timeout,
+        ));
         let cleaner = ExpiredPendingResponsesCleaner::new(
             waiting_list_options,
             pending_responses.clone(),
+            round_trip_times.clone(),
             clock.clone(),
         );
 
         let waiting_list = WaitingList {
             pending_responses,
             expired_pending_responses_cleaner: cleaner,
+            round_trip_times,
             clock,
         };
         Arc::new(waiting_list)
     }
 
-    pub(crate) fn add(&self, message_id: MessageId, callback: Arc<dyn Callback>) {
-        self.pending_responses
-            .insert(message_id, TimedCallback::new(callback, self.clock.now()));
+    pub(crate) fn add(
+        &self,
+        message_id: MessageId,
+        endpoint: &Endpoint,
+        callback: Arc<dyn Callback>,
+    ) {
+        let expiry_after = self.round_trip_times.timeout_for(endpoint);
+        self.pending_responses.insert(
+            message_id,
+            TimedCallback::new(callback, self.clock.now(), expiry_after, endpoint.clone()),
+        );
+    }
+
+    pub(crate) fn remove(&self, message_id: &MessageId) {
+        self.pending_responses.remove(message_id);
     }
 
-    pub(crate) fn contains(&self, message_id: &MessageId) -> bool {
-        self.pending_responses.contains_key(message_id)
+    pub(crate) fn pending_responses_count(&self) -> usize {
+        self.pending_responses.len()
     }
 
     pub(crate) fn handle_response(
@@ -119,10 +161,22 @@ impl WaitingList {
         let key_value_existence = self.pending_responses.remove(&message_id);
         if let Some(callback_by_key) = key_value_existence {
             let callback = callback_by_key.1;
+            if let Ok(reply) = &response {
+                let round_trip_time = self.clock.duration_since(callback.creation_time);
+                self.round_trip_times.observe_reply(
+                    &callback.endpoint,
+                    reply.message_type(),
+                    round_trip_time,
+                );
+            }
             callback.on_response(response);
         }
     }
 
+    pub(crate) fn round_trip_times(&self) -> Arc<RoundTripTimes> {
+        self.round_trip_times.clone()
+    }
+
     pub(crate) fn stop(&self) {
         self.expired_pending_responses_cleaner.stop();
     }
@@ -130,8 +184,8 @@ impl WaitingList {
 
 struct ExpiredPendingResponsesCleaner {
     pending_responses: Arc<DashMap<MessageId, TimedCallback>>,
+    round_trip_times: Arc<RoundTripTimes>,
     clock: Box<dyn Clock>,
-    expiry_after: Duration,
     should_stop: AtomicBool,
 }
 
@@ -139,12 +193,13 @@ impl ExpiredPendingResponsesCleaner {
     fn new(
         waiting_list_options: WaitingListOptions,
         pending_responses: Arc<DashMap<MessageId, TimedCallback>>,
+        round_trip_times: Arc<RoundTripTimes>,
         clock: Box<dyn Clock>,
     ) -> Arc<ExpiredPendingResponsesCleaner> {
         let cleaner = Arc::new(ExpiredPendingResponsesCleaner {
             pending_responses,
+            round_trip_times,
             clock,
-            expiry_after: waiting_list_options.expire_pending_responses_after,
             should_stop: AtomicBool::new(false),
         });
         cleaner.clone().start(waiting_list_options);
@@ -167,7 +222,9 @@ impl ExpiredPendingResponsesCleaner {
 
     fn clean(self: &Arc<ExpiredPendingResponsesCleaner>) {
         self.pending_responses.retain(|message_id, timed_callback| {
-            if timed_callback.has_expired(&self.clock, &self.expiry_after) {
+            if timed_callback.has_expired(self.clock.as_ref()) {
+                self.round_trip_times
+                    .record_timeout(&timed_callback.endpoint);
                 timed_callback.on_timeout_response(message_id);
                 return false;
             }
@@ -181,13 +238,13 @@ mod waiting_list_tests {
     use std::thread;
     use std::time::Duration;
 
+    use crate::net::endpoint::Endpoint;
     use crate::net::message::{Message, MessageId};
     use crate::net::wait::waiting_list_tests::setup::{TestCallback, TestError};
     use crate::net::wait::{WaitingList, WaitingListOptions};
     use crate::time::SystemClock;
 
     mod setup {
-        use std::any::Any;
         use std::error::Error;
         use std::fmt::{Display, Formatter};
         use std::sync::{Arc, Mutex};
@@ -251,10 +308,6 @@ mod waiting_list_tests {
                         .push(response.unwrap_err());
                 }
             }
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
@@ -267,7 +320,7 @@ mod waiting_list_tests {
         let callback = TestCallback::new();
 
         let message_id: MessageId = 10;
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &endpoint(), callback.clone());
         waiting_list.handle_response(message_id, Ok(Message::shutdown_type()));
 
         let message = callback.get_message_at(0).unwrap();
@@ -285,7 +338,7 @@ mod waiting_list_tests {
         let callback = TestCallback::new();
 
         let message_id: MessageId = 10;
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &endpoint(), callback.clone());
         waiting_list.handle_response(
             message_id,
             Err(Box::new(TestError {
@@ -310,7 +363,7 @@ mod waiting_list_tests {
         let message_id: MessageId = 10;
         let unknown_message_id: MessageId = 20;
 
-        waiting_list.add(message_id, callback.clone());
+        waiting_list.add(message_id, &endpoint(), callback.clone());
         waiting_list.handle_response(unknown_message_id, Ok(Message::shutdown_type()));
 
         let message = callback.get_message_at(0);
@@ -328,13 +381,77 @@ mod waiting_list_tests {
         let callback = TestCallback::new();
 
         let message_id: MessageId = 10;
-        waiting_list.add(message_id, callback);
+        waiting_list.add(message_id, &endpoint(), callback);
 
         thread::sleep(Duration::from_secs(1));
 
         assert!(waiting_list.pending_responses.is_empty());
         waiting_list.stop();
     }
+
+    #[test]
+    fn estimate_the_round_trip_time_of_a_peer_from_its_replies() {
+        let waiting_list = WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100))
+                .with_response_timeout_bounds(Duration::from_millis(40), Duration::from_secs(10)),
+            SystemClock::new(),
+        );
+        assert!(waiting_list
+            .round_trip_times()
+            .estimate_for(&endpoint())
+            .is_none());
+
+        waiting_list.add(10, &endpoint(), TestCallback::new());
+        assert_eq!(
+            Duration::from_secs(120),
+            waiting_list
+                .pending_responses
+                .get(&10)
+                .unwrap()
+                .expiry_after
+        );
+        thread::sleep(Duration::from_millis(20));
+        waiting_list.handle_response(10, Ok(Message::shutdown_type()));
+
+        let estimate = waiting_list
+            .round_trip_times()
+            .estimate_for(&endpoint())
+            .unwrap();
+        assert!(estimate.smoothed >= Duration::from_millis(20));
+
+        waiting_list.add(11, &endpoint(), TestCallback::new());
+        let expiry_after = waiting_list
+            .pending_responses
+            .get(&11)
+            .unwrap()
+            .expiry_after;
+        assert!(expiry_after >= Duration::from_millis(60));
+        assert!(expiry_after < Duration::from_secs(10));
+
+        waiting_list.stop();
+    }
+
+    #[test]
+    fn expire_a_pending_response_after_the_timeout_of_its_peer() {
+        let waiting_list = WaitingList::new(
+            WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(5))
+                .with_response_timeout_bounds(Duration::from_millis(50), Duration::from_secs(10)),
+            SystemClock::new(),
+        );
+        waiting_list.add(10, &endpoint(), TestCallback::new());
+        waiting_list.handle_response(10, Ok(Message::shutdown_type()));
+
+        waiting_list.add(11, &endpoint(), TestCallback::new());
+        thread::sleep(Duration::from_millis(300));
+
+        assert!(waiting_list.pending_responses.is_empty());
+        assert_eq!(1, waiting_list.round_trip_times().rpc_timeouts());
+        waiting_list.stop();
+    }
+
+    fn endpoint() -> Endpoint {
+        Endpoint::new("localhost".to_string(), 2379)
+    }
 }
 
 #[cfg(test)]
@@ -342,12 +459,12 @@ mod timed_callback_tests {
     use std::sync::Arc;
     use std::time::Duration;
 
+    use crate::net::endpoint::Endpoint;
     use crate::net::wait::timed_callback_tests::setup::{FutureClock, NothingCallback};
     use crate::net::wait::TimedCallback;
     use crate::time::{Clock, SystemClock};
 
     mod setup {
-        use std::any::Any;
         use std::ops::Add;
         use std::time::{Duration, SystemTime};
 
@@ -370,36 +487,36 @@ mod timed_callback_tests {
 
         impl Callback for NothingCallback {
             fn on_response(&self, _response: Result<Message, ResponseError>) {}
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
     #[test]
     fn has_expired() {
-        let timed_callback =
-            TimedCallback::new(Arc::new(NothingCallback), SystemClock::new().now());
+        let timed_callback = timed_callback(Duration::from_secs(2));
 
         let clock: Box<dyn Clock> = Box::new(FutureClock {
             duration_to_add: Duration::from_secs(5),
         });
 
-        assert!(timed_callback.has_expired(&clock, &Duration::from_secs(2)));
+        assert!(timed_callback.has_expired(clock.as_ref()));
     }
 
     #[test]
     fn has_not_expired() {
-        let timed_callback =
-            TimedCallback::new(Arc::new(NothingCallback), SystemClock::new().now());
+        let timed_callback = timed_callback(Duration::from_secs(2));
 
         let clock: Box<dyn Clock> = SystemClock::new();
 
-        assert_eq!(
-            false,
-            timed_callback.has_expired(&clock, &Duration::from_secs(2))
-        );
+        assert_eq!(false, timed_callback.has_expired(clock.as_ref()));
+    }
+
+    fn timed_callback(expiry_after: Duration) -> TimedCallback {
+        TimedCallback::new(
+            Arc::new(NothingCallback),
+            SystemClock::new().now(),
+            expiry_after,
+            Endpoint::new("localhost".to_string(), 2379),
+        )
     }
 }
 
@@ -411,7 +528,9 @@ mod expired_pending_responses_cleaner_tests {
 
     use dashmap::DashMap;
 
+    use crate::net::endpoint::Endpoint;
     use crate::net::message::MessageId;
+    use crate::net::rtt::RoundTripTimes;
     use crate::net::wait::expired_pending_responses_cleaner_tests::setup::{
         FutureClock, TimeoutErrorResponseCallback,
     };
@@ -419,7 +538,6 @@ mod expired_pending_responses_cleaner_tests {
     use crate::time::Clock;
 
     mod setup {
-        use std::any::Any;
         use std::ops::Add;
         use std::sync::Mutex;
         use std::time::{Duration, SystemTime};
@@ -452,10 +570,6 @@ mod expired_pending_responses_cleaner_tests {
                 let mut guard = self.failed_message_id.lock().unwrap();
                 *guard = timeout_error.message_id;
             }
-
-            fn as_any(&self) -> &dyn Any {
-                self
-            }
         }
     }
 
@@ -473,16 +587,28 @@ mod expired_pending_responses_cleaner_tests {
 
         pending_responses.insert(
             message_id,
-            TimedCallback::new(error_response_callback, SystemTime::now()),
+            TimedCallback::new(
+                error_response_callback,
+                SystemTime::now(),
+                Duration::from_secs(2),
+                Endpoint::new("localhost".to_string(), 2379),
+            ),
         );
 
+        let round_trip_times = Arc::new(RoundTripTimes::new(
+            Duration::from_secs(2),
+            Duration::from_millis(200),
+            Duration::from_secs(120),
+        ));
         let cleaner = ExpiredPendingResponsesCleaner::new(
             WaitingListOptions::new(Duration::from_secs(2), Duration::from_millis(0)),
             pending_responses.clone(),
+            round_trip_times.clone(),
             clock,
         );
         thread::sleep(Duration::from_millis(5));
         assert!(pending_responses.is_empty());
+        assert_eq!(1, round_trip_times.rpc_timeouts());
 
         cleaner.stop();
     }
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::net::node::Node;
+
+const IPV4_SUBNET_PREFIX_LENGTH: u32 = 24;
+const IPV6_SUBNET_PREFIX_LENGTH: u32 = 64;
+// Endpoints with a host name are not resolved in the table, they share the unspecified address
+// and count against the same ip and subnet.
+const UNRESOLVED_HOST: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+#[derive(Copy, Clone)]
+pub(crate) struct DiversityLimits {
+    pub(crate) per_ip_in_bucket: usize,
+    pub(crate) per_subnet_in_bucket: usize,
+    pub(crate) per_ip_in_table: usize,
+    pub(crate) per_subnet_in_table: usize,
+}
+
+impl DiversityLimits {
+    pub(crate) fn new(
+        per_ip_in_bucket: usize,
+        per_subnet_in_bucket: usize,
+        per_ip_in_table: usize,
+        per_subnet_in_table: usize,
+    ) -> Self {
+        DiversityLimits {
+            per_ip_in_bucket,
+            per_subnet_in_bucket,
+            per_ip_in_table,
+            per_subnet_in_table,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn unlimited() -> Self {
+        DiversityLimits::new(usize::MAX, usize::MAX, usize::MAX, usize::MAX)
+    }
+}
+
+impl Default for DiversityLimits {
+    fn default() -> Self {
+        DiversityLimits::new(2, 2, 5, 10)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct DiversityCounts {
+    by_ip: HashMap<IpAddr, usize>,
+    by_subnet: HashMap<IpAddr, usize>,
+}
+
+impl DiversityCounts {
+    pub(crate) fn admits(
+        &self,
+        node: &Node,
+        bucket: &[Node],
+        excluding: Option<&Node>,
+        limits: &DiversityLimits,
+    ) -> bool {
+        let ip = ip_of(node);
+        let subnet = subnet_of(ip);
+        let excluded_ip = excluding.map(ip_of);
+        let excluded_subnet = excluded_ip.map(subnet_of);
+
+        let ips_in_bucket = bucket
+            .iter()
+            .filter(|existing_node| ip_of(existing_node) == ip)
+            .count()
+            .saturating_sub(usize::from(excluded_ip == Some(ip)));
+        let subnets_in_bucket = bucket
+            .iter()
+            .filter(|existing_node| subnet_of(ip_of(existing_node)) == subnet)
+            .count()
+            .saturating_sub(usize::from(excluded_subnet == Some(subnet)));
+        let ips_in_table = self
+            .by_ip
+            .get(&ip)
+            .copied()
+            .unwrap_or(0)
+            .saturating_sub(usize::from(excluded_ip == Some(ip)));
+        let subnets_in_table = self
+            .by_subnet
+            .get(&subnet)
+            .copied()
+            .unwrap_or(0)
+            .saturating_sub(usize::from(excluded_subnet == Some(subnet)));
+
+        ips_in_bucket < limits.per_ip_in_bucket
+            && subnets_in_bucket < limits.per_subnet_in_bucket
+            && ips_in_table < limits.per_ip_in_table
+            && subnets_in_table < limits.per_subnet_in_table
+    }
+
+    pub(crate) fn add(&mut self, node: &Node) {
+        let ip = ip_of(node);
+        *self.by_ip.entry(ip).or_insert(0) += 1;
+        *self.by_subnet.entry(subnet_of(ip)).or_insert(0) += 1;
+    }
+
+    pub(crate) fn remove(&mut self, node: &Node) {
+        let ip = ip_of(node);
+        Self::decrement(&mut self.by_ip, ip);
+        Self::decrement(&mut self.by_subnet, subnet_of(ip));
+    }
+
+    fn decrement(counts: &mut HashMap<IpAddr, usize>, key: IpAddr) {
+        if let Some(count) = counts.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&key);
+            }
+        }
+    }
+}
+
+fn ip_of(node: &Node) -> IpAddr {
+    node.endpoint.ip().unwrap_or(UNRESOLVED_HOST)
+}
+
+pub(crate) fn subnet_of(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX << (32 - IPV4_SUBNET_PREFIX_LENGTH);
+            IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX << (128 - IPV6_SUBNET_PREFIX_LENGTH);
+            IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::node::Node;
+    use crate::routing::diversity::{subnet_of, DiversityCounts, DiversityLimits};
+
+    fn node_at(host: &str, id: u16) -> Node {
+        Node::new_with_id(
+            Endpoint::new(host.to_string(), 2379),
+            Id::new(id.to_be_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn subnet_of_an_ipv4_address() {
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::new(10, 1, 2, 0)),
+            subnet_of(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+        );
+    }
+
+    #[test]
+    fn subnet_of_an_ipv6_address() {
+        assert_eq!(
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 0, 0, 0, 0)),
+            subnet_of(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 3, 4, 5, 6)))
+        );
+    }
+
+    #[test]
+    fn limit_nodes_without_an_ip_as_a_single_ip() {
+        let mut counts = DiversityCounts::default();
+        counts.add(&node_at("localhost", 1));
+        let bucket = vec![node_at("localhost", 1)];
+        let limits = DiversityLimits::new(1, 1, 5, 10);
+
+        assert!(!counts.admits(&node_at("localhost", 2), &bucket, None, &limits));
+        assert!(!counts.admits(&node_at("kademlia.example", 2), &bucket, None, &limits));
+        assert!(counts.admits(&node_at("10.1.2.3", 2), &bucket, None, &limits));
+    }
+
+    #[test]
+    fn do_not_admit_a_node_over_the_subnet_limit_in_a_bucket() {
+        let counts = DiversityCounts::default();
+        let bucket = vec![node_at("10.1.2.3", 1), node_at("10.1.2.4", 2)];
+        let limits = DiversityLimits::new(2, 2, 5, 10);
+
+        assert!(!counts.admits(&node_at("10.1.2.5", 3), &bucket, None, &limits));
+        assert!(counts.admits(&node_at("10.1.3.5", 3), &bucket, None, &limits));
+    }
+
+    #[test]
+    fn do_not_admit_a_node_over_the_ip_limit_in_the_table() {
+        let mut counts = DiversityCounts::default();
+        counts.add(&node_at("10.1.2.3", 1));
+        let limits = DiversityLimits::new(2, 2, 1, 10);
+
+        assert!(!counts.admits(&node_at("10.1.2.3", 2), &[], None, &limits));
+
+        counts.remove(&node_at("10.1.2.3", 1));
+        assert!(counts.admits(&node_at("10.1.2.3", 2), &[], None, &limits));
+    }
+
+    #[test]
+    fn admit_a_node_replacing_one_from_the_same_subnet() {
+        let mut counts = DiversityCounts::default();
+        let to_remove = node_at("10.1.2.3", 1);
+        counts.add(&to_remove);
+        let bucket = vec![to_remove.clone()];
+        let limits = DiversityLimits::new(1, 1, 1, 1);
+
+        assert!(counts.admits(&node_at("10.1.2.4", 2), &bucket, Some(&to_remove), &limits));
+    }
+}
@@ -0,0 +1,21 @@
+pub(crate) const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoutingEvent {
+    NodeAdded {
+        bucket_index: usize,
+        node_id: Vec<u8>,
+    },
+    NodeRemoved {
+        bucket_index: usize,
+        node_id: Vec<u8>,
+    },
+    NodeReplaced {
+        bucket_index: usize,
+        removed_node_id: Vec<u8>,
+        added_node_id: Vec<u8>,
+    },
+    BucketRefreshed {
+        bucket_index: usize,
+    },
+}
@@ -0,0 +1,61 @@
+use std::time::SystemTime;
+
+pub(crate) const DEFAULT_STALE_AFTER_FAILURES: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Liveness {
+    pub(crate) last_seen: SystemTime,
+    pub(crate) consecutive_failures: u32,
+}
+
+impl Liveness {
+    pub(crate) fn new(last_seen: SystemTime) -> Self {
+        Liveness {
+            last_seen,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub(crate) fn mark_seen(&mut self, now: SystemTime) {
+        self.last_seen = now;
+        self.consecutive_failures = 0;
+    }
+
+    pub(crate) fn mark_failed(&mut self) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+
+    pub(crate) fn is_stale(&self, stale_after_failures: u32) -> bool {
+        self.consecutive_failures >= stale_after_failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::routing::liveness::Liveness;
+
+    #[test]
+    fn mark_a_contact_stale_after_consecutive_failures() {
+        let mut liveness = Liveness::new(SystemTime::UNIX_EPOCH);
+        liveness.mark_failed();
+        assert!(!liveness.is_stale(2));
+
+        liveness.mark_failed();
+        assert!(liveness.is_stale(2));
+    }
+
+    #[test]
+    fn reset_failures_when_a_contact_is_seen() {
+        let mut liveness = Liveness::new(SystemTime::UNIX_EPOCH);
+        liveness.mark_failed();
+        liveness.mark_failed();
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        liveness.mark_seen(now);
+        assert_eq!(0, liveness.consecutive_failures);
+        assert_eq!(now, liveness.last_seen);
+        assert!(!liveness.is_stale(2));
+    }
+}
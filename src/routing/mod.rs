@@ -1,70 +1,406 @@
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 
 use log::info;
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
 
 use crate::id::Id;
 use crate::net::node::{Node, NodeId};
+use crate::net::rtt::RoundTripTimes;
+use crate::routing::diversity::{DiversityCounts, DiversityLimits};
+use crate::routing::event::{RoutingEvent, EVENT_CHANNEL_CAPACITY};
+use crate::routing::liveness::Liveness;
 use crate::routing::neighbors::ClosestNeighbors;
+use crate::routing::proximity::ProximityPolicy;
+use crate::routing::tree::Tree;
+use crate::time::Clock;
 
+pub(crate) mod diversity;
+pub(crate) mod event;
+pub(crate) mod liveness;
 mod neighbors;
+pub(crate) mod persist;
+pub(crate) mod proximity;
+mod tree;
+
+pub(crate) const MAX_BUCKET_CAPACITY: usize = 10;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingLayout {
+    #[default]
+    Fixed,
+    Tree,
+}
 
-const MAX_BUCKET_CAPACITY: usize = 10;
+#[derive(Copy, Clone)]
+pub(crate) struct TableOptions {
+    pub(crate) bucket_capacity: usize,
+    pub(crate) diversity_limits: DiversityLimits,
+    pub(crate) layout: RoutingLayout,
+    pub(crate) stale_after_failures: u32,
+    pub(crate) proximity_policy: ProximityPolicy,
+}
+
+impl TableOptions {
+    pub(crate) fn new(
+        bucket_capacity: usize,
+        diversity_limits: DiversityLimits,
+        layout: RoutingLayout,
+        stale_after_failures: u32,
+        proximity_policy: ProximityPolicy,
+    ) -> Self {
+        TableOptions {
+            bucket_capacity,
+            diversity_limits,
+            layout,
+            stale_after_failures,
+            proximity_policy,
+        }
+    }
+}
+
+enum Buckets {
+    Fixed(Vec<RwLock<Vec<Node>>>),
+    Tree(RwLock<Tree>),
+}
 
 pub(crate) struct Table {
-    buckets: Vec<RwLock<Vec<Node>>>,
+    buckets: Buckets,
     node_id: NodeId,
     max_bucket_capacity: usize,
+    diversity_limits: DiversityLimits,
+    diversity_counts: Mutex<DiversityCounts>,
+    stale_after_failures: u32,
+    liveness: Mutex<HashMap<NodeId, Liveness>>,
+    replacements: Mutex<Vec<(Node, Liveness)>>,
+    proximity_policy: ProximityPolicy,
+    round_trip_times: Arc<RoundTripTimes>,
+    clock: Box<dyn Clock>,
+    events: Sender<RoutingEvent>,
 }
 
 impl Table {
+    #[cfg(test)]
     pub(crate) fn new(node_id: NodeId) -> Arc<Self> {
         Self::new_with_bucket_capacity(node_id, MAX_BUCKET_CAPACITY)
     }
 
+    #[cfg(test)]
     pub(crate) fn new_with_bucket_capacity(node_id: NodeId, bucket_capacity: usize) -> Arc<Self> {
-        let mut buckets = Vec::with_capacity(node_id.id_length_in_bits);
-        (0..node_id.id_length_in_bits).for_each(|_| buckets.push(RwLock::new(Vec::new())));
+        Self::new_with_diversity_limits(node_id, bucket_capacity, DiversityLimits::default())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_with_diversity_limits(
+        node_id: NodeId,
+        bucket_capacity: usize,
+        diversity_limits: DiversityLimits,
+    ) -> Arc<Self> {
+        Self::new_with_layout(
+            node_id,
+            bucket_capacity,
+            diversity_limits,
+            RoutingLayout::Fixed,
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_with_layout(
+        node_id: NodeId,
+        bucket_capacity: usize,
+        diversity_limits: DiversityLimits,
+        layout: RoutingLayout,
+    ) -> Arc<Self> {
+        Self::new_with_options(
+            node_id,
+            TableOptions::new(
+                bucket_capacity,
+                diversity_limits,
+                layout,
+                liveness::DEFAULT_STALE_AFTER_FAILURES,
+                ProximityPolicy::default(),
+            ),
+            RoundTripTimes::with_default_timeouts(),
+            crate::time::SystemClock::new(),
+        )
+    }
+
+    // The round-trip times are the estimates the network keeps from the replies, they rank
+    // the replacements of a stale contact.
+    pub(crate) fn new_with_options(
+        node_id: NodeId,
+        options: TableOptions,
+        round_trip_times: Arc<RoundTripTimes>,
+        clock: Box<dyn Clock>,
+    ) -> Arc<Self> {
+        let buckets = match options.layout {
+            RoutingLayout::Fixed => {
+                let mut buckets = Vec::with_capacity(node_id.id_length_in_bits);
+                (0..node_id.id_length_in_bits).for_each(|_| buckets.push(RwLock::new(Vec::new())));
+                Buckets::Fixed(buckets)
+            }
+            RoutingLayout::Tree => Buckets::Tree(RwLock::new(Tree::new(node_id.clone()))),
+        };
 
         Arc::new(Table {
             buckets,
             node_id,
-            max_bucket_capacity: bucket_capacity,
+            max_bucket_capacity: options.bucket_capacity,
+            diversity_limits: options.diversity_limits,
+            diversity_counts: Mutex::new(DiversityCounts::default()),
+            stale_after_failures: options.stale_after_failures,
+            liveness: Mutex::new(HashMap::new()),
+            replacements: Mutex::new(Vec::new()),
+            proximity_policy: options.proximity_policy,
+            round_trip_times,
+            clock,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
         })
     }
 
     pub(crate) fn add(&self, node: Node) -> (usize, bool) {
-        let (bucket_index, contains) = self.contains(&node);
-        if !contains {
-            let nodes = &mut self.buckets[bucket_index].write().unwrap();
-            return self.add_internal(node, bucket_index, nodes);
+        let node_id = node.id.clone();
+        let (bucket_index, added) = match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                let (bucket_index, contains) = self.contains(&node);
+                if contains {
+                    return (bucket_index, false);
+                }
+                let nodes = &mut buckets[bucket_index].write().unwrap();
+                let (bucket_index, added) = self.add_internal(node.clone(), bucket_index, nodes);
+                if !added && nodes.len() >= self.max_bucket_capacity {
+                    self.cache_replacement(node, bucket_index, |id| self.bucket_index(id));
+                }
+                (bucket_index, added)
+            }
+            Buckets::Tree(tree) => {
+                let mut tree = tree.write().unwrap();
+                let bucket_index = tree.bucket_index(&node.id);
+                if tree.bucket_mut(bucket_index).contains(&node) {
+                    return (bucket_index, false);
+                }
+                let bucket_index = tree.split_to_fit(&node.id, self.max_bucket_capacity);
+                let (bucket_index, added) =
+                    self.add_internal(node.clone(), bucket_index, tree.bucket_mut(bucket_index));
+                if !added && tree.bucket_mut(bucket_index).len() >= self.max_bucket_capacity {
+                    self.cache_replacement(node, bucket_index, |id| tree.bucket_index(id));
+                }
+                (bucket_index, added)
+            }
+        };
+        if added {
+            self.emit(RoutingEvent::NodeAdded {
+                bucket_index,
+                node_id: node_id.as_bytes().to_vec(),
+            });
         }
-        return (bucket_index, false);
+        (bucket_index, added)
     }
 
     pub(crate) fn remove_and_add(&self, bucket_index: usize, to_remove: &Node, to_add: Node) {
-        assert!(bucket_index < self.node_id.id_length_in_bits);
-        assert_eq!(
-            self.bucket_index(&to_remove.id),
-            self.bucket_index(&to_add.id)
-        );
-        if self.contains(to_remove).1 && !self.contains(&to_add).1 {
-            let mut nodes = &mut self.buckets[bucket_index].write().unwrap();
-            Self::remove_internal(to_remove, bucket_index, &mut nodes);
-            self.add_internal(to_add, bucket_index, &mut nodes);
+        match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                assert!(bucket_index < self.node_id.id_length_in_bits);
+                assert_eq!(
+                    self.bucket_index(&to_remove.id),
+                    self.bucket_index(&to_add.id)
+                );
+                if self.contains(to_remove).1 && !self.contains(&to_add).1 {
+                    let nodes = &mut buckets[bucket_index].write().unwrap();
+                    self.replace(bucket_index, to_remove, to_add, nodes);
+                }
+            }
+            Buckets::Tree(tree) => {
+                let mut tree = tree.write().unwrap();
+                let bucket_index = tree.bucket_index(&to_remove.id);
+                if bucket_index != tree.bucket_index(&to_add.id) {
+                    info!(
+                        "not replacing node with id {:?} by {:?} which now belong to different buckets",
+                        to_remove.id, to_add.id
+                    );
+                    return;
+                }
+                let nodes = tree.bucket_mut(bucket_index);
+                if nodes.contains(to_remove) && !nodes.contains(&to_add) {
+                    self.replace(bucket_index, to_remove, to_add, nodes);
+                }
+            }
         }
     }
 
+    pub(crate) fn mark_seen(&self, node: &Node) {
+        if let Some(liveness) = self.liveness.lock().unwrap().get_mut(&node.id) {
+            liveness.mark_seen(self.clock.now());
+            return;
+        }
+        if let Some((_, liveness)) = self
+            .replacements
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|(replacement, _)| replacement.id == node.id)
+        {
+            liveness.mark_seen(self.clock.now());
+        }
+    }
+
+    // A stale contact stays in its bucket until a replacement for that bucket is known, so a
+    // lossy link does not empty the table.
+    pub(crate) fn mark_failed(&self, node: &Node) -> bool {
+        let is_stale = match self.liveness.lock().unwrap().get_mut(&node.id) {
+            Some(liveness) => {
+                liveness.mark_failed();
+                liveness.is_stale(self.stale_after_failures)
+            }
+            None => return false,
+        };
+        if !is_stale {
+            return false;
+        }
+
+        // Among the replacements of the bucket the proximity policy picks the fastest one,
+        // and the most recently cached one otherwise.
+        let bucket_index = self.bucket_index_of(&node.id);
+        let replacements = self.replacements.lock().unwrap().clone();
+        let replacement = replacements
+            .into_iter()
+            .rev()
+            .filter(|(replacement, _)| self.bucket_index_of(&replacement.id) == bucket_index)
+            .min_by_key(|(replacement, _)| {
+                self.proximity_policy.preference_key(
+                    self.round_trip_times
+                        .estimate_for(&replacement.endpoint)
+                        .map(|estimate| estimate.smoothed),
+                )
+            })
+            .map(|(replacement, _)| replacement);
+        match replacement {
+            Some(replacement) => {
+                info!(
+                    "evicting stale node with id {:?} for the replacement {:?}",
+                    node.id, replacement.id
+                );
+                let replacement_id = replacement.id.clone();
+                self.remove_and_add(bucket_index, node, replacement);
+                self.replacements
+                    .lock()
+                    .unwrap()
+                    .retain(|(cached, _)| cached.id != replacement_id);
+                !self.contains(node).1
+            }
+            None => {
+                info!(
+                    "keeping stale node with id {:?}, no replacement is known for its bucket",
+                    node.id
+                );
+                false
+            }
+        }
+    }
+
+    pub(crate) fn stale_after_failures(&self) -> u32 {
+        self.stale_after_failures
+    }
+
+    pub(crate) fn liveness_of(&self, node_id: &NodeId) -> Option<Liveness> {
+        self.liveness.lock().unwrap().get(node_id).cloned()
+    }
+
+    pub(crate) fn least_recently_seen(&self) -> Vec<(Node, Liveness)> {
+        let buckets = self.buckets();
+        let liveness = self.liveness.lock().unwrap();
+        buckets
+            .into_iter()
+            .filter_map(|(_, nodes)| {
+                nodes
+                    .into_iter()
+                    .filter_map(|node| {
+                        let node_liveness = liveness.get(&node.id)?.clone();
+                        Some((node, node_liveness))
+                    })
+                    .min_by_key(|(_, node_liveness)| node_liveness.last_seen)
+            })
+            .collect()
+    }
+
+    pub(crate) fn replacements(&self) -> Vec<Node> {
+        self.replacements
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(replacement, _)| replacement.clone())
+            .collect()
+    }
+
+    pub(crate) fn proximity_policy(&self) -> ProximityPolicy {
+        self.proximity_policy
+    }
+
     pub(crate) fn contains(&self, node: &Node) -> (usize, bool) {
-        let bucket_index = self.bucket_index(&node.id);
-        let nodes = self.buckets[bucket_index].read().unwrap();
+        match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                let bucket_index = self.bucket_index(&node.id);
+                let nodes = buckets[bucket_index].read().unwrap();
 
-        (bucket_index, nodes.contains(node))
+                (bucket_index, nodes.contains(node))
+            }
+            Buckets::Tree(tree) => {
+                let tree = tree.read().unwrap();
+                let bucket_index = tree.bucket_index(&node.id);
+
+                (
+                    bucket_index,
+                    tree.bucket(bucket_index).unwrap().contains(node),
+                )
+            }
+        }
+    }
+
+    pub(crate) fn all_nodes(&self) -> Vec<Node> {
+        self.buckets()
+            .into_iter()
+            .flat_map(|(_, nodes)| nodes)
+            .collect()
+    }
+
+    pub(crate) fn buckets(&self) -> Vec<(usize, Vec<Node>)> {
+        let buckets: Vec<Vec<Node>> = match &self.buckets {
+            Buckets::Fixed(buckets) => buckets
+                .iter()
+                .map(|bucket| bucket.read().unwrap().clone())
+                .collect(),
+            Buckets::Tree(tree) => tree.read().unwrap().buckets().cloned().collect(),
+        };
+        buckets
+            .into_iter()
+            .enumerate()
+            .filter(|(_, nodes)| !nodes.is_empty())
+            .collect()
     }
 
     pub(crate) fn first_node_in(&self, bucket_index: usize) -> Option<Node> {
-        assert!(bucket_index < self.node_id.id_length_in_bits);
-        let nodes = self.buckets[bucket_index].read().unwrap();
-        nodes.get(0).map(|node| node.clone())
+        match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                assert!(bucket_index < self.node_id.id_length_in_bits);
+                let nodes = buckets[bucket_index].read().unwrap();
+                nodes.first().cloned()
+            }
+            Buckets::Tree(tree) => tree
+                .read()
+                .unwrap()
+                .bucket(bucket_index)
+                .and_then(|nodes| nodes.first().cloned()),
+        }
+    }
+
+    pub(crate) fn random_id_in(&self, bucket_index: usize) -> Option<Id> {
+        match &self.buckets {
+            Buckets::Fixed(_) => (bucket_index < self.node_id.id_length_in_bits)
+                .then(|| self.node_id.random_in_bucket(bucket_index)),
+            Buckets::Tree(tree) => tree.read().unwrap().random_id_in(bucket_index),
+        }
     }
 
     pub(crate) fn closest_neighbors(
@@ -72,14 +408,27 @@ impl Table {
         id: &Id,
         number_of_neighbors: usize,
     ) -> ClosestNeighbors {
-        let bucket_index = self.node_id.differing_bit_position(id);
         let mut closest_neighbors = ClosestNeighbors::new(number_of_neighbors, id.clone());
 
-        for bucket_index in self.all_adjacent_bucket_indices(bucket_index) {
-            let nodes = self.buckets[bucket_index].read().unwrap();
-            if !nodes.is_empty() {
-                if !closest_neighbors.add_missing(&nodes) {
-                    break;
+        match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                let bucket_index = self.node_id.differing_bit_position(id);
+                for bucket_indices in self.bucket_indices_by_distance(bucket_index) {
+                    for bucket_index in bucket_indices {
+                        closest_neighbors.add_missing(&buckets[bucket_index].read().unwrap());
+                    }
+                    if closest_neighbors.is_full() {
+                        break;
+                    }
+                }
+            }
+            Buckets::Tree(tree) => {
+                let tree = tree.read().unwrap();
+                for bucket_index in tree.bucket_indices_by_distance(id) {
+                    closest_neighbors.add_missing(tree.bucket(bucket_index).unwrap());
+                    if closest_neighbors.is_full() {
+                        break;
+                    }
                 }
             }
         }
@@ -92,37 +441,110 @@ impl Table {
         return closest_neighbors;
     }
 
+    fn replace(&self, bucket_index: usize, to_remove: &Node, to_add: Node, nodes: &mut Vec<Node>) {
+        if !self.diversity_counts.lock().unwrap().admits(
+            &to_add,
+            nodes,
+            Some(to_remove),
+            &self.diversity_limits,
+        ) {
+            info!(
+                "not replacing node with id {:?} by {:?} which exceeds the ip diversity limits",
+                to_remove.id, to_add.id
+            );
+            return;
+        }
+        let added_node_id = to_add.id.as_bytes().to_vec();
+        if self.remove_internal(to_remove, bucket_index, nodes)
+            && self.add_internal(to_add, bucket_index, nodes).1
+        {
+            self.emit(RoutingEvent::NodeReplaced {
+                bucket_index,
+                removed_node_id: to_remove.id.as_bytes().to_vec(),
+                added_node_id,
+            });
+        }
+    }
+
     fn add_internal(
         &self,
         node: Node,
         bucket_index: usize,
-        nodes: &mut RwLockWriteGuard<Vec<Node>>,
+        nodes: &mut Vec<Node>,
     ) -> (usize, bool) {
         if nodes.len() < self.max_bucket_capacity {
+            let mut diversity_counts = self.diversity_counts.lock().unwrap();
+            if !diversity_counts.admits(&node, nodes, None, &self.diversity_limits) {
+                info!(
+                    "not adding node with id {:?} which exceeds the ip diversity limits",
+                    node.id
+                );
+                return (bucket_index, false);
+            }
             info!(
                 "adding node with id {:?} to the bucket with index {}",
                 node.id, bucket_index
             );
+            diversity_counts.add(&node);
+            self.replacements
+                .lock()
+                .unwrap()
+                .retain(|(replacement, _)| replacement.id != node.id);
+            self.liveness
+                .lock()
+                .unwrap()
+                .insert(node.id.clone(), Liveness::new(self.clock.now()));
             nodes.push(node);
-            return (bucket_index, true);
+            (bucket_index, true)
+        } else {
+            (bucket_index, false)
         }
-        return (bucket_index, false);
     }
 
-    fn remove(&self, node: &Node) -> bool {
-        let (bucket_index, contains) = self.contains(node);
-        if contains {
-            let mut nodes = self.buckets[bucket_index].write().unwrap();
-            return Self::remove_internal(node, bucket_index, &mut nodes);
+    pub(crate) fn remove(&self, node: &Node) -> bool {
+        let (bucket_index, removed) = match &self.buckets {
+            Buckets::Fixed(buckets) => {
+                let (bucket_index, contains) = self.contains(node);
+                if !contains {
+                    return false;
+                }
+                let mut nodes = buckets[bucket_index].write().unwrap();
+                (
+                    bucket_index,
+                    self.remove_internal(node, bucket_index, &mut nodes),
+                )
+            }
+            Buckets::Tree(tree) => {
+                let mut tree = tree.write().unwrap();
+                let bucket_index = tree.bucket_index(&node.id);
+                (
+                    bucket_index,
+                    self.remove_internal(node, bucket_index, tree.bucket_mut(bucket_index)),
+                )
+            }
+        };
+        if removed {
+            self.emit(RoutingEvent::NodeRemoved {
+                bucket_index,
+                node_id: node.id.as_bytes().to_vec(),
+            });
         }
-        return false;
+        removed
     }
 
-    fn remove_internal(
-        node: &Node,
-        bucket_index: usize,
-        nodes: &mut RwLockWriteGuard<Vec<Node>>,
-    ) -> bool {
+    pub(crate) fn subscribe(&self) -> Receiver<RoutingEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn record_refresh(&self, bucket_index: usize) {
+        self.emit(RoutingEvent::BucketRefreshed { bucket_index });
+    }
+
+    fn emit(&self, event: RoutingEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn remove_internal(&self, node: &Node, bucket_index: usize, nodes: &mut Vec<Node>) -> bool {
         let node_index = nodes
             .iter()
             .position(|existing_node| existing_node.eq(node));
@@ -132,31 +554,62 @@ impl Table {
                 "removing node with id {:?} from the bucket with index {}",
                 node.id, bucket_index
             );
-            nodes.remove(index);
-            return true;
+            let removed = nodes.remove(index);
+            self.diversity_counts.lock().unwrap().remove(&removed);
+            self.liveness.lock().unwrap().remove(&removed.id);
+            true
+        } else {
+            false
         }
-        return false;
     }
 
-    //TODO: confirm this from the paper
-    fn all_adjacent_bucket_indices(&self, bucket_index: usize) -> Vec<usize> {
-        let mut low_bucket_index: isize = bucket_index as isize - 1;
-        let mut high_bucket_index: usize = bucket_index + 1;
+    fn cache_replacement(
+        &self,
+        node: Node,
+        bucket_index: usize,
+        bucket_index_of: impl Fn(&NodeId) -> usize,
+    ) {
+        let mut replacements = self.replacements.lock().unwrap();
+        let liveness = match replacements
+            .iter()
+            .position(|(replacement, _)| replacement.id == node.id)
+        {
+            Some(position) => replacements.remove(position).1,
+            None => Liveness::new(self.clock.now()),
+        };
+        info!(
+            "caching node with id {:?} as a replacement for the bucket with index {}",
+            node.id, bucket_index
+        );
+        replacements.push((node, liveness));
 
-        let mut adjacent_indices = Vec::new();
-        adjacent_indices.push(bucket_index);
+        let in_bucket: Vec<usize> = replacements
+            .iter()
+            .enumerate()
+            .filter(|(_, (replacement, _))| bucket_index_of(&replacement.id) == bucket_index)
+            .map(|(position, _)| position)
+            .collect();
+        if in_bucket.len() > self.max_bucket_capacity {
+            replacements.remove(in_bucket[0]);
+        }
+    }
 
-        while adjacent_indices.len() < self.node_id.id_length_in_bits {
-            if high_bucket_index < self.node_id.id_length_in_bits {
-                adjacent_indices.push(high_bucket_index);
-            }
-            if low_bucket_index >= 0 {
-                adjacent_indices.push(low_bucket_index as usize);
-            }
-            high_bucket_index += 1;
-            low_bucket_index -= 1;
+    fn bucket_index_of(&self, node_id: &NodeId) -> usize {
+        match &self.buckets {
+            Buckets::Fixed(_) => self.bucket_index(node_id),
+            Buckets::Tree(tree) => tree.read().unwrap().bucket_index(node_id),
         }
-        return adjacent_indices;
+    }
+
+    // Nodes in the target's bucket share its longest prefix, nodes in all the lower buckets
+    // differ from it first at that bucket's bit, and each higher bucket is farther than the last.
+    fn bucket_indices_by_distance(&self, bucket_index: usize) -> Vec<Vec<usize>> {
+        let mut bucket_indices = vec![vec![bucket_index], (0..bucket_index).collect()];
+        bucket_indices.extend(
+            (bucket_index + 1..self.node_id.id_length_in_bits)
+                .map(|bucket_index| vec![bucket_index]),
+        );
+        bucket_indices
     }
 
     fn bucket_index(&self, node_id: &NodeId) -> usize {
@@ -169,10 +622,20 @@ impl Table {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use proptest::prelude::*;
+
     use crate::id::Id;
     use crate::net::endpoint::Endpoint;
+    use crate::net::message::MessageTypes;
     use crate::net::node::Node;
-    use crate::routing::Table;
+    use crate::net::rtt::RoundTripTimes;
+    use crate::routing::diversity::DiversityLimits;
+    use crate::routing::event::RoutingEvent;
+    use crate::routing::proximity::ProximityPolicy;
+    use crate::routing::{RoutingLayout, Table, TableOptions};
+    use crate::time::SystemClock;
 
     #[test]
     fn add_a_node_to_routing_table() {
@@ -213,6 +676,68 @@ mod tests {
         assert_eq!(false, added);
     }
 
+    #[test]
+    fn do_not_add_a_node_to_routing_table_over_the_subnet_limit_in_a_bucket() {
+        let routing_table = Table::new_with_diversity_limits(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            10,
+            DiversityLimits::new(2, 2, 5, 10),
+        );
+        for (host, id) in [("10.1.2.3", 247u16), ("10.1.2.4", 246u16)] {
+            let (_, added) = routing_table.add(Node::new_with_id(
+                Endpoint::new(host.to_string(), 2379),
+                Id::new(id.to_be_bytes().to_vec()),
+            ));
+            assert!(added);
+        }
+
+        let (_, added) = routing_table.add(Node::new_with_id(
+            Endpoint::new("10.1.2.5".to_string(), 2379),
+            Id::new(245u16.to_be_bytes().to_vec()),
+        ));
+        assert!(!added);
+
+        let (_, added) = routing_table.add(Node::new_with_id(
+            Endpoint::new("10.1.3.5".to_string(), 2379),
+            Id::new(245u16.to_be_bytes().to_vec()),
+        ));
+        assert!(added);
+    }
+
+    #[test]
+    fn do_not_add_a_node_to_routing_table_over_the_ip_limit_across_buckets() {
+        let routing_table = Table::new_with_diversity_limits(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            10,
+            DiversityLimits::new(2, 2, 1, 10),
+        );
+        let (_, added) = routing_table.add(Node::new_with_id(
+            Endpoint::new("10.1.2.3".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+        assert!(added);
+
+        let (_, added) = routing_table.add(Node::new_with_id(
+            Endpoint::new("10.1.2.3".to_string(), 2380),
+            Id::new(511u16.to_be_bytes().to_vec()),
+        ));
+        assert!(!added);
+    }
+
+    #[test]
+    fn add_nodes_from_the_same_ip_without_diversity_limits() {
+        let routing_table = Table::new_with_diversity_limits(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            10,
+            DiversityLimits::unlimited(),
+        );
+        for port in [2379, 2380, 2381] {
+            let (_, added) =
+                routing_table.add(Node::new(Endpoint::new("10.1.2.3".to_string(), port)));
+            assert!(added);
+        }
+    }
+
     #[test]
     fn remove_an_existing_node() {
         let id: u16 = 255;
@@ -296,6 +821,38 @@ mod tests {
         assert!(contains);
     }
 
+    #[test]
+    fn do_not_replace_a_node_by_one_over_the_subnet_limit() {
+        let routing_table = Table::new_with_diversity_limits(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            10,
+            DiversityLimits::new(1, 1, 5, 10),
+        );
+        let node = Node::new_with_id(
+            Endpoint::new("10.1.2.3".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        );
+        let other_node = Node::new_with_id(
+            Endpoint::new("10.1.3.3".to_string(), 2379),
+            Id::new(246u16.to_be_bytes().to_vec()),
+        );
+        routing_table.add(node.clone());
+        let (bucket_index, added) = routing_table.add(other_node.clone());
+        assert!(added);
+
+        let to_add = Node::new_with_id(
+            Endpoint::new("10.1.3.4".to_string(), 2379),
+            Id::new(245u16.to_be_bytes().to_vec()),
+        );
+        routing_table.remove_and_add(bucket_index, &node, to_add.clone());
+
+        let (_, contains) = routing_table.contains(&to_add);
+        assert!(!contains);
+
+        let (_, contains) = routing_table.contains(&node);
+        assert!(contains);
+    }
+
     #[test]
     #[should_panic]
     fn remove_and_add_a_node_with_different_bucket_index() {
@@ -340,6 +897,42 @@ mod tests {
         assert_eq!(false, contains);
     }
 
+    #[test]
+    fn all_nodes_across_buckets() {
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2380),
+            Id::new(511u16.to_be_bytes().to_vec()),
+        ));
+
+        let mut addresses: Vec<String> = routing_table
+            .all_nodes()
+            .iter()
+            .map(|node| node.endpoint.address())
+            .collect();
+        addresses.sort();
+
+        assert_eq!(vec!["localhost:2379", "localhost:2380"], addresses);
+    }
+
+    #[test]
+    fn non_empty_buckets() {
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+
+        let buckets = routing_table.buckets();
+        assert_eq!(1, buckets.len());
+        assert_eq!(3, buckets[0].0);
+        assert_eq!("localhost:2379", buckets[0].1[0].endpoint.address());
+    }
+
     #[test]
     fn first_node() {
         let id: u16 = 511;
@@ -458,8 +1051,394 @@ mod tests {
         let closest_neighbors =
             routing_table.closest_neighbors(&Id::new(255u16.to_be_bytes().to_vec()), 1);
         assert_eq!(
-            &Id::new(509u16.to_be_bytes().to_vec()),
+            &Id::new(511u16.to_be_bytes().to_vec()),
             closest_neighbors.node_ids.iter().next().unwrap()
         );
     }
+
+    fn tree_table(id: u16, bucket_capacity: usize) -> std::sync::Arc<Table> {
+        Table::new_with_layout(
+            Id::new(id.to_be_bytes().to_vec()),
+            bucket_capacity,
+            DiversityLimits::unlimited(),
+            RoutingLayout::Tree,
+        )
+    }
+
+    fn node_with_id(port: u16, id: u16) -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), port),
+            Id::new(id.to_be_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn add_and_remove_nodes_in_a_tree_layout() {
+        let routing_table = tree_table(255, 2);
+        let (_, added) = routing_table.add(node_with_id(2379, 0x8000));
+        assert!(added);
+        let (_, added) = routing_table.add(node_with_id(2379, 0x8000));
+        assert!(!added);
+
+        assert!(routing_table.contains(&node_with_id(2379, 0x8000)).1);
+        assert!(routing_table.remove(&node_with_id(2379, 0x8000)));
+        assert!(!routing_table.contains(&node_with_id(2379, 0x8000)).1);
+        assert!(routing_table.all_nodes().is_empty());
+    }
+
+    #[test]
+    fn split_buckets_in_a_tree_layout() {
+        let routing_table = tree_table(255, 2);
+        routing_table.add(node_with_id(2379, 0x8000));
+        routing_table.add(node_with_id(2380, 0x8001));
+        routing_table.add(node_with_id(2381, 0x00f0));
+
+        let buckets = routing_table.buckets();
+        assert_eq!(2, buckets.len());
+        assert_eq!(0, buckets[0].0);
+        assert_eq!(1, buckets[0].1.len());
+        assert_eq!(1, buckets[1].0);
+        assert_eq!(2, buckets[1].1.len());
+    }
+
+    #[test]
+    fn add_the_closest_nodes_beyond_a_full_bucket_in_a_tree_layout() {
+        let fixed_table = Table::new_with_diversity_limits(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            2,
+            DiversityLimits::unlimited(),
+        );
+        let tree_table = tree_table(255, 2);
+        for (port, id) in [(2379, 0x8000), (2380, 0x8001), (2381, 0x8002)] {
+            fixed_table.add(node_with_id(port, id));
+            tree_table.add(node_with_id(port, id));
+        }
+
+        assert_eq!(2, fixed_table.all_nodes().len());
+        assert_eq!(3, tree_table.all_nodes().len());
+    }
+
+    #[test]
+    fn do_not_add_a_far_node_to_a_full_bucket_in_a_tree_layout() {
+        let routing_table = tree_table(255, 2);
+        for (port, id) in [
+            (2379, 0x00f0),
+            (2380, 0x00f1),
+            (2381, 0x8000),
+            (2382, 0x8001),
+        ] {
+            let (_, added) = routing_table.add(node_with_id(port, id));
+            assert!(added);
+        }
+
+        let (bucket_index, added) = routing_table.add(node_with_id(2383, 0x8002));
+        assert!(!added);
+        assert!(Some(node_with_id(2381, 0x8000)) == routing_table.first_node_in(bucket_index));
+    }
+
+    #[test]
+    fn remove_and_add_a_node_in_a_tree_layout() {
+        let routing_table = tree_table(255, 2);
+        for (port, id) in [
+            (2379, 0x00f0),
+            (2380, 0x00f1),
+            (2381, 0x8000),
+            (2382, 0x8001),
+        ] {
+            routing_table.add(node_with_id(port, id));
+        }
+        let (bucket_index, _) = routing_table.add(node_with_id(2383, 0x8002));
+
+        routing_table.remove_and_add(
+            bucket_index,
+            &node_with_id(2381, 0x8000),
+            node_with_id(2383, 0x8002),
+        );
+        assert!(!routing_table.contains(&node_with_id(2381, 0x8000)).1);
+        assert!(routing_table.contains(&node_with_id(2383, 0x8002)).1);
+    }
+
+    #[test]
+    fn do_not_remove_and_add_nodes_of_different_buckets_in_a_tree_layout() {
+        let routing_table = tree_table(255, 2);
+        for (port, id) in [
+            (2379, 0x00f0),
+            (2380, 0x00f1),
+            (2381, 0x8000),
+            (2382, 0x8001),
+        ] {
+            routing_table.add(node_with_id(port, id));
+        }
+
+        routing_table.remove_and_add(0, &node_with_id(2379, 0x00f0), node_with_id(2383, 0x8002));
+        assert!(routing_table.contains(&node_with_id(2379, 0x00f0)).1);
+        assert!(!routing_table.contains(&node_with_id(2383, 0x8002)).1);
+    }
+
+    #[test]
+    fn random_id_in_a_bucket_of_both_layouts() {
+        let fixed_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        let random_id = fixed_table.random_id_in(3).unwrap();
+        assert_eq!(
+            3,
+            Id::new(255u16.to_be_bytes().to_vec()).differing_bit_position(&random_id)
+        );
+        assert!(fixed_table.random_id_in(16).is_none());
+
+        let tree_table = tree_table(255, 2);
+        assert!(tree_table.random_id_in(0).is_some());
+        assert!(tree_table.random_id_in(1).is_none());
+    }
+
+    #[test]
+    fn track_the_liveness_of_a_contact() {
+        let routing_table = tree_table(255, 2);
+        let node = node_with_id(2379, 0x8000);
+        routing_table.add(node.clone());
+        assert_eq!(
+            0,
+            routing_table
+                .liveness_of(&node.id)
+                .unwrap()
+                .consecutive_failures
+        );
+
+        assert!(!routing_table.mark_failed(&node));
+        assert_eq!(
+            1,
+            routing_table
+                .liveness_of(&node.id)
+                .unwrap()
+                .consecutive_failures
+        );
+
+        routing_table.mark_seen(&node);
+        assert_eq!(
+            0,
+            routing_table
+                .liveness_of(&node.id)
+                .unwrap()
+                .consecutive_failures
+        );
+
+        routing_table.remove(&node);
+        assert!(routing_table.liveness_of(&node.id).is_none());
+    }
+
+    #[test]
+    fn least_recently_seen_contact_in_each_bucket() {
+        let routing_table = tree_table(255, 2);
+        let (first, second, other) = (
+            node_with_id(2379, 0x8000),
+            node_with_id(2380, 0x8001),
+            node_with_id(2381, 0x00f0),
+        );
+        routing_table.add(first.clone());
+        routing_table.add(second.clone());
+        routing_table.add(other.clone());
+        std::thread::sleep(Duration::from_millis(5));
+        routing_table.mark_seen(&first);
+
+        let least_recently_seen: Vec<Node> = routing_table
+            .least_recently_seen()
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(2, least_recently_seen.len());
+        assert!(least_recently_seen.contains(&second));
+        assert!(least_recently_seen.contains(&other));
+    }
+
+    #[test]
+    fn keep_a_stale_contact_without_a_replacement() {
+        let routing_table = table_evicting_after(RoutingLayout::Fixed, 1, 1);
+        let node = node_with_id(2379, 0x8000);
+        routing_table.add(node.clone());
+
+        assert!(!routing_table.mark_failed(&node));
+        assert!(routing_table.contains(&node).1);
+    }
+
+    #[test]
+    fn evict_a_stale_contact_for_a_replacement_in_both_layouts() {
+        for layout in [RoutingLayout::Fixed, RoutingLayout::Tree] {
+            let routing_table = table_evicting_after(layout, 1, 2);
+            let node = node_with_id(2379, 0x8000);
+            let replacement = node_with_id(2380, 0x8001);
+            routing_table.add(node_with_id(2378, 0x00f0));
+            routing_table.add(node.clone());
+            let (_, added) = routing_table.add(replacement.clone());
+            assert!(!added);
+            assert!(routing_table.replacements().contains(&replacement));
+
+            assert!(!routing_table.mark_failed(&node));
+            assert!(routing_table.mark_failed(&node));
+            assert!(!routing_table.contains(&node).1);
+            assert!(routing_table.contains(&replacement).1);
+            assert!(routing_table.replacements().is_empty());
+        }
+    }
+
+    #[test]
+    fn bound_the_replacements_of_a_bucket_by_its_capacity() {
+        let routing_table = table_evicting_after(RoutingLayout::Fixed, 1, 1);
+        for (port, id) in [(2379, 0x8000), (2380, 0x8001), (2381, 0x8002)] {
+            routing_table.add(node_with_id(port, id));
+        }
+        routing_table.add(node_with_id(2382, 0x0001));
+
+        let replacements = routing_table.replacements();
+        assert_eq!(1, replacements.len());
+        assert!(replacements.contains(&node_with_id(2381, 0x8002)));
+    }
+
+    fn table_evicting_after(
+        layout: RoutingLayout,
+        bucket_capacity: usize,
+        stale_after_failures: u32,
+    ) -> std::sync::Arc<Table> {
+        Table::new_with_options(
+            Id::new(255u16.to_be_bytes().to_vec()),
+            TableOptions::new(
+                bucket_capacity,
+                DiversityLimits::unlimited(),
+                layout,
+                stale_after_failures,
+                ProximityPolicy::default(),
+            ),
+            RoundTripTimes::with_default_timeouts(),
+            SystemClock::new(),
+        )
+    }
+
+    #[test]
+    fn notify_subscribers_of_added_and_removed_nodes() {
+        let routing_table = table_evicting_after(RoutingLayout::Fixed, 1, 1);
+        let mut events = routing_table.subscribe();
+        let node = node_with_id(2379, 0x8000);
+
+        let (bucket_index, _) = routing_table.add(node.clone());
+        routing_table.add(node_with_id(2380, 0x8001));
+        routing_table.remove(&node);
+
+        assert_eq!(
+            Ok(RoutingEvent::NodeAdded {
+                bucket_index,
+                node_id: node.id.as_bytes().to_vec(),
+            }),
+            events.try_recv()
+        );
+        assert_eq!(
+            Ok(RoutingEvent::NodeRemoved {
+                bucket_index,
+                node_id: node.id.as_bytes().to_vec(),
+            }),
+            events.try_recv()
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn notify_subscribers_of_replaced_nodes_and_refreshed_buckets() {
+        let routing_table = table_evicting_after(RoutingLayout::Fixed, 1, 1);
+        let node = node_with_id(2379, 0x8000);
+        let replacement = node_with_id(2380, 0x8001);
+        let (bucket_index, _) = routing_table.add(node.clone());
+        routing_table.add(replacement.clone());
+        let mut events = routing_table.subscribe();
+
+        assert!(routing_table.mark_failed(&node));
+        routing_table.record_refresh(bucket_index);
+
+        assert_eq!(
+            Ok(RoutingEvent::NodeReplaced {
+                bucket_index,
+                removed_node_id: node.id.as_bytes().to_vec(),
+                added_node_id: replacement.id.as_bytes().to_vec(),
+            }),
+            events.try_recv()
+        );
+        assert_eq!(
+            Ok(RoutingEvent::BucketRefreshed { bucket_index }),
+            events.try_recv()
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn evict_a_stale_contact_for_the_fastest_replacement() {
+        for (policy, prefers_faster) in [
+            (ProximityPolicy::LowestRoundTripTime, true),
+            (ProximityPolicy::Disabled, false),
+        ] {
+            let round_trip_times = RoundTripTimes::with_default_timeouts();
+            let routing_table = Table::new_with_options(
+                Id::new(255u16.to_be_bytes().to_vec()),
+                TableOptions::new(
+                    2,
+                    DiversityLimits::unlimited(),
+                    RoutingLayout::Fixed,
+                    1,
+                    policy,
+                ),
+                round_trip_times.clone(),
+                SystemClock::new(),
+            );
+            let node = node_with_id(2378, 0x8000);
+            routing_table.add(node.clone());
+            routing_table.add(node_with_id(2379, 0x8001));
+            let (faster, newer) = (node_with_id(2380, 0x8002), node_with_id(2381, 0x8003));
+            routing_table.add(faster.clone());
+            routing_table.add(newer.clone());
+            round_trip_times.observe_reply(
+                &faster.endpoint,
+                MessageTypes::PingReply,
+                Duration::from_millis(10),
+            );
+            round_trip_times.observe_reply(
+                &newer.endpoint,
+                MessageTypes::PingReply,
+                Duration::from_millis(80),
+            );
+
+            assert!(routing_table.mark_failed(&node));
+            assert_eq!(prefers_faster, routing_table.contains(&faster).1);
+            assert_eq!(!prefers_faster, routing_table.contains(&newer).1);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn closest_neighbors_match_a_brute_force_sort(
+            own_id in any::<u16>(),
+            node_ids in proptest::collection::hash_set(any::<u16>(), 0..64),
+            target in any::<u16>(),
+            number_of_neighbors in 1..24usize,
+            bucket_capacity in 1..8usize,
+            layout in prop_oneof![Just(RoutingLayout::Fixed), Just(RoutingLayout::Tree)],
+        ) {
+            let routing_table = Table::new_with_layout(
+                Id::new(own_id.to_be_bytes().to_vec()),
+                bucket_capacity,
+                DiversityLimits::unlimited(),
+                layout,
+            );
+            for (port, node_id) in node_ids.into_iter().filter(|node_id| *node_id != own_id).enumerate() {
+                routing_table.add(Node::new_with_id(
+                    Endpoint::new("localhost".to_string(), 2379 + port as u16),
+                    Id::new(node_id.to_be_bytes().to_vec()),
+                ));
+            }
+
+            let target = Id::new(target.to_be_bytes().to_vec());
+            let mut expected = routing_table.all_nodes();
+            expected.sort_by_key(|node| node.id.distance_from(&target));
+            expected.truncate(number_of_neighbors);
+
+            let closest_neighbors = routing_table.closest_neighbors(&target, number_of_neighbors);
+            let expected_ids: Vec<Id> = expected.into_iter().map(|node| node.id).collect();
+            let actual_ids: Vec<Id> = closest_neighbors.all_nodes().iter().map(|node| node.id.clone()).collect();
+            prop_assert_eq!(expected_ids, actual_ids);
+        }
+    }
 }
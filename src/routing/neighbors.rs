@@ -20,23 +20,23 @@ impl ClosestNeighbors {
         }
     }
 
-    pub(crate) fn add_missing(&mut self, nodes: &Vec<Node>) -> bool {
+    pub(crate) fn add_missing(&mut self, nodes: &[Node]) {
         for node in nodes {
-            if self.nodes.len() < self.maximum_capacity {
-                if !self.node_ids.contains(&node.id) {
-                    self.node_ids.insert(node.id.clone());
-                    self.nodes.push(node.clone());
-                }
-            } else {
-                return false;
+            if self.node_ids.insert(node.id.clone()) {
+                self.nodes.push(node.clone());
             }
         }
-        return true;
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.nodes.len() >= self.maximum_capacity
     }
 
     pub(crate) fn sort_ascending_by_distance(&mut self) {
         self.nodes
             .sort_by_key(|node| node.id.distance_from(&self.target));
+        self.nodes.truncate(self.maximum_capacity);
+        self.node_ids = self.nodes.iter().map(|node| node.id.clone()).collect();
     }
 
     pub(crate) fn all_nodes(&self) -> &Vec<Node> {
@@ -68,9 +68,12 @@ mod tests {
 
         let mut closest_neighbors = ClosestNeighbors::new(1, target);
         closest_neighbors.add_missing(&nodes);
+        assert!(closest_neighbors.is_full());
 
+        closest_neighbors.sort_ascending_by_distance();
         assert_eq!(1, closest_neighbors.nodes.len());
         assert_eq!(1, closest_neighbors.node_ids.len());
+        assert_eq!(&Id::new(vec![10, 20]), &closest_neighbors.nodes[0].id);
     }
 
     #[test]
@@ -0,0 +1,257 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::net::node::Node;
+use crate::routing::Table;
+
+#[derive(Debug)]
+pub(crate) enum PersistenceErrorKind {
+    Io(Error),
+    SerializationError(String),
+}
+
+impl From<Error> for PersistenceErrorKind {
+    fn from(err: Error) -> Self {
+        PersistenceErrorKind::Io(err)
+    }
+}
+
+impl From<bincode::Error> for PersistenceErrorKind {
+    fn from(value: bincode::Error) -> Self {
+        PersistenceErrorKind::SerializationError(value.to_string())
+    }
+}
+
+impl Display for PersistenceErrorKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PersistenceErrorKind::Io(err) => write!(formatter, "io error: {}", err),
+            PersistenceErrorKind::SerializationError(description) => {
+                write!(formatter, "serialization err: {}", description)
+            }
+        }
+    }
+}
+
+pub(crate) fn save(table: &Table, file_path: &Path) -> Result<usize, PersistenceErrorKind> {
+    let nodes = table.all_nodes();
+    let serialized = bincode::serialize(&nodes)?;
+
+    // the table is written to a temporary file first, a crash while writing leaves the
+    // previous table in place.
+    let temporary_file_path = file_path.with_extension("tmp");
+    let mut temporary_file = File::create(&temporary_file_path)?;
+    temporary_file.write_all(&serialized)?;
+    temporary_file.sync_all()?;
+    fs::rename(&temporary_file_path, file_path)?;
+
+    Ok(nodes.len())
+}
+
+pub(crate) fn load(file_path: &Path) -> Result<Vec<Node>, PersistenceErrorKind> {
+    match fs::read(file_path) {
+        Ok(bytes) => Ok(bincode::deserialize(&bytes)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(PersistenceErrorKind::Io(err)),
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TablePersisterOptions {
+    pub(crate) file_path: PathBuf,
+    pub(crate) persist_every: Duration,
+}
+
+impl TablePersisterOptions {
+    pub(crate) fn new(file_path: PathBuf, persist_every: Duration) -> Self {
+        TablePersisterOptions {
+            file_path,
+            persist_every,
+        }
+    }
+}
+
+pub(crate) struct TablePersister {
+    table: Arc<Table>,
+    file_path: PathBuf,
+    // guards the writes and records whether the persister stopped, a periodic write racing
+    // with stop never lands after the final write.
+    stopped: Mutex<bool>,
+    persist_loop: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TablePersister {
+    pub(crate) fn new(table: Arc<Table>, options: TablePersisterOptions) -> Arc<TablePersister> {
+        let persister = Arc::new(TablePersister {
+            table,
+            file_path: options.file_path,
+            stopped: Mutex::new(false),
+            persist_loop: Mutex::new(None),
+        });
+        let persist_loop = tokio::spawn(persister.clone().run(options.persist_every));
+        *persister.persist_loop.lock().unwrap() = Some(persist_loop);
+        persister
+    }
+
+    pub(crate) fn stop(&self) -> Result<usize, PersistenceErrorKind> {
+        if let Some(persist_loop) = self.persist_loop.lock().unwrap().take() {
+            persist_loop.abort();
+        }
+        let mut stopped = self.stopped.lock().unwrap();
+        *stopped = true;
+        self.persist()
+    }
+
+    async fn run(self: Arc<TablePersister>, persist_every: Duration) {
+        let mut rounds = interval(persist_every);
+        rounds.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        rounds.tick().await;
+        loop {
+            rounds.tick().await;
+            let stopped = self.stopped.lock().unwrap();
+            if *stopped {
+                return;
+            }
+            if let Err(err) = self.persist() {
+                error!("could not persist the routing table {}", err);
+            }
+        }
+    }
+
+    fn persist(&self) -> Result<usize, PersistenceErrorKind> {
+        let total_nodes = save(&self.table, &self.file_path)?;
+        info!(
+            "persisted {} nodes of the routing table to {:?}",
+            total_nodes, self.file_path
+        );
+        Ok(total_nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::node::Node;
+    use crate::routing::persist::{load, save, TablePersister, TablePersisterOptions};
+    use crate::routing::Table;
+
+    #[test]
+    fn save_and_load_the_routing_table() {
+        let file_path = file_path("save_and_load.table");
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+
+        let total_saved = save(&routing_table, &file_path).unwrap();
+        assert_eq!(1, total_saved);
+
+        let nodes = load(&file_path).unwrap();
+        assert_eq!(1, nodes.len());
+        assert_eq!("localhost:2379", nodes[0].endpoint.address());
+        assert_eq!(Id::new(247u16.to_be_bytes().to_vec()), nodes[0].id);
+    }
+
+    #[test]
+    fn replace_a_saved_table_without_a_temporary_file_left() {
+        let file_path = file_path("replace.table");
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        save(&routing_table, &file_path).unwrap();
+
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+        save(&routing_table, &file_path).unwrap();
+
+        assert_eq!(1, load(&file_path).unwrap().len());
+        assert!(!file_path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn load_from_a_missing_file() {
+        let nodes = load(&file_path("missing.table")).unwrap();
+        assert!(nodes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn persist_the_routing_table_periodically() {
+        let file_path = file_path("periodic.table");
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+
+        let persister = TablePersister::new(
+            routing_table,
+            TablePersisterOptions::new(file_path.clone(), Duration::from_millis(5)),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let nodes = load(&file_path).unwrap();
+        assert_eq!(1, nodes.len());
+
+        persister.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop_persisting_the_routing_table_after_stop() {
+        let file_path = file_path("stopped.table");
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+
+        let persister = TablePersister::new(
+            routing_table.clone(),
+            TablePersisterOptions::new(file_path.clone(), Duration::from_millis(5)),
+        );
+        persister.stop().unwrap();
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2379),
+            Id::new(247u16.to_be_bytes().to_vec()),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(load(&file_path).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn persist_the_routing_table_on_stop() {
+        let file_path = file_path("stop.table");
+        let routing_table = Table::new(Id::new(255u16.to_be_bytes().to_vec()));
+
+        let persister = TablePersister::new(
+            routing_table.clone(),
+            TablePersisterOptions::new(file_path.clone(), Duration::from_secs(120)),
+        );
+        routing_table.add(Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 2380),
+            Id::new(511u16.to_be_bytes().to_vec()),
+        ));
+
+        let total_persisted = persister.stop().unwrap();
+        assert_eq!(1, total_persisted);
+
+        let nodes = load(&file_path).unwrap();
+        assert_eq!("localhost:2380", nodes[0].endpoint.address());
+    }
+
+    fn file_path(file_name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("kademlia-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory.join(file_name)
+    }
+}
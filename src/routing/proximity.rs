@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProximityPolicy {
+    #[default]
+    LowestRoundTripTime,
+    Disabled,
+}
+
+impl ProximityPolicy {
+    // Among equally eligible candidates the one with the lowest key is preferred. Candidates
+    // without a measured round-trip time come last, and a disabled policy prefers none of them.
+    pub(crate) fn preference_key(&self, round_trip_time: Option<Duration>) -> Duration {
+        match self {
+            ProximityPolicy::LowestRoundTripTime => round_trip_time.unwrap_or(Duration::MAX),
+            ProximityPolicy::Disabled => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::routing::proximity::ProximityPolicy;
+
+    #[test]
+    fn prefer_the_lowest_measured_round_trip_time() {
+        let policy = ProximityPolicy::LowestRoundTripTime;
+
+        assert!(
+            policy.preference_key(Some(Duration::from_millis(10)))
+                < policy.preference_key(Some(Duration::from_millis(80)))
+        );
+        assert!(policy.preference_key(Some(Duration::from_secs(60))) < policy.preference_key(None));
+    }
+
+    #[test]
+    fn prefer_no_candidate_when_disabled() {
+        let policy = ProximityPolicy::Disabled;
+
+        assert_eq!(
+            policy.preference_key(None),
+            policy.preference_key(Some(Duration::from_millis(10)))
+        );
+    }
+}
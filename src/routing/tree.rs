@@ -0,0 +1,219 @@
+use log::info;
+
+use crate::id::Id;
+use crate::net::node::{Node, NodeId};
+
+struct Leaf {
+    prefix: Id,
+    depth: usize,
+    nodes: Vec<Node>,
+}
+
+impl Leaf {
+    fn covers(&self, id: &Id) -> bool {
+        id.prefix(self.depth) == self.prefix
+    }
+}
+
+pub(crate) struct Tree {
+    node_id: NodeId,
+    leaves: Vec<Leaf>,
+}
+
+impl Tree {
+    pub(crate) fn new(node_id: NodeId) -> Self {
+        let prefix = node_id.prefix(0);
+        Tree {
+            node_id,
+            leaves: vec![Leaf {
+                prefix,
+                depth: 0,
+                nodes: Vec::new(),
+            }],
+        }
+    }
+
+    pub(crate) fn bucket_index(&self, id: &Id) -> usize {
+        self.leaves
+            .iter()
+            .position(|leaf| leaf.covers(id))
+            .expect("the leaves of the routing tree cover the whole id space")
+    }
+
+    pub(crate) fn bucket(&self, bucket_index: usize) -> Option<&Vec<Node>> {
+        self.leaves.get(bucket_index).map(|leaf| &leaf.nodes)
+    }
+
+    pub(crate) fn bucket_mut(&mut self, bucket_index: usize) -> &mut Vec<Node> {
+        &mut self.leaves[bucket_index].nodes
+    }
+
+    pub(crate) fn buckets(&self) -> impl Iterator<Item = &Vec<Node>> {
+        self.leaves.iter().map(|leaf| &leaf.nodes)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn bucket_depth(&self, bucket_index: usize) -> Option<usize> {
+        self.leaves.get(bucket_index).map(|leaf| leaf.depth)
+    }
+
+    pub(crate) fn random_id_in(&self, bucket_index: usize) -> Option<Id> {
+        self.leaves
+            .get(bucket_index)
+            .map(|leaf| leaf.prefix.random_with_prefix(leaf.depth))
+    }
+
+    // Each leaf covers a contiguous range of distances from the target, so ordering leaves by
+    // the distance of their prefix from the target's prefix orders every node they hold.
+    pub(crate) fn bucket_indices_by_distance(&self, target: &Id) -> Vec<usize> {
+        let mut bucket_indices: Vec<usize> = (0..self.leaves.len()).collect();
+        bucket_indices.sort_by_cached_key(|bucket_index| {
+            let leaf = &self.leaves[*bucket_index];
+            leaf.prefix.distance_from(&target.prefix(leaf.depth))
+        });
+        bucket_indices
+    }
+
+    // A full bucket splits when its range covers our own id or, following the relaxed rule, when
+    // the new node would be among the `bucket_capacity` nodes closest to us.
+    pub(crate) fn split_to_fit(&mut self, id: &Id, bucket_capacity: usize) -> usize {
+        loop {
+            let bucket_index = self.bucket_index(id);
+            let leaf = &self.leaves[bucket_index];
+            if leaf.nodes.len() < bucket_capacity
+                || leaf.depth == self.node_id.id_length_in_bits
+                || !(leaf.covers(&self.node_id) || self.is_among_closest(id, bucket_capacity))
+            {
+                return bucket_index;
+            }
+            self.split(bucket_index);
+        }
+    }
+
+    fn is_among_closest(&self, id: &Id, bucket_capacity: usize) -> bool {
+        let distance = self.node_id.distance_from(id);
+        let closer_nodes = self
+            .buckets()
+            .flatten()
+            .filter(|node| self.node_id.distance_from(&node.id) < distance)
+            .count();
+        closer_nodes < bucket_capacity
+    }
+
+    fn split(&mut self, bucket_index: usize) {
+        let leaf = self.leaves.remove(bucket_index);
+        let (far_nodes, near_nodes): (Vec<Node>, Vec<Node>) = leaf
+            .nodes
+            .into_iter()
+            .partition(|node| node.id.has_bit_set(leaf.depth));
+
+        info!(
+            "splitting the bucket with index {} at depth {}",
+            bucket_index, leaf.depth
+        );
+        self.leaves.insert(
+            bucket_index,
+            Leaf {
+                prefix: leaf.prefix.with_bit_set(leaf.depth),
+                depth: leaf.depth + 1,
+                nodes: far_nodes,
+            },
+        );
+        self.leaves.insert(
+            bucket_index,
+            Leaf {
+                prefix: leaf.prefix,
+                depth: leaf.depth + 1,
+                nodes: near_nodes,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id::Id;
+    use crate::net::endpoint::Endpoint;
+    use crate::net::node::Node;
+    use crate::routing::tree::Tree;
+
+    fn node(port: u16, id: u16) -> Node {
+        Node::new_with_id(
+            Endpoint::new("localhost".to_string(), port),
+            Id::new(id.to_be_bytes().to_vec()),
+        )
+    }
+
+    fn add(tree: &mut Tree, node: Node, bucket_capacity: usize) -> bool {
+        let bucket_index = tree.split_to_fit(&node.id, bucket_capacity);
+        let nodes = tree.bucket_mut(bucket_index);
+        if nodes.len() < bucket_capacity {
+            nodes.push(node);
+            return true;
+        }
+        false
+    }
+
+    #[test]
+    fn start_with_a_single_bucket() {
+        let tree = Tree::new(Id::new(255u16.to_be_bytes().to_vec()));
+
+        assert_eq!(1, tree.buckets().count());
+        assert_eq!(0, tree.bucket_index(&Id::new(0u16.to_be_bytes().to_vec())));
+        assert_eq!(Some(0), tree.bucket_depth(0));
+    }
+
+    #[test]
+    fn split_a_full_bucket_covering_own_id() {
+        let mut tree = Tree::new(Id::new(255u16.to_be_bytes().to_vec()));
+        assert!(add(&mut tree, node(2379, 0x8000), 2));
+        assert!(add(&mut tree, node(2380, 0x8001), 2));
+        assert!(add(&mut tree, node(2381, 0x00f0), 2));
+
+        assert_eq!(2, tree.buckets().count());
+        assert_eq!(Some(1), tree.bucket_depth(0));
+        assert_eq!(1, tree.bucket(0).unwrap().len());
+        assert_eq!(2, tree.bucket(1).unwrap().len());
+    }
+
+    #[test]
+    fn do_not_split_a_full_bucket_far_from_own_id() {
+        let mut tree = Tree::new(Id::new(255u16.to_be_bytes().to_vec()));
+        assert!(add(&mut tree, node(2379, 0x00f0), 2));
+        assert!(add(&mut tree, node(2380, 0x00f1), 2));
+        assert!(add(&mut tree, node(2381, 0x8000), 2));
+        assert!(add(&mut tree, node(2382, 0x8001), 2));
+
+        assert!(!add(&mut tree, node(2383, 0x8002), 2));
+        assert_eq!(
+            2,
+            tree.bucket(tree.bucket_index(&Id::new(vec![0x80, 0])))
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn split_a_full_bucket_far_from_own_id_holding_the_closest_nodes() {
+        let mut tree = Tree::new(Id::new(255u16.to_be_bytes().to_vec()));
+        assert!(add(&mut tree, node(2379, 0x8000), 2));
+        assert!(add(&mut tree, node(2380, 0x8001), 2));
+        assert!(add(&mut tree, node(2381, 0x00f0), 2));
+
+        assert!(add(&mut tree, node(2382, 0x8002), 2));
+        assert_eq!(4, tree.buckets().flatten().count());
+    }
+
+    #[test]
+    fn random_id_in_a_bucket() {
+        let mut tree = Tree::new(Id::new(255u16.to_be_bytes().to_vec()));
+        add(&mut tree, node(2379, 0x8000), 1);
+        add(&mut tree, node(2380, 0x00f0), 1);
+
+        for bucket_index in 0..tree.buckets().count() {
+            let random_id = tree.random_id_in(bucket_index).unwrap();
+            assert_eq!(bucket_index, tree.bucket_index(&random_id));
+        }
+        assert!(tree.random_id_in(tree.buckets().count()).is_none());
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use dashmap::DashMap;
+
+use crate::net::message::{Message, MessageTypes};
+use crate::net::node::NodeId;
+use crate::time::Clock;
+
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+const MIN_PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Requests a peer can send unsolicited, replies are matched against the waiting list.
+pub(crate) const REQUEST_MESSAGE_TYPES: [MessageTypes; 6] = [
+    MessageTypes::Store,
+    MessageTypes::Delete,
+    MessageTypes::AddNode,
+    MessageTypes::FindValue,
+    MessageTypes::FindNode,
+    MessageTypes::Ping,
+];
+
+#[derive(Copy, Clone)]
+pub(crate) struct RateLimit {
+    pub(crate) capacity: u32,
+    pub(crate) refill_per_second: u32,
+}
+
+impl RateLimit {
+    pub(crate) fn new(capacity: u32, refill_per_second: u32) -> Self {
+        RateLimit {
+            capacity,
+            refill_per_second,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct RateLimitOptions {
+    pub(crate) per_source: HashMap<MessageTypes, RateLimit>,
+    pub(crate) per_ip: HashMap<MessageTypes, RateLimit>,
+}
+
+impl RateLimitOptions {
+    pub(crate) fn new(
+        per_source: HashMap<MessageTypes, RateLimit>,
+        per_ip: HashMap<MessageTypes, RateLimit>,
+    ) -> Self {
+        RateLimitOptions { per_source, per_ip }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn unlimited() -> Self {
+        RateLimitOptions::default()
+    }
+}
+
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
+pub(crate) enum ThrottleScope {
+    Source,
+    Ip,
+}
+
+// Each bucket keeps the limit of its message type, buckets of other message types are refilled
+// and pruned by their own limit.
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: SystemTime,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: SystemTime) -> Self {
+        TokenBucket {
+            limit,
+            tokens: limit.capacity as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: SystemTime) {
+        if let Ok(elapsed) = now.duration_since(self.last_refill) {
+            self.tokens = (self.tokens
+                + elapsed.as_secs_f64() * self.limit.refill_per_second as f64)
+                .min(self.limit.capacity as f64);
+            self.last_refill = now;
+        }
+    }
+
+    fn try_take(&mut self, now: SystemTime) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        false
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.capacity as f64
+    }
+}
+
+struct TokenBuckets<K: Hash + Eq> {
+    buckets: DashMap<(K, MessageTypes), TokenBucket>,
+    last_pruned: Mutex<Option<SystemTime>>,
+}
+
+impl<K: Hash + Eq> TokenBuckets<K> {
+    fn new() -> Self {
+        TokenBuckets {
+            buckets: DashMap::new(),
+            last_pruned: Mutex::new(None),
+        }
+    }
+
+    fn try_take(&self, limit: &RateLimit, key: (K, MessageTypes), now: SystemTime) -> bool {
+        if let Some(mut bucket) = self.buckets.get_mut(&key) {
+            return bucket.try_take(now);
+        }
+        if self.buckets.len() >= MAX_TRACKED_BUCKETS {
+            self.prune(now);
+            // a full map means many peers are sending at once, a new peer waits until
+            // the bucket of a quiet peer refills and is pruned.
+            if self.buckets.len() >= MAX_TRACKED_BUCKETS {
+                return false;
+            }
+        }
+        self.buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::new(*limit, now))
+            .try_take(now)
+    }
+
+    // A full bucket behaves as a missing one, so it is dropped. Every bucket is refilled by the
+    // limit of its own message type, and the scan runs at most once per interval.
+    fn prune(&self, now: SystemTime) {
+        {
+            let mut last_pruned = self.last_pruned.lock().unwrap();
+            let pruned_recently = last_pruned.is_some_and(|last_pruned| {
+                now.duration_since(last_pruned)
+                    .is_ok_and(|elapsed| elapsed < MIN_PRUNE_INTERVAL)
+            });
+            if pruned_recently {
+                return;
+            }
+            *last_pruned = Some(now);
+        }
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            !bucket.is_full()
+        });
+    }
+}
+
+pub(crate) struct RateLimiter {
+    options: RateLimitOptions,
+    clock: Box<dyn Clock>,
+    source_buckets: TokenBuckets<NodeId>,
+    ip_buckets: TokenBuckets<IpAddr>,
+    throttled: DashMap<(MessageTypes, ThrottleScope), AtomicU64>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(options: RateLimitOptions, clock: Box<dyn Clock>) -> Self {
+        RateLimiter {
+            options,
+            clock,
+            source_buckets: TokenBuckets::new(),
+            ip_buckets: TokenBuckets::new(),
+            throttled: DashMap::new(),
+        }
+    }
+
+    pub(crate) fn allow(&self, message: &Message, ip: Option<IpAddr>) -> bool {
+        let message_type = message.message_type();
+        let now = self.clock.now();
+
+        if let Some(ip) = ip {
+            if let Some(limit) = self.options.per_ip.get(&message_type) {
+                if !self.ip_buckets.try_take(limit, (ip, message_type), now) {
+                    self.record_throttled(message_type, ThrottleScope::Ip);
+                    return false;
+                }
+            }
+        }
+        if let Some(source) = message.source() {
+            if let Some(limit) = self.options.per_source.get(&message_type) {
+                if !self
+                    .source_buckets
+                    .try_take(limit, (source.id, message_type), now)
+                {
+                    self.record_throttled(message_type, ThrottleScope::Source);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    #[cfg(test)]
+    pub(crate) fn throttled_count(&self, message_type: MessageTypes, scope: ThrottleScope) -> u64 {
+        self.throttled
+            .get(&(message_type, scope))
+            .map(|count| count.load(Ordering::SeqCst))
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn throttled_counts(&self) -> Vec<(MessageTypes, ThrottleScope, u64)> {
+        let mut throttled_counts: Vec<(MessageTypes, ThrottleScope, u64)> = self
+            .throttled
+            .iter()
+            .map(|entry| {
+                let (message_type, scope) = *entry.key();
+                (message_type, scope, entry.value().load(Ordering::SeqCst))
+            })
+            .collect();
+        throttled_counts
+            .sort_by_key(|(message_type, scope, _)| (*message_type as u8, *scope as u8));
+        throttled_counts
+    }
+
+    fn record_throttled(&self, message_type: MessageTypes, scope: ThrottleScope) {
+        self.throttled
+            .entry((message_type, scope))
+            .or_insert_with(|| AtomicU64::new(0))
+            .fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::ops::Add;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
+    use crate::net::endpoint::Endpoint;
+    use crate::net::message::{Message, MessageTypes};
+    use crate::net::node::Node;
+    use crate::server::limit::{
+        RateLimit, RateLimitOptions, RateLimiter, ThrottleScope, MAX_TRACKED_BUCKETS,
+    };
+    use crate::time::Clock;
+
+    #[derive(Clone)]
+    struct ManualClock {
+        now: Arc<Mutex<SystemTime>>,
+    }
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            let mut now = self.now.lock().unwrap();
+            *now = now.add(duration);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> SystemTime {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    fn manual_clock() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(SystemTime::now())),
+        }
+    }
+
+    fn store_message(port: u16) -> Message {
+        Message::store_type(
+            "kademlia".as_bytes().to_vec(),
+            "distributed hash table".as_bytes().to_vec(),
+            1,
+            Node::new(Endpoint::new("localhost".to_string(), port)),
+        )
+    }
+
+    fn localhost() -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    #[test]
+    fn allow_all_messages_without_limits() {
+        let rate_limiter =
+            RateLimiter::new(RateLimitOptions::unlimited(), Box::new(manual_clock()));
+        for _ in 0..100 {
+            assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        }
+    }
+
+    #[test]
+    fn throttle_a_source_after_its_capacity() {
+        let per_source = HashMap::from([(MessageTypes::Store, RateLimit::new(2, 1))]);
+        let rate_limiter = RateLimiter::new(
+            RateLimitOptions::new(per_source, HashMap::new()),
+            Box::new(manual_clock()),
+        );
+
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(!rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(rate_limiter.allow(&store_message(1982), localhost()));
+
+        assert_eq!(
+            1,
+            rate_limiter.throttled_count(MessageTypes::Store, ThrottleScope::Source)
+        );
+    }
+
+    #[test]
+    fn throttle_an_ip_across_sources() {
+        let per_ip = HashMap::from([(MessageTypes::Store, RateLimit::new(1, 1))]);
+        let rate_limiter = RateLimiter::new(
+            RateLimitOptions::new(HashMap::new(), per_ip),
+            Box::new(manual_clock()),
+        );
+
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(!rate_limiter.allow(&store_message(1982), localhost()));
+        assert!(rate_limiter.allow(
+            &store_message(1982),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))
+        ));
+
+        assert_eq!(
+            1,
+            rate_limiter.throttled_count(MessageTypes::Store, ThrottleScope::Ip)
+        );
+        assert_eq!(
+            vec![(MessageTypes::Store, ThrottleScope::Ip, 1)],
+            rate_limiter.throttled_counts()
+        );
+    }
+
+    #[test]
+    fn limit_only_the_configured_message_type() {
+        let per_source = HashMap::from([(MessageTypes::Store, RateLimit::new(1, 1))]);
+        let rate_limiter = RateLimiter::new(
+            RateLimitOptions::new(per_source, HashMap::new()),
+            Box::new(manual_clock()),
+        );
+        let node = Node::new(Endpoint::new("localhost".to_string(), 2330));
+
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(rate_limiter.allow(
+            &Message::find_value_type(node.clone(), b"kademlia".to_vec()),
+            localhost()
+        ));
+        assert!(rate_limiter.allow(
+            &Message::find_value_type(node, b"kademlia".to_vec()),
+            localhost()
+        ));
+    }
+
+    #[test]
+    fn throttle_new_sources_until_tracked_buckets_refill() {
+        let clock = manual_clock();
+        let per_source = HashMap::from([(MessageTypes::Store, RateLimit::new(1, 1))]);
+        let rate_limiter = RateLimiter::new(
+            RateLimitOptions::new(per_source, HashMap::new()),
+            Box::new(clock.clone()),
+        );
+
+        for port in 0..MAX_TRACKED_BUCKETS as u16 {
+            assert!(rate_limiter.allow(&store_message(port), localhost()));
+        }
+        let new_source = MAX_TRACKED_BUCKETS as u16;
+        assert!(!rate_limiter.allow(&store_message(new_source), localhost()));
+
+        clock.advance(Duration::from_secs(1));
+        assert!(rate_limiter.allow(&store_message(new_source), localhost()));
+        assert_eq!(1, rate_limiter.source_buckets.buckets.len());
+    }
+
+    #[test]
+    fn refill_tokens_over_time() {
+        let clock = manual_clock();
+        let per_source = HashMap::from([(MessageTypes::Store, RateLimit::new(1, 2))]);
+        let rate_limiter = RateLimiter::new(
+            RateLimitOptions::new(per_source, HashMap::new()),
+            Box::new(clock.clone()),
+        );
+
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+        assert!(!rate_limiter.allow(&store_message(2330), localhost()));
+
+        clock.advance(Duration::from_millis(500));
+        assert!(rate_limiter.allow(&store_message(2330), localhost()));
+    }
+}
@@ -1,19 +1,34 @@
 use std::sync::Arc;
 
-use log::error;
+use log::{error, warn};
+use tokio::net::TcpStream;
 use tokio::sync::mpsc::error::SendError;
+use tracing::field::{display, Empty};
+use tracing::{info_span, Instrument};
 
+use crate::config::NodeConfig;
 use crate::executor::message::MessageExecutor;
 use crate::executor::node::AddNodeExecutor;
 use crate::executor::response::{ChanneledMessage, MessageResponse};
+use crate::metrics::Metrics;
 use crate::net::connection::AsyncTcpConnection;
+use crate::net::identity::Identity;
 use crate::net::message::Message;
 use crate::net::node::Node;
-use crate::net::wait::WaitingList;
+use crate::net::secure::SecureChannel;
+use crate::net::AsyncNetwork;
 use crate::routing::Table;
+use crate::server::limit::RateLimiter;
 use crate::store::Store;
 
-struct AsyncConnectionHandler {
+pub(crate) mod limit;
+
+pub(crate) struct AsyncConnectionHandler {
+    secure_channel: Option<Arc<SecureChannel>>,
+    rate_limiter: Arc<RateLimiter>,
+    metrics: Arc<Metrics>,
+    routing_table: Arc<Table>,
+    max_message_size: usize,
     message_executor: MessageExecutor,
     add_node_executor: AddNodeExecutor,
 }
@@ -21,34 +36,72 @@ struct AsyncConnectionHandler {
 impl AsyncConnectionHandler {
     pub(crate) fn new(
         current_node: Node,
+        identity: Arc<Identity>,
         store: Arc<dyn Store>,
-        waiting_list: Arc<WaitingList>,
+        async_network: Arc<AsyncNetwork>,
         routing_table: Arc<Table>,
+        rate_limiter: Arc<RateLimiter>,
+        config: &NodeConfig,
     ) -> Self {
         AsyncConnectionHandler {
+            secure_channel: async_network.secure_channel(),
+            rate_limiter,
+            metrics: async_network.metrics(),
+            routing_table: routing_table.clone(),
+            max_message_size: config.max_message_size,
             message_executor: MessageExecutor::new(
                 current_node.clone(),
+                identity,
                 store,
-                waiting_list.clone(),
+                async_network.clone(),
                 routing_table.clone(),
+                config,
+            ),
+            add_node_executor: AddNodeExecutor::new(
+                current_node,
+                async_network,
+                routing_table,
+                config,
             ),
-            add_node_executor: AddNodeExecutor::new(current_node, waiting_list, routing_table),
         }
     }
 
+    pub(crate) async fn accept(&self, tcp_stream: TcpStream) {
+        let connection = match &self.secure_channel {
+            Some(secure_channel) => {
+                match AsyncTcpConnection::accept_secure(tcp_stream, secure_channel).await {
+                    Ok(connection) => connection.with_max_message_size(self.max_message_size),
+                    Err(err) => {
+                        error!("received an error in the secure handshake {:?}", err);
+                        return;
+                    }
+                }
+            }
+            None => {
+                AsyncTcpConnection::new(tcp_stream).with_max_message_size(self.max_message_size)
+            }
+        };
+        self.handle(connection).await
+    }
+
     pub(crate) async fn handle(&self, mut connection: AsyncTcpConnection) {
         match connection.read().await {
             Ok(message) => {
-                let source = message.source();
-                Self::log_error_if_any(self.message_executor.submit(message).await);
-
-                if let Some(node) = source {
-                    Self::log_error_if_any(
-                        self.add_node_executor
-                            .submit(Message::add_node_type(node))
-                            .await,
-                    )
+                let inbound_span = info_span!(
+                    "inbound",
+                    r#type = ?message.message_type(),
+                    message_id = Empty,
+                    source = Empty
+                );
+                if let Some(message_id) = message.message_id() {
+                    inbound_span.record("message_id", message_id);
                 }
+                if let Some(source) = message.source() {
+                    inbound_span.record("source", display(source.id.to_hex()));
+                }
+                self.handle_message(message, &connection)
+                    .instrument(inbound_span)
+                    .await
             }
             Err(err) => {
                 error!(
@@ -59,6 +112,62 @@ impl AsyncConnectionHandler {
         }
     }
 
+    async fn handle_message(&self, message: Message, connection: &AsyncTcpConnection) {
+        self.metrics.record_received(message.message_type());
+        let source = message.source();
+        let source_is_authenticated = connection.authenticated_node_id().is_some();
+        if let (Some(authenticated_node_id), Some(node)) =
+            (connection.authenticated_node_id(), &source)
+        {
+            if &node.id != authenticated_node_id {
+                warn!(
+                    "rejecting message from {:?} claiming to be {:?}",
+                    authenticated_node_id, node.id
+                );
+                return;
+            }
+        }
+        if !self.rate_limiter.allow(&message, connection.peer_ip()) {
+            warn!(
+                "dropping throttled {:?} message from {:?}",
+                message.message_type(),
+                connection.peer_ip()
+            );
+            return;
+        }
+        Self::log_error_if_any(self.message_executor.submit(message).await);
+
+        if let Some(node) = source {
+            // Anyone can claim any source on a plain connection, so liveness is left to the
+            // replies to our own requests.
+            if source_is_authenticated {
+                self.routing_table.mark_seen(&node);
+            }
+            Self::log_error_if_any(
+                self.add_node_executor
+                    .submit(Message::add_node_type(node))
+                    .await,
+            )
+        }
+    }
+
+    pub(crate) fn rate_limiter(&self) -> Arc<RateLimiter> {
+        self.rate_limiter.clone()
+    }
+
+    pub(crate) fn message_executor_queue_depth(&self) -> usize {
+        self.message_executor.queue_depth()
+    }
+
+    pub(crate) fn add_node_executor_queue_depth(&self) -> usize {
+        self.add_node_executor.queue_depth()
+    }
+
+    pub(crate) async fn shutdown(&self) {
+        Self::log_error_if_any(self.message_executor.shutdown().await);
+        Self::log_error_if_any(self.add_node_executor.shutdown().await);
+    }
+
     fn log_error_if_any(result: Result<MessageResponse, SendError<ChanneledMessage>>) {
         if let Err(err) = result {
             error!(
@@ -71,19 +180,26 @@ impl AsyncConnectionHandler {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
 
     use tokio::net::TcpListener;
 
+    use crate::config::NodeConfig;
+    use crate::executor::ownership::tests::reply_to_pings;
     use crate::id::Id;
     use crate::net::connection::AsyncTcpConnection;
     use crate::net::endpoint::Endpoint;
-    use crate::net::message::Message;
+    use crate::net::identity::Identity;
+    use crate::net::message::{Message, MessageTypes};
     use crate::net::node::Node;
+    use crate::net::secure::SecureChannel;
     use crate::net::wait::{WaitingList, WaitingListOptions};
+    use crate::net::AsyncNetwork;
     use crate::routing::Table;
+    use crate::server::limit::{RateLimit, RateLimitOptions, RateLimiter, ThrottleScope};
     use crate::server::AsyncConnectionHandler;
     use crate::store::{InMemoryStore, Store};
     use crate::time::SystemClock;
@@ -105,14 +221,24 @@ mod tests {
         let routing_table = Table::new(node_id);
         let routing_table_clone = routing_table.clone();
 
+        let waiting_list = waiting_list();
+        let waiting_list_clone = waiting_list.clone();
+
         let handle = tokio::spawn(async move {
             let tcp_listener = listener_result.unwrap();
             let stream = tcp_listener.accept().await.unwrap();
 
             let connection = AsyncTcpConnection::new(stream.0);
 
-            let connection_handler =
-                AsyncConnectionHandler::new(node, store, waiting_list(), routing_table);
+            let connection_handler = AsyncConnectionHandler::new(
+                node,
+                Arc::new(Identity::from_bytes([7; 32])),
+                store,
+                AsyncNetwork::new(waiting_list_clone),
+                routing_table,
+                unlimited_rate_limiter(),
+                &NodeConfig::default(),
+            );
 
             connection_handler.handle(connection).await;
         });
@@ -121,10 +247,14 @@ mod tests {
         let connection_result = AsyncTcpConnection::establish_with(&endpoint).await;
         assert!(connection_result.is_ok());
 
-        let source_node = Node::new(Endpoint::new("localhost".to_string(), 8787));
+        let identity = Identity::from_bytes([1; 32]);
+        let source_node =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 8787), &identity);
+        let reply_handle = reply_to_pings(source_node.clone(), identity, waiting_list, 1).await;
         let store_message = Message::store_type(
             "kademlia".as_bytes().to_vec(),
             "distributed hash table".as_bytes().to_vec(),
+            1,
             source_node.clone(),
         );
 
@@ -133,6 +263,7 @@ mod tests {
         assert!(connection_write_result.is_ok());
 
         handle.await.unwrap();
+        reply_handle.await.unwrap();
         thread::sleep(Duration::from_millis(100));
 
         let value = store_clone.get(&"kademlia".as_bytes().to_vec());
@@ -146,6 +277,207 @@ mod tests {
         assert!(contains);
     }
 
+    #[tokio::test]
+    async fn reject_message_with_source_other_than_the_authenticated_node() {
+        let listener_result = TcpListener::bind("localhost:6161").await;
+        assert!(listener_result.is_ok());
+
+        let node = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 9090),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        );
+        let node_id = node.node_id();
+
+        let store = Arc::new(InMemoryStore::new());
+        let store_clone = store.clone();
+
+        let routing_table = Table::new(node_id);
+        let routing_table_clone = routing_table.clone();
+
+        let server_identity = Arc::new(Identity::from_bytes([7; 32]));
+        let server_channel = SecureChannel::new(server_identity.clone()).unwrap();
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let stream = tcp_listener.accept().await.unwrap();
+
+            let connection_handler = AsyncConnectionHandler::new(
+                node,
+                server_identity,
+                store,
+                AsyncNetwork::new_secure(waiting_list(), server_channel),
+                routing_table,
+                unlimited_rate_limiter(),
+                &NodeConfig::default(),
+            );
+
+            connection_handler.accept(stream.0).await;
+        });
+
+        let client_channel = SecureChannel::new(Arc::new(Identity::from_bytes([1; 32]))).unwrap();
+        let mut connection = AsyncTcpConnection::establish_secure_with(
+            &Endpoint::new("localhost".to_string(), 6161),
+            &client_channel,
+        )
+        .await
+        .unwrap();
+
+        let impersonated_node = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 8787),
+            &Identity::from_bytes([3; 32]),
+        );
+        let store_message = Message::store_type(
+            "kademlia".as_bytes().to_vec(),
+            "distributed hash table".as_bytes().to_vec(),
+            1,
+            impersonated_node.clone(),
+        );
+        let connection_write_result = connection.write(&store_message).await;
+        assert!(connection_write_result.is_ok());
+
+        handle.await.unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        assert!(store_clone.get("kademlia".as_bytes()).is_none());
+
+        let (_, contains) = routing_table_clone.contains(&impersonated_node);
+        assert!(!contains);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn drop_messages_from_a_throttled_source() {
+        let listener_result = TcpListener::bind("localhost:6162").await;
+        assert!(listener_result.is_ok());
+
+        let node = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 9090),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        );
+        let node_id = node.node_id();
+
+        let store = Arc::new(InMemoryStore::new());
+        let store_clone = store.clone();
+
+        let waiting_list = waiting_list();
+        let waiting_list_clone = waiting_list.clone();
+
+        let per_source = HashMap::from([(MessageTypes::Store, RateLimit::new(1, 1))]);
+        let rate_limiter = Arc::new(RateLimiter::new(
+            RateLimitOptions::new(per_source, HashMap::new()),
+            SystemClock::new(),
+        ));
+        let rate_limiter_clone = rate_limiter.clone();
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let connection_handler = AsyncConnectionHandler::new(
+                node,
+                Arc::new(Identity::from_bytes([7; 32])),
+                store,
+                AsyncNetwork::new(waiting_list_clone),
+                Table::new(node_id),
+                rate_limiter_clone,
+                &NodeConfig::default(),
+            );
+            for _ in 0..2 {
+                let stream = tcp_listener.accept().await.unwrap();
+                connection_handler.accept(stream.0).await;
+            }
+        });
+
+        let identity = Identity::from_bytes([1; 32]);
+        let source_node =
+            Node::new_with_identity(Endpoint::new("localhost".to_string(), 6163), &identity);
+        let reply_handle = reply_to_pings(source_node.clone(), identity, waiting_list, 1).await;
+
+        let endpoint = Endpoint::new("localhost".to_string(), 6162);
+        for key in ["kademlia", "distributed hash table"] {
+            let mut connection = AsyncTcpConnection::establish_with(&endpoint).await.unwrap();
+            let store_message = Message::store_type(
+                key.as_bytes().to_vec(),
+                "value".as_bytes().to_vec(),
+                1,
+                source_node.clone(),
+            );
+            assert!(connection.write(&store_message).await.is_ok());
+        }
+
+        handle.await.unwrap();
+        reply_handle.await.unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        assert!(store_clone.get("kademlia".as_bytes()).is_some());
+        assert!(store_clone
+            .get("distributed hash table".as_bytes())
+            .is_none());
+        assert_eq!(
+            1,
+            rate_limiter.throttled_count(MessageTypes::Store, ThrottleScope::Source)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn do_not_mark_an_unauthenticated_source_as_seen() {
+        let listener_result = TcpListener::bind("localhost:6182").await;
+        assert!(listener_result.is_ok());
+
+        let node = Node::new_with_id(
+            Endpoint::new("localhost".to_string(), 9090),
+            Id::new(255u16.to_be_bytes().to_vec()),
+        );
+        let routing_table = Table::new(node.node_id());
+        let routing_table_clone = routing_table.clone();
+
+        let source_node = Node::new_with_identity(
+            Endpoint::new("localhost".to_string(), 6183),
+            &Identity::from_bytes([1; 32]),
+        );
+        routing_table.add(source_node.clone());
+        routing_table.mark_failed(&source_node);
+
+        let handle = tokio::spawn(async move {
+            let tcp_listener = listener_result.unwrap();
+            let stream = tcp_listener.accept().await.unwrap();
+
+            let connection_handler = AsyncConnectionHandler::new(
+                node,
+                Arc::new(Identity::from_bytes([7; 32])),
+                Arc::new(InMemoryStore::new()),
+                AsyncNetwork::new(waiting_list()),
+                routing_table,
+                unlimited_rate_limiter(),
+                &NodeConfig::default(),
+            );
+
+            connection_handler.accept(stream.0).await;
+        });
+
+        let mut connection =
+            AsyncTcpConnection::establish_with(&Endpoint::new("localhost".to_string(), 6182))
+                .await
+                .unwrap();
+        let store_message = Message::store_type(
+            "kademlia".as_bytes().to_vec(),
+            "distributed hash table".as_bytes().to_vec(),
+            1,
+            source_node.clone(),
+        );
+        assert!(connection.write(&store_message).await.is_ok());
+
+        handle.await.unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let liveness = routing_table_clone.liveness_of(&source_node.id).unwrap();
+        assert_eq!(1, liveness.consecutive_failures);
+    }
+
+    fn unlimited_rate_limiter() -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(
+            RateLimitOptions::unlimited(),
+            SystemClock::new(),
+        ))
+    }
+
     fn waiting_list() -> Arc<WaitingList> {
         WaitingList::new(
             WaitingListOptions::new(Duration::from_secs(120), Duration::from_millis(100)),
@@ -1,9 +1,19 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::time::Duration;
 
 use log::debug;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::id::Id;
+use crate::store::record::{immutable_key, RecordKind};
+use crate::time::{Clock, SystemClock};
+
+pub(crate) mod record;
+
+const TOMBSTONE_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_TOMBSTONE_CLOCK_SKEW: Duration = Duration::from_secs(10 * 60);
 
 pub(crate) type KeyId = Id;
 
@@ -20,64 +30,288 @@ impl Key {
     pub(crate) fn new_with_id(key: Vec<u8>, id: KeyId) -> Self {
         Key { id, key }
     }
+    #[cfg(test)]
     pub(crate) fn length_key_id(&self) -> usize {
         self.id.len()
     }
 }
 
+pub(crate) type Version = u64;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct VersionedValue {
+    pub(crate) value: Vec<u8>,
+    pub(crate) version: Version,
+    pub(crate) kind: RecordKind,
+}
+
+impl VersionedValue {
+    pub(crate) fn new(value: Vec<u8>, version: Version) -> Self {
+        Self::new_with_kind(value, version, RecordKind::Plain)
+    }
+
+    pub(crate) fn new_with_kind(value: Vec<u8>, version: Version, kind: RecordKind) -> Self {
+        VersionedValue {
+            value,
+            version,
+            kind,
+        }
+    }
+
+    pub(crate) fn verify(&self, key: &[u8]) -> bool {
+        match &self.kind {
+            RecordKind::Plain => true,
+            RecordKind::Mutable(proof) => proof.verify(key, self.version, &self.value),
+            RecordKind::Immutable => immutable_key(&self.value) == key,
+        }
+    }
+
+    pub(crate) fn is_mutable(&self) -> bool {
+        matches!(self.kind, RecordKind::Mutable(_))
+    }
+
+    pub(crate) fn is_immutable(&self) -> bool {
+        matches!(self.kind, RecordKind::Immutable)
+    }
+
+    pub(crate) fn is_plain(&self) -> bool {
+        matches!(self.kind, RecordKind::Plain)
+    }
+
+    // a delete is unsigned, so a tombstone only shadows plain values. Signed and
+    // content-addressed records verify against their key and cannot be deleted.
+    pub(crate) fn is_deleted_by(&self, deleted_at: u64) -> bool {
+        self.is_plain() && deleted_at >= self.version
+    }
+
+    pub(crate) fn is_newer_than(&self, other: &VersionedValue) -> bool {
+        self.version > other.version || (self.version == other.version && self.value > other.value)
+    }
+}
+
 pub(crate) struct StoredValue {
     pub(crate) key_id: KeyId,
-    pub(crate) value: Vec<u8>,
+    pub(crate) value: VersionedValue,
 }
 
 impl StoredValue {
-    pub(crate) fn new(key_id: KeyId, value: Vec<u8>) -> Self {
+    pub(crate) fn new(key_id: KeyId, value: VersionedValue) -> Self {
         StoredValue { key_id, value }
     }
+    #[cfg(test)]
     pub(crate) fn clone_value(&self) -> Vec<u8> {
-        self.value.clone()
+        self.value.value.clone()
+    }
+}
+
+pub(crate) struct Tombstone {
+    pub(crate) deleted_at: u64,
+}
+
+impl Tombstone {
+    pub(crate) fn new(deleted_at: u64) -> Self {
+        Tombstone { deleted_at }
+    }
+
+    fn has_expired(&self, clock: &dyn Clock, expiry_after: &Duration) -> bool {
+        let expiry_after = u64::try_from(expiry_after.as_millis()).unwrap_or(u64::MAX);
+        let expires_at = self.deleted_at.saturating_add(expiry_after);
+        clock.now_millis() > expires_at
     }
 }
 
+#[derive(Serialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct StoreStats {
+    pub(crate) keys: usize,
+    pub(crate) tombstones: usize,
+    pub(crate) value_bytes: usize,
+}
+
 pub(crate) trait Store: Send + Sync {
-    fn put_or_update(&self, key: Key, value: Vec<u8>);
+    fn put_or_update(&self, key: Key, value: VersionedValue);
     fn delete(&self, key: &[u8]);
+    fn delete_with_tombstone(&self, key: Key, deleted_at: u64);
+    #[cfg(test)]
     fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    fn get_versioned(&self, key: &[u8]) -> Option<VersionedValue>;
+    fn deleted_at(&self, key: &[u8]) -> Option<u64>;
+    #[cfg(test)]
+    fn is_tombstoned(&self, key: &[u8]) -> bool;
+    fn stats(&self) -> StoreStats;
+    fn all_values(&self) -> Vec<(Key, VersionedValue)>;
+    fn all_tombstones(&self) -> Vec<(Key, u64)>;
 }
 
 pub(crate) struct InMemoryStore {
     value_by_key: RefCell<HashMap<Vec<u8>, StoredValue>>,
+    tombstone_by_key: RefCell<HashMap<Vec<u8>, Tombstone>>,
+    tombstone_expiry: Duration,
+    clock: Box<dyn Clock>,
 }
 
 impl InMemoryStore {
     pub(crate) fn new() -> Self {
+        Self::new_with_tombstone_expiry(TOMBSTONE_EXPIRY, SystemClock::new())
+    }
+
+    pub(crate) fn new_with_tombstone_expiry(
+        tombstone_expiry: Duration,
+        clock: Box<dyn Clock>,
+    ) -> Self {
         InMemoryStore {
             value_by_key: RefCell::new(HashMap::new()),
+            tombstone_by_key: RefCell::new(HashMap::new()),
+            tombstone_expiry,
+            clock,
         }
     }
+
+    fn tombstone_deleted_at(&self, key: &[u8]) -> Option<u64> {
+        let mut tombstone_by_key = self.tombstone_by_key.borrow_mut();
+        let (deleted_at, expired) = match tombstone_by_key.get(key) {
+            None => return None,
+            Some(tombstone) => (
+                tombstone.deleted_at,
+                tombstone.has_expired(self.clock.as_ref(), &self.tombstone_expiry),
+            ),
+        };
+        if expired {
+            tombstone_by_key.remove(key);
+            return None;
+        }
+        Some(deleted_at)
+    }
 }
 
 impl Store for InMemoryStore {
-    fn put_or_update(&self, key: Key, value: Vec<u8>) {
+    fn put_or_update(&self, key: Key, value: VersionedValue) {
+        if let Some(deleted_at) = self.tombstone_deleted_at(&key.key) {
+            if value.is_deleted_by(deleted_at) {
+                debug!(
+                    "ignoring the key/value pair in InMemoryStore, the key is tombstoned. The key id is {:?}",
+                    key.id
+                );
+                return;
+            }
+        }
+        let mut value_by_key = self.value_by_key.borrow_mut();
+        if let Some(stored_value) = value_by_key.get(&key.key) {
+            if !value.is_newer_than(&stored_value.value) {
+                debug!(
+                    "ignoring the key/value pair in InMemoryStore, a newer version is stored. The key id is {:?}",
+                    key.id
+                );
+                return;
+            }
+        }
         debug!(
             "storing the key/value pair in InMemoryStore. The key id is {:?}",
             key.id
         );
-        self.value_by_key
-            .borrow_mut()
-            .insert(key.key, StoredValue::new(key.id, value));
+        value_by_key.insert(key.key, StoredValue::new(key.id, value));
     }
 
     fn delete(&self, key: &[u8]) {
         self.value_by_key.borrow_mut().remove_entry(key);
     }
 
+    fn delete_with_tombstone(&self, key: Key, deleted_at: u64) {
+        // deleted_at comes from the network, a tombstone dated far in the future would
+        // block every later write to the key.
+        let latest_accepted = self
+            .clock
+            .now_millis()
+            .saturating_add(MAX_TOMBSTONE_CLOCK_SKEW.as_millis() as u64);
+        if deleted_at > latest_accepted {
+            debug!(
+                "ignoring the tombstone in InMemoryStore, it is dated in the future. The key id is {:?}",
+                key.id
+            );
+            return;
+        }
+        debug!(
+            "deleting the key in InMemoryStore with a tombstone. The key id is {:?}",
+            key.id
+        );
+        {
+            let mut value_by_key = self.value_by_key.borrow_mut();
+            let stored_value_is_older = value_by_key
+                .get(&key.key)
+                .is_some_and(|stored_value| stored_value.value.is_deleted_by(deleted_at));
+            if stored_value_is_older {
+                value_by_key.remove(&key.key);
+            }
+        }
+
+        let mut tombstone_by_key = self.tombstone_by_key.borrow_mut();
+        let newer_tombstone_exists = tombstone_by_key
+            .get(&key.key)
+            .is_some_and(|tombstone| tombstone.deleted_at >= deleted_at);
+
+        if !newer_tombstone_exists {
+            tombstone_by_key.insert(key.key, Tombstone::new(deleted_at));
+        }
+    }
+
+    #[cfg(test)]
     fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
         let value_by_key = self.value_by_key.borrow();
         value_by_key
             .get(key)
             .map(|stored_value| stored_value.clone_value())
     }
+
+    fn get_versioned(&self, key: &[u8]) -> Option<VersionedValue> {
+        let value_by_key = self.value_by_key.borrow();
+        value_by_key
+            .get(key)
+            .map(|stored_value| stored_value.value.clone())
+    }
+
+    fn deleted_at(&self, key: &[u8]) -> Option<u64> {
+        self.tombstone_deleted_at(key)
+    }
+
+    #[cfg(test)]
+    fn is_tombstoned(&self, key: &[u8]) -> bool {
+        self.tombstone_deleted_at(key).is_some()
+    }
+
+    fn all_values(&self) -> Vec<(Key, VersionedValue)> {
+        self.value_by_key
+            .borrow()
+            .iter()
+            .map(|(key, stored_value)| {
+                (
+                    Key::new_with_id(key.clone(), stored_value.key_id.clone()),
+                    stored_value.value.clone(),
+                )
+            })
+            .collect()
+    }
+
+    fn all_tombstones(&self) -> Vec<(Key, u64)> {
+        let mut tombstone_by_key = self.tombstone_by_key.borrow_mut();
+        tombstone_by_key.retain(|_, tombstone| {
+            !tombstone.has_expired(self.clock.as_ref(), &self.tombstone_expiry)
+        });
+        tombstone_by_key
+            .iter()
+            .map(|(key, tombstone)| (Key::new(key.clone()), tombstone.deleted_at))
+            .collect()
+    }
+
+    fn stats(&self) -> StoreStats {
+        let value_by_key = self.value_by_key.borrow();
+        StoreStats {
+            keys: value_by_key.len(),
+            tombstones: self.tombstone_by_key.borrow().len(),
+            value_bytes: value_by_key
+                .values()
+                .map(|stored_value| stored_value.value.value.len())
+                .sum(),
+        }
+    }
 }
 
 unsafe impl Send for InMemoryStore {}
@@ -86,8 +320,14 @@ unsafe impl Sync for InMemoryStore {}
 
 #[cfg(test)]
 mod tests {
-    use crate::id::EXPECTED_ID_LENGTH_IN_BYTES;
-    use crate::store::{InMemoryStore, Key, Store};
+    use std::time::Duration;
+
+    use ed25519_dalek::SigningKey;
+
+    use crate::id::{Id, EXPECTED_ID_LENGTH_IN_BYTES};
+    use crate::store::record::MutableRecord;
+    use crate::store::{InMemoryStore, Key, Store, StoreStats, VersionedValue};
+    use crate::time::{Clock, SystemClock};
 
     #[test]
     fn key_with_id_and_content() {
@@ -103,7 +343,7 @@ mod tests {
         let key = "kademlia".as_bytes().to_vec();
         let value = "distributed hash table".as_bytes().to_vec();
 
-        store.put_or_update(Key::new(key), value);
+        store.put_or_update(Key::new(key), VersionedValue::new(value, 1));
 
         let query_key = "kademlia".as_bytes();
         let stored_value = store.get(query_key);
@@ -121,16 +361,35 @@ mod tests {
         assert_eq!(expected_value, stored_value.unwrap())
     }
 
+    #[test]
+    fn stats_of_the_store() {
+        let store = InMemoryStore::new();
+        store.put_or_update(
+            Key::new(b"kademlia".to_vec()),
+            VersionedValue::new(b"distributed hash table".to_vec(), 1),
+        );
+        store.delete_with_tombstone(Key::new(b"chord".to_vec()), 2);
+
+        assert_eq!(
+            StoreStats {
+                keys: 1,
+                tombstones: 1,
+                value_bytes: 22
+            },
+            store.stats()
+        );
+    }
+
     #[test]
     fn update_the_value_for_an_existing_key() {
         let store = InMemoryStore::new();
         let key = "kademlia".as_bytes().to_vec();
         let value = "distributed hash table".as_bytes().to_vec();
 
-        store.put_or_update(Key::new(key.clone()), value);
+        store.put_or_update(Key::new(key.clone()), VersionedValue::new(value, 1));
 
         let updated_value = "hash table".as_bytes().to_vec();
-        store.put_or_update(Key::new(key), updated_value);
+        store.put_or_update(Key::new(key), VersionedValue::new(updated_value, 2));
 
         let query_key = "kademlia".as_bytes();
         let stored_value = store.get(query_key);
@@ -171,7 +430,7 @@ mod tests {
         let key = "kademlia".as_bytes().to_vec();
         let value = "distributed hash table".as_bytes().to_vec();
 
-        store.put_or_update(Key::new(key), value);
+        store.put_or_update(Key::new(key), VersionedValue::new(value, 1));
 
         let key_to_delete = "kademlia".as_bytes();
         store.delete(key_to_delete);
@@ -186,4 +445,211 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn delete_the_value_with_a_tombstone() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+        store.delete_with_tombstone(Key::new(key.clone()), SystemClock::new().now_millis());
+
+        assert!(store.get(&key).is_none());
+        assert!(store.is_tombstoned(&key));
+    }
+
+    #[test]
+    fn ignore_the_value_for_a_tombstoned_key() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.delete_with_tombstone(Key::new(key.clone()), SystemClock::new().now_millis());
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+
+        assert!(store.get(&key).is_none());
+    }
+
+    #[test]
+    fn accept_the_value_after_the_tombstone_expires() {
+        let store = InMemoryStore::new_with_tombstone_expiry(
+            Duration::from_millis(100),
+            SystemClock::new(),
+        );
+        let key = "kademlia".as_bytes().to_vec();
+
+        let deleted_at = SystemClock::new().now_millis() - 200;
+        store.delete_with_tombstone(Key::new(key.clone()), deleted_at);
+        assert!(!store.is_tombstoned(&key));
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+        assert!(store.get(&key).is_some());
+    }
+
+    #[test]
+    fn list_all_values() {
+        let store = InMemoryStore::new();
+        store.put_or_update(
+            Key::new("kademlia".as_bytes().to_vec()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+
+        let values = store.all_values();
+        assert_eq!(1, values.len());
+        let (key, value) = &values[0];
+        assert_eq!("kademlia".as_bytes().to_vec(), key.key);
+        assert_eq!(Id::generate_from_bytes("kademlia".as_bytes()), key.id);
+        assert_eq!(1, value.version);
+    }
+
+    #[test]
+    fn list_the_unexpired_tombstones() {
+        let store =
+            InMemoryStore::new_with_tombstone_expiry(Duration::from_secs(60), SystemClock::new());
+        let now = SystemClock::new().now_millis();
+        store.delete_with_tombstone(Key::new("kademlia".as_bytes().to_vec()), now);
+        store.delete_with_tombstone(
+            Key::new("distributed hash table".as_bytes().to_vec()),
+            now - 120_000,
+        );
+
+        let tombstones = store.all_tombstones();
+        assert_eq!(1, tombstones.len());
+        let (key, deleted_at) = &tombstones[0];
+        assert_eq!("kademlia".as_bytes().to_vec(), key.key);
+        assert_eq!(now, *deleted_at);
+        assert_eq!(1, store.stats().tombstones);
+    }
+
+    #[test]
+    fn store_a_mutable_record_over_a_tombstone() {
+        let store = InMemoryStore::new();
+        let record = MutableRecord::sign(
+            &SigningKey::from_bytes(&[7; 32]),
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        store.delete_with_tombstone(
+            Key::new(record.key.clone()),
+            SystemClock::new().now_millis(),
+        );
+
+        store.put_or_update(Key::new(record.key.clone()), record.value);
+        assert_eq!(
+            Some("distributed hash table".as_bytes().to_vec()),
+            store.get(&record.key)
+        );
+    }
+
+    #[test]
+    fn ignore_a_tombstone_dated_in_the_future() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.delete_with_tombstone(Key::new(key.clone()), u64::MAX);
+        assert!(!store.is_tombstoned(&key));
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+        assert!(store.get(&key).is_some());
+    }
+
+    #[test]
+    fn do_not_overflow_the_expiry_of_a_late_tombstone() {
+        let store = InMemoryStore::new_with_tombstone_expiry(
+            Duration::from_millis(u64::MAX),
+            SystemClock::new(),
+        );
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.delete_with_tombstone(Key::new(key.clone()), SystemClock::new().now_millis());
+        assert!(store.is_tombstoned(&key));
+    }
+
+    #[test]
+    fn ignore_an_older_version_of_the_value() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("hash table".as_bytes().to_vec(), 2),
+        );
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), 1),
+        );
+
+        let stored_value = store.get_versioned(&key).unwrap();
+        assert_eq!("hash table".as_bytes().to_vec(), stored_value.value);
+        assert_eq!(2, stored_value.version);
+    }
+
+    #[test]
+    fn resolve_the_same_version_deterministically() {
+        let store = InMemoryStore::new();
+        let other_store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("a".as_bytes().to_vec(), 1),
+        );
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("b".as_bytes().to_vec(), 1),
+        );
+
+        other_store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("b".as_bytes().to_vec(), 1),
+        );
+        other_store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("a".as_bytes().to_vec(), 1),
+        );
+
+        assert_eq!(store.get(&key), other_store.get(&key));
+    }
+
+    #[test]
+    fn retain_a_value_newer_than_the_tombstone() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+        let deleted_at = SystemClock::new().now_millis();
+
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), deleted_at + 1),
+        );
+        store.delete_with_tombstone(Key::new(key.clone()), deleted_at);
+
+        assert!(store.get(&key).is_some());
+    }
+
+    #[test]
+    fn accept_a_value_newer_than_the_tombstone() {
+        let store = InMemoryStore::new();
+        let key = "kademlia".as_bytes().to_vec();
+        let deleted_at = SystemClock::new().now_millis();
+
+        store.delete_with_tombstone(Key::new(key.clone()), deleted_at);
+        store.put_or_update(
+            Key::new(key.clone()),
+            VersionedValue::new("distributed hash table".as_bytes().to_vec(), deleted_at + 1),
+        );
+
+        assert!(store.get(&key).is_some());
+    }
 }
@@ -0,0 +1,258 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::id::Id;
+use crate::store::{Version, VersionedValue};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum RecordKind {
+    Plain,
+    Mutable(MutableRecordProof),
+    Immutable,
+}
+
+impl RecordKind {
+    pub(crate) fn record_type(&self) -> RecordType {
+        match self {
+            RecordKind::Plain => RecordType::Plain,
+            RecordKind::Mutable(_) => RecordType::Mutable,
+            RecordKind::Immutable => RecordType::Immutable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RecordType {
+    Plain,
+    Mutable,
+    Immutable,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct MutableRecordProof {
+    pub(crate) public_key: Vec<u8>,
+    pub(crate) salt: Option<Vec<u8>>,
+    pub(crate) signature: Vec<u8>,
+}
+
+impl MutableRecordProof {
+    pub(crate) fn verify(&self, key: &[u8], sequence: Version, value: &[u8]) -> bool {
+        if mutable_key(&self.public_key, &self.salt) != key {
+            return false;
+        }
+        let public_key: [u8; 32] = match self.public_key.as_slice().try_into() {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+        let verifying_key = match VerifyingKey::from_bytes(&public_key) {
+            Ok(verifying_key) => verifying_key,
+            Err(_) => return false,
+        };
+        let signature = match Signature::from_slice(&self.signature) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        verifying_key
+            .verify(&signable(&self.salt, sequence, value), &signature)
+            .is_ok()
+    }
+}
+
+pub(crate) struct MutableRecord {
+    pub(crate) key: Vec<u8>,
+    pub(crate) value: VersionedValue,
+}
+
+impl MutableRecord {
+    pub(crate) fn sign(
+        signing_key: &SigningKey,
+        salt: Option<Vec<u8>>,
+        sequence: Version,
+        value: Vec<u8>,
+    ) -> Self {
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        let signature = signing_key.sign(&signable(&salt, sequence, &value));
+
+        let key = mutable_key(&public_key, &salt);
+        let proof = MutableRecordProof {
+            public_key,
+            salt,
+            signature: signature.to_bytes().to_vec(),
+        };
+        MutableRecord {
+            key,
+            value: VersionedValue::new_with_kind(value, sequence, RecordKind::Mutable(proof)),
+        }
+    }
+}
+
+pub(crate) struct ImmutableRecord {
+    pub(crate) key: Vec<u8>,
+    pub(crate) value: VersionedValue,
+}
+
+impl ImmutableRecord {
+    pub(crate) fn new(value: Vec<u8>) -> Self {
+        ImmutableRecord {
+            key: immutable_key(&value),
+            value: VersionedValue::new_with_kind(value, 0, RecordKind::Immutable),
+        }
+    }
+}
+
+pub(crate) fn immutable_key(value: &[u8]) -> Vec<u8> {
+    Id::generate_from_bytes(value).as_bytes().to_vec()
+}
+
+pub(crate) fn mutable_key(public_key: &[u8], salt: &Option<Vec<u8>>) -> Vec<u8> {
+    let mut key = public_key.to_vec();
+    if let Some(salt) = salt {
+        key.extend_from_slice(salt);
+    }
+    key
+}
+
+fn signable(salt: &Option<Vec<u8>>, sequence: Version, value: &[u8]) -> Vec<u8> {
+    let mut signable = Vec::new();
+    if let Some(salt) = salt {
+        signable.extend_from_slice(format!("4:salt{}:", salt.len()).as_bytes());
+        signable.extend_from_slice(salt);
+    }
+    signable.extend_from_slice(format!("3:seqi{}e1:v{}:", sequence, value.len()).as_bytes());
+    signable.extend_from_slice(value);
+    signable
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use crate::store::record::{
+        immutable_key, mutable_key, ImmutableRecord, MutableRecord, RecordKind,
+    };
+
+    #[test]
+    fn verify_a_signed_mutable_record() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let record = MutableRecord::sign(
+            &signing_key,
+            Some("salt".as_bytes().to_vec()),
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+
+        assert!(record.value.verify(&record.key));
+    }
+
+    #[test]
+    fn key_of_a_mutable_record_is_derived_from_public_key_and_salt() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let record = MutableRecord::sign(
+            &signing_key,
+            Some("salt".as_bytes().to_vec()),
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+
+        let public_key = signing_key.verifying_key().to_bytes().to_vec();
+        assert_eq!(
+            mutable_key(&public_key, &Some("salt".as_bytes().to_vec())),
+            record.key
+        );
+    }
+
+    #[test]
+    fn do_not_verify_a_mutable_record_with_a_tampered_value() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let mut record = MutableRecord::sign(
+            &signing_key,
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        record.value.value = "hash table".as_bytes().to_vec();
+
+        assert!(!record.value.verify(&record.key));
+    }
+
+    #[test]
+    fn do_not_verify_a_mutable_record_with_a_tampered_sequence() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let mut record = MutableRecord::sign(
+            &signing_key,
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        record.value.version = 2;
+
+        assert!(!record.value.verify(&record.key));
+    }
+
+    #[test]
+    fn do_not_verify_a_mutable_record_under_a_different_key() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let record = MutableRecord::sign(
+            &signing_key,
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+
+        assert!(!record.value.verify("kademlia".as_bytes()));
+    }
+
+    #[test]
+    fn do_not_verify_a_mutable_record_signed_by_another_key() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let other_signing_key = SigningKey::from_bytes(&[9; 32]);
+
+        let record = MutableRecord::sign(
+            &signing_key,
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+        let other_record = MutableRecord::sign(
+            &other_signing_key,
+            None,
+            1,
+            "distributed hash table".as_bytes().to_vec(),
+        );
+
+        let mut forged_value = record.value.clone();
+        if let (RecordKind::Mutable(proof), RecordKind::Mutable(other_proof)) =
+            (&mut forged_value.kind, &other_record.value.kind)
+        {
+            proof.signature = other_proof.signature.clone();
+        }
+        assert!(!forged_value.verify(&record.key));
+    }
+
+    #[test]
+    fn key_of_an_immutable_record_is_the_hash_of_its_value() {
+        let record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+
+        assert_eq!(
+            immutable_key("distributed hash table".as_bytes()),
+            record.key
+        );
+        assert!(record.value.verify(&record.key));
+    }
+
+    #[test]
+    fn do_not_verify_an_immutable_record_with_a_tampered_value() {
+        let mut record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+        record.value.value = "hash table".as_bytes().to_vec();
+
+        assert!(!record.value.verify(&record.key));
+    }
+
+    #[test]
+    fn do_not_verify_an_immutable_record_under_a_different_key() {
+        let record = ImmutableRecord::new("distributed hash table".as_bytes().to_vec());
+
+        assert!(!record.value.verify("kademlia".as_bytes()));
+    }
+}
@@ -14,6 +14,10 @@ pub trait Clock: Send + Sync + BoxedClockClone {
         self.now().duration_since(UNIX_EPOCH).unwrap().as_secs()
     }
 
+    fn now_millis(&self) -> u64 {
+        self.now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
     fn duration_since(&self, time: SystemTime) -> Duration {
         self.now().duration_since(time).unwrap()
     }